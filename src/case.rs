@@ -0,0 +1,277 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use arrayfire as af;
+use serde_json;
+use toml;
+use super::lbm::{self, Discretization, Geometry, Matrix, OperatorKind, Scalar};
+use super::lbm::{Edge, State, D2Q9};
+use super::convergence::{self, ConvergenceMonitor, Integral, Status};
+use super::schedule::{Inlet, Schedule};
+use super::spectral;
+use super::headless::{ImageSequence, ImageSettings};
+
+// -----------------------------------------------------------------------------
+
+/// The flow past a cylinder in a channel, described in units of the cylinder
+/// diameter and the inlet velocity, as read from a TOML case file.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct CaseFile {
+    pub name:             String,
+    /// The number of lattice nodes across the cylinder.
+    pub resolution:       usize,
+    /// The length and height of the channel.
+    pub domain:           (Scalar, Scalar),
+    /// The centre of the cylinder, from the lower left corner.
+    pub center:           (Scalar, Scalar),
+    /// The kinematic viscosity.
+    pub viscosity:        Scalar,
+    pub inlet_velocity:   Scalar,
+    /// The inlet velocity in lattice units, which sets the time step and so
+    /// the Mach number. The run itself is in lattice units.
+    #[serde(default = "default_lattice_velocity")]
+    pub lattice_velocity: Scalar,
+    pub operator:         OperatorKind,
+    /// The physical time to run for, unless the run converges first.
+    pub duration:         Scalar,
+    /// The time over which the inlet velocity ramps up from zero.
+    #[serde(default)]
+    pub ramp:             Scalar,
+    /// The tolerance of the convergence monitor.
+    #[serde(default = "default_tolerance")]
+    pub tolerance:        Scalar,
+    /// Images of the flow to write while it runs, in a subdirectory named
+    /// after the case.
+    #[serde(default)]
+    pub images:           Option<ImageSettings>,
+}
+
+fn default_lattice_velocity() -> Scalar { 0.05 }
+
+fn default_tolerance() -> Scalar { 1.0e-6 }
+
+const LATTICE_UNITS: Discretization = Discretization { delta_x: 1.0, delta_t: 1.0 };
+
+fn invalid<E: std::fmt::Display>(error: E) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, error.to_string())
+}
+
+impl CaseFile {
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(invalid)
+    }
+
+    pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
+        let text = toml::to_string(self).map_err(invalid)?;
+        std::fs::write(path, text)
+    }
+
+    pub fn reynolds(&self) -> Scalar {
+        self.inlet_velocity / self.viscosity
+    }
+
+    /// The viscosity that gives the case the given Reynolds number.
+    pub fn set_reynolds(&mut self, reynolds: Scalar) {
+        self.viscosity = self.inlet_velocity / reynolds;
+    }
+
+    /// The spacing and time step of the lattice in the units of the case,
+    /// to convert times to and from the steps of the run.
+    pub fn discretization(&self) -> Discretization {
+        let delta_x = 1.0 / (self.resolution as Scalar);
+        Discretization {
+            delta_x: delta_x,
+            delta_t: self.lattice_velocity * delta_x / self.inlet_velocity,
+        }
+    }
+
+    /// The kinematic viscosity in lattice units, at the same Reynolds number.
+    pub fn lattice_viscosity(&self) -> Scalar {
+        self.lattice_velocity * (self.resolution as Scalar) / self.reynolds()
+    }
+
+    /// The size of the lattice, including a wall row above and below the
+    /// channel.
+    pub fn size(&self) -> (usize, usize) {
+        let n = self.resolution as Scalar;
+        ((self.domain.0 * n).round() as usize, (self.domain.1 * n).round() as usize + 2)
+    }
+
+    fn mask<F: Fn(usize, usize) -> bool>(&self, f: F) -> Geometry {
+        let (w, h) = self.size();
+        let mut vec = Vec::with_capacity(w * h);
+        for y in 0 .. h {
+            for x in 0 .. w { vec.push(f(x, y)); }
+        }
+        let dim4 = af::Dim4::new(&[w as u64, h as u64, 1, 1]);
+        af::transpose(&af::Array::new(&vec[..], dim4), false)
+    }
+
+    /// The solid nodes of the cylinder alone.
+    pub fn cylinder(&self) -> Geometry {
+        let n = self.resolution as Scalar;
+        let (cx, cy) = (self.center.0 * n, self.center.1 * n + 1.0);
+        let radius = 0.5 * n;
+        self.mask(|x, y| {
+            let dx = (x as Scalar) + 0.5 - cx;
+            let dy = (y as Scalar) + 0.5 - cy;
+            dx * dx + dy * dy < radius * radius
+        })
+    }
+
+    /// The initial state, in lattice units: the fluid at rest, the channel
+    /// walls and the cylinder solid, an inlet on the first column and an
+    /// outlet on the last.
+    pub fn state(&self) -> State<D2Q9> {
+        let size = self.size();
+        let h = size.1;
+        let disc = LATTICE_UNITS;
+        let walls = self.mask(|_, y| (y == 0) || (y == h - 1));
+        let geometry = af::or(&walls, &self.cylinder(), false);
+
+        let zero = Matrix::new_filled(0.0, size);
+        let pops = lbm::compute_equilibrium(Matrix::new_filled(1.0, size),
+                                            (zero.clone(), zero),
+                                            &D2Q9::directions(), disc);
+        let mut state = State::initial(
+            Box::new(D2Q9::from_populations(pops)),
+            geometry,
+            self.operator.build(self.lattice_viscosity(), &disc),
+            disc,
+        );
+
+        let inlet = self.mask(|x, y| (x == 0) && (y != 0) && (y != h - 1));
+        let velocity = if self.ramp > 0.0 {
+            let steps = self.ramp / self.discretization().delta_t;
+            Schedule::Ramp { value: self.lattice_velocity, duration: steps }
+        } else {
+            Schedule::Constant(self.lattice_velocity)
+        };
+        state.inlets.push(Inlet::new(inlet, (velocity, Schedule::Constant(0.0))));
+        state.outlets.push(Edge::Right);
+        state
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// The scalar outputs of a finished run.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct CaseResult {
+    pub case:          CaseFile,
+    pub steps:         usize,
+    pub time:          Scalar,
+    /// "steady", "periodic", "unconverged" or "unstable".
+    pub status:        String,
+    /// The mean drag coefficient over the second half of the run.
+    pub drag:          Scalar,
+    /// The root mean square lift coefficient over the second half of the run.
+    pub lift:          Scalar,
+    /// The Strouhal number of the lift, if it oscillates.
+    pub strouhal:      Option<Scalar>,
+    /// Millions of lattice node updates per second.
+    pub mlups:         Scalar,
+    pub wall_seconds:  Scalar,
+}
+
+impl CaseResult {
+    pub fn save<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
+
+/// The integrals are checked this often.
+const CHECK_INTERVAL: usize = 50;
+
+pub fn run(case: &CaseFile) -> CaseResult {
+    let mut state = case.state();
+    let disc = case.discretization();
+    let (w, h) = state.size();
+
+    // The force is per lattice node of depth, in lattice units like the
+    // inlet velocity and the diameter.
+    let (u, n) = (case.lattice_velocity, case.resolution as Scalar);
+    let coefficient = 2.0 / (u * u * n);
+    let cylinder = case.cylinder();
+    // The lift is not tracked by the monitor: it is close to zero in steady
+    // flow, so its relative change never settles. The drag oscillates at
+    // twice the shedding frequency, which is just as good to detect it.
+    let mut monitor = ConvergenceMonitor::new(CHECK_INTERVAL, case.tolerance,
+                                              convergence::Action::Stop)
+        .track(Integral::drag(cylinder.clone()))
+        .detect_shedding();
+
+    let steps = (case.duration / disc.delta_t).ceil() as usize;
+    let start = std::time::Instant::now();
+    let mut taken = 0;
+    let mut unstable = false;
+    let mut lift = Vec::new();
+    let mut images = case.images.as_ref().and_then(|settings| {
+        ImageSequence::from_settings(settings, (w, h), &case.name)
+            .map_err(|e| println!("Case {}: not writing images: {}", case.name, e))
+            .ok()
+    });
+    while (taken < steps) && !monitor.should_stop() {
+        let failed = match images {
+            Some(ref mut sequence) => match sequence.record(&state) {
+                Ok(()) => false,
+                Err(e) => {
+                    println!("Case {}: stopped writing images: {}", case.name, e);
+                    true
+                },
+            },
+            None => false,
+        };
+        if failed { images = None; }
+        if taken % CHECK_INTERVAL == 0 {
+            lift.push(coefficient * state.momentum_exchange(&cylinder, (0.0, 0.0)).0.to_pair().1);
+            if state.is_unstable() {
+                unstable = true;
+                break;
+            }
+        }
+        state.step();
+        taken += 1;
+        monitor.check(&state);
+    }
+    let seconds = start.elapsed().as_float_secs();
+
+    let history = &monitor.history[(monitor.history.len() / 2) ..];
+    let count = std::cmp::max(history.len(), 1) as Scalar;
+    let drag = history.iter().map(|r| coefficient * r.integrals[0].0).sum::<Scalar>() / count;
+    let lift = &lift[(lift.len() / 2) ..];
+    let rms = (lift.iter().map(|l| l * l).sum::<Scalar>()
+               / (std::cmp::max(lift.len(), 1) as Scalar)).sqrt();
+    let strouhal = match monitor.status {
+        Status::Periodic { .. } if lift.len() >= 4 => {
+            let interval = CHECK_INTERVAL as Scalar;
+            spectral::dominant_frequency(&spectral::psd(lift, interval))
+                .map(|f| spectral::strouhal(f, n, u))
+        },
+        _ => None,
+    };
+
+    let status = match monitor.status {
+        _ if unstable           => "unstable",
+        Status::Running         => "unconverged",
+        Status::Steady          => "steady",
+        Status::Periodic { .. } => "periodic",
+    };
+
+    CaseResult {
+        case:         case.clone(),
+        steps:        taken,
+        time:         state.time * disc.delta_t,
+        status:       status.to_string(),
+        drag:         drag,
+        lift:         rms,
+        strouhal:     strouhal,
+        mlups:        ((w * h * taken) as f64 / seconds / 1.0e6) as Scalar,
+        wall_seconds: seconds as Scalar,
+    }
+}
+
+// -----------------------------------------------------------------------------
@@ -0,0 +1,384 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use arrayfire as af;
+use super::lbm::{Matrix, Scalar};
+
+// -----------------------------------------------------------------------------
+
+/// Perceptually uniform colour maps, after "A Better Default Colormap for
+/// Matplotlib" by van der Walt and Smith, and "Optimizing colormaps with
+/// consideration for color vision deficiency" by Nuñez et al. for cividis.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub enum Colormap {
+    Viridis,
+    Magma,
+    Cividis,
+    /// The diverging red to blue map of ColorBrewer, for signed fields such as
+    /// the vorticity.
+    RdBu,
+}
+
+impl Colormap {
+    pub fn all() -> [Colormap; 4] {
+        [Colormap::Viridis, Colormap::Magma, Colormap::Cividis, Colormap::RdBu]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Colormap::Viridis => "viridis",
+            Colormap::Magma   => "magma",
+            Colormap::Cividis => "cividis",
+            Colormap::RdBu    => "RdBu",
+        }
+    }
+
+    /// Whether the map is meant to be centred on zero.
+    pub fn is_diverging(&self) -> bool {
+        *self == Colormap::RdBu
+    }
+
+    pub fn next(&self) -> Colormap {
+        match *self {
+            Colormap::Viridis => Colormap::Magma,
+            Colormap::Magma   => Colormap::Cividis,
+            Colormap::Cividis => Colormap::RdBu,
+            Colormap::RdBu    => Colormap::Viridis,
+        }
+    }
+
+    /// Evenly spaced colours along the map, as `0xRRGGBB`: the published
+    /// tables of the perceptually uniform maps, and the eleven classes of
+    /// RdBu.
+    fn anchors(&self) -> &'static [u32] {
+        match *self {
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Magma   => &MAGMA,
+            Colormap::Cividis => &CIVIDIS,
+            Colormap::RdBu    => &[0x67001f, 0xb2182b, 0xd6604d, 0xf4a582, 0xfddbc7,
+                                   0xf7f7f7, 0xd1e5f0, 0x92c5de, 0x4393c3, 0x2166ac,
+                                   0x053061],
+        }
+    }
+
+    /// The colour at `t` in `[0, 1]`, interpolated linearly between anchors.
+    pub fn color(&self, t: Scalar) -> [Scalar; 3] {
+        let anchors = self.anchors();
+        let channel = |c: u32, k: u32| ((c >> (8 * (2 - k))) & 0xff) as Scalar / 255.0;
+        let position = t.max(0.0).min(1.0) * ((anchors.len() - 1) as Scalar);
+        let i = std::cmp::min(position.floor() as usize, anchors.len() - 2);
+        let s = position - (i as Scalar);
+        let mut result = [0.0; 3];
+        for k in 0 .. 3 {
+            let (a, b) = (channel(anchors[i], k as u32), channel(anchors[i + 1], k as u32));
+            result[k] = a + s * (b - a);
+        }
+        result
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// A colour map sampled into a table on the device.
+pub struct Lut {
+    pub colormap: Colormap,
+    /// One row per entry and one column per channel.
+    table:        af::Array<f32>,
+}
+
+impl Lut {
+    pub const DEFAULT_SIZE: usize = 256;
+
+    pub fn new(colormap: Colormap) -> Self {
+        Lut::with_size(colormap, Lut::DEFAULT_SIZE)
+    }
+
+    pub fn with_size(colormap: Colormap, size: usize) -> Self {
+        assert!(size >= 2, "a lookup table needs at least two entries");
+        // Column-major, so every red value comes first.
+        let mut data = vec![0.0; 3 * size];
+        for i in 0 .. size {
+            let c = colormap.color((i as Scalar) / ((size - 1) as Scalar));
+            for k in 0 .. 3 { data[k * size + i] = c[k]; }
+        }
+        let dims = af::Dim4::new(&[size as u64, 3, 1, 1]);
+        Lut { colormap: colormap, table: af::Array::new(&data, dims) }
+    }
+
+    pub fn size(&self) -> usize {
+        self.table.dims()[0] as usize
+    }
+
+    /// Map a field with values in `[0, 1]` to colours, as an array of the
+    /// same shape with the red, green and blue channels along the third
+    /// dimension. Values outside the range are clamped.
+    pub fn apply(&self, normalized: &Matrix) -> af::Array<f32> {
+        let array = normalized.get_array();
+        let dims = array.dims();
+        let last = (self.size() - 1) as f32;
+        let indices = af::clamp(array, &0.0f32, &1.0f32, true) * last + 0.5f32;
+        let indices = af::flat(&indices).cast::<u32>();
+        let colors = af::lookup(&self.table, &indices, 0);
+        af::moddims(&colors, af::Dim4::new(&[dims[0], dims[1], 3, 1]))
+    }
+}
+
+/// Pack an array of colours as returned by `Lut::apply`, with values in
+/// `[0, 1]`, into row-major RGBA bytes for `Drawable::set_pixels`, with a
+/// single copy from the device.
+pub fn to_rgba(rgb: &af::Array<f32>) -> Vec<u8> {
+    let dims = rgb.dims();
+    let alpha = af::constant(1.0f32, af::Dim4::new(&[dims[0], dims[1], 1, 1]));
+    let rgba = af::join(2, rgb, &alpha);
+    // Channels first, then columns (x), then rows (y).
+    let ordered = af::reorder(&rgba, af::Dim4::new(&[2, 1, 0, 3]));
+    let bytes = (af::clamp(&ordered, &0.0f32, &1.0f32, true) * 255.0f32 + 0.5f32)
+        .cast::<u8>();
+    let mut result = vec![0; (4 * dims[0] * dims[1]) as usize];
+    bytes.host(&mut result);
+    result
+}
+
+// -----------------------------------------------------------------------------
+
+/// How the values of a field are mapped onto a colour map.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Range {
+    /// The same limits on every frame, so that frames can be compared.
+    Fixed { min: Scalar, max: Scalar },
+    /// Limits of `-max` and `max`, or of the largest magnitude in the field
+    /// on each frame if no `max` is given.
+    Symmetric(Option<Scalar>),
+    /// The given percentiles of the field on each frame, which ignores a
+    /// few extreme nodes, e.g. next to a sharp corner.
+    Percentile { low: Scalar, high: Scalar },
+}
+
+impl Range {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Range::Fixed { .. }      => "fixed",
+            Range::Symmetric(_)      => "symmetric",
+            Range::Percentile { .. } => "percentile",
+        }
+    }
+}
+
+impl Default for Range {
+    fn default() -> Self {
+        Range::Percentile { low: 1.0, high: 99.0 }
+    }
+}
+
+/// A colour map together with the range of values it covers, shared by the
+/// live display and recordings, and drawn by `legend::draw_colorbar`.
+pub struct ColorScale {
+    pub lut:         Lut,
+    pub range:       Range,
+    /// Map the logarithm of the values, whose limits must then be positive.
+    pub logarithmic: bool,
+    pub label:       String,
+}
+
+impl ColorScale {
+    pub fn new(colormap: Colormap, range: Range) -> Self {
+        ColorScale {
+            lut:         Lut::new(colormap),
+            range:       range,
+            logarithmic: false,
+            label:       String::new(),
+        }
+    }
+
+    pub fn log_scaled(mut self) -> Self {
+        self.logarithmic = true;
+        self
+    }
+
+    pub fn labelled(mut self, label: &str) -> Self {
+        self.label = label.to_string();
+        self
+    }
+
+    pub fn set_colormap(&mut self, colormap: Colormap) {
+        self.lut = Lut::new(colormap);
+    }
+
+    /// The values mapped to either end of the colour map.
+    pub fn limits(&self, field: &Matrix) -> (Scalar, Scalar) {
+        let (min, max) = match self.range {
+            Range::Fixed { min, max } => (min, max),
+            Range::Symmetric(Some(max)) => (-max, max),
+            Range::Symmetric(None) => {
+                let max = af::max_all(&af::abs(field.get_array())).0 as Scalar;
+                (-max, max)
+            },
+            Range::Percentile { low, high } => percentiles(field, low, high),
+        };
+        if self.logarithmic {
+            // Clamp to a range of at most twelve decades below the maximum.
+            let max = max.max(std::f32::MIN_POSITIVE);
+            (min.max(max * 1.0e-12), max)
+        } else {
+            (min, max)
+        }
+    }
+
+    /// The field mapped into `[0, 1]` between the given limits.
+    pub fn normalize(&self, field: &Matrix, limits: (Scalar, Scalar)) -> Matrix {
+        let (min, max) = limits;
+        if self.logarithmic {
+            let (low, high) = (min.log10(), max.log10());
+            let clamped = af::clamp(field.get_array(), &min, &max, true);
+            Matrix::unsafe_new(af::log10(&clamped))
+                .shift(-low)
+                .scale(1.0 / (high - low).max(std::f32::MIN_POSITIVE))
+        } else {
+            field.shift(-min).scale(1.0 / (max - min).max(std::f32::MIN_POSITIVE))
+        }
+    }
+
+    /// The value at `t` in `[0, 1]` along the colour map, i.e. the inverse
+    /// of `normalize`.
+    pub fn value_at(&self, t: Scalar, limits: (Scalar, Scalar)) -> Scalar {
+        let (min, max) = limits;
+        if self.logarithmic {
+            10.0f32.powf(min.log10() + t * (max.log10() - min.log10()))
+        } else {
+            min + t * (max - min)
+        }
+    }
+
+    /// The colours of the field, as returned by `Lut::apply`, and the limits
+    /// they were mapped with.
+    pub fn apply(&self, field: &Matrix) -> (af::Array<f32>, (Scalar, Scalar)) {
+        let limits = self.limits(field);
+        (self.lut.apply(&self.normalize(field, limits)), limits)
+    }
+}
+
+/// The given percentiles of the values of a field, by sorting on the device.
+fn percentiles(field: &Matrix, low: Scalar, high: Scalar) -> (Scalar, Scalar) {
+    let (w, h) = field.get_shape();
+    let n = w * h;
+    let index = |p: Scalar| {
+        ((p.max(0.0).min(100.0) / 100.0) * ((n - 1) as Scalar)).round() as u32
+    };
+    let sorted = af::sort(&af::flat(field.get_array()), 0, true);
+    let indices = af::Array::new(&[index(low), index(high)], af::Dim4::new(&[2, 1, 1, 1]));
+    let mut values = [0.0; 2];
+    af::lookup(&sorted, &indices, 0).host(&mut values);
+    (values[0], values[1])
+}
+
+// -----------------------------------------------------------------------------
+
+/// The 256 entries of viridis, as published with matplotlib.
+static VIRIDIS: [u32; 256] = [
+    0x440154, 0x440256, 0x450457, 0x450559, 0x46075a, 0x46085c, 0x460a5d, 0x460b5e,
+    0x470d60, 0x470e61, 0x471063, 0x471164, 0x471365, 0x481467, 0x481668, 0x481769,
+    0x48186a, 0x481a6c, 0x481b6d, 0x481c6e, 0x481d6f, 0x481f70, 0x482071, 0x482173,
+    0x482374, 0x482475, 0x482576, 0x482677, 0x482878, 0x482979, 0x472a7a, 0x472c7a,
+    0x472d7b, 0x472e7c, 0x472f7d, 0x46307e, 0x46327e, 0x46337f, 0x463480, 0x453581,
+    0x453781, 0x453882, 0x443983, 0x443a83, 0x443b84, 0x433d84, 0x433e85, 0x423f85,
+    0x424086, 0x424186, 0x414287, 0x414487, 0x404588, 0x404688, 0x3f4788, 0x3f4889,
+    0x3e4989, 0x3e4a89, 0x3e4c8a, 0x3d4d8a, 0x3d4e8a, 0x3c4f8a, 0x3c508b, 0x3b518b,
+    0x3b528b, 0x3a538b, 0x3a548c, 0x39558c, 0x39568c, 0x38588c, 0x38598c, 0x375a8c,
+    0x375b8d, 0x365c8d, 0x365d8d, 0x355e8d, 0x355f8d, 0x34608d, 0x34618d, 0x33628d,
+    0x33638d, 0x32648e, 0x32658e, 0x31668e, 0x31678e, 0x31688e, 0x30698e, 0x306a8e,
+    0x2f6b8e, 0x2f6c8e, 0x2e6d8e, 0x2e6e8e, 0x2e6f8e, 0x2d708e, 0x2d718e, 0x2c718e,
+    0x2c728e, 0x2c738e, 0x2b748e, 0x2b758e, 0x2a768e, 0x2a778e, 0x2a788e, 0x29798e,
+    0x297a8e, 0x297b8e, 0x287c8e, 0x287d8e, 0x277e8e, 0x277f8e, 0x27808e, 0x26818e,
+    0x26828e, 0x26828e, 0x25838e, 0x25848e, 0x25858e, 0x24868e, 0x24878e, 0x23888e,
+    0x23898e, 0x238a8d, 0x228b8d, 0x228c8d, 0x228d8d, 0x218e8d, 0x218f8d, 0x21908d,
+    0x21918c, 0x20928c, 0x20928c, 0x20938c, 0x1f948c, 0x1f958b, 0x1f968b, 0x1f978b,
+    0x1f988b, 0x1f998a, 0x1f9a8a, 0x1e9b8a, 0x1e9c89, 0x1e9d89, 0x1f9e89, 0x1f9f88,
+    0x1fa088, 0x1fa188, 0x1fa187, 0x1fa287, 0x20a386, 0x20a486, 0x21a585, 0x21a685,
+    0x22a785, 0x22a884, 0x23a983, 0x24aa83, 0x25ab82, 0x25ac82, 0x26ad81, 0x27ad81,
+    0x28ae80, 0x29af7f, 0x2ab07f, 0x2cb17e, 0x2db27d, 0x2eb37c, 0x2fb47c, 0x31b57b,
+    0x32b67a, 0x34b679, 0x35b779, 0x37b878, 0x38b977, 0x3aba76, 0x3bbb75, 0x3dbc74,
+    0x3fbc73, 0x40bd72, 0x42be71, 0x44bf70, 0x46c06f, 0x48c16e, 0x4ac16d, 0x4cc26c,
+    0x4ec36b, 0x50c46a, 0x52c569, 0x54c568, 0x56c667, 0x58c765, 0x5ac864, 0x5cc863,
+    0x5ec962, 0x60ca60, 0x63cb5f, 0x65cb5e, 0x67cc5c, 0x69cd5b, 0x6ccd5a, 0x6ece58,
+    0x70cf57, 0x73d056, 0x75d054, 0x77d153, 0x7ad151, 0x7cd250, 0x7fd34e, 0x81d34d,
+    0x84d44b, 0x86d549, 0x89d548, 0x8bd646, 0x8ed645, 0x90d743, 0x93d741, 0x95d840,
+    0x98d83e, 0x9bd93c, 0x9dd93b, 0xa0da39, 0xa2da37, 0xa5db36, 0xa8db34, 0xaadc32,
+    0xaddc30, 0xb0dd2f, 0xb2dd2d, 0xb5de2b, 0xb8de29, 0xbade28, 0xbddf26, 0xc0df25,
+    0xc2df23, 0xc5e021, 0xc8e020, 0xcae11f, 0xcde11d, 0xd0e11c, 0xd2e21b, 0xd5e21a,
+    0xd8e219, 0xdae319, 0xdde318, 0xdfe318, 0xe2e418, 0xe5e419, 0xe7e419, 0xeae51a,
+    0xece51b, 0xefe51c, 0xf1e51d, 0xf4e61e, 0xf6e620, 0xf8e621, 0xfbe723, 0xfde725,
+];
+
+/// The 256 entries of magma, as published with matplotlib.
+static MAGMA: [u32; 256] = [
+    0x000004, 0x010005, 0x010106, 0x010108, 0x020109, 0x02020b, 0x02020d, 0x03030f,
+    0x030312, 0x040414, 0x050416, 0x060518, 0x06051a, 0x07061c, 0x08071e, 0x090720,
+    0x0a0822, 0x0b0924, 0x0c0926, 0x0d0a29, 0x0e0b2b, 0x100b2d, 0x110c2f, 0x120d31,
+    0x130d34, 0x140e36, 0x150e38, 0x160f3b, 0x180f3d, 0x19103f, 0x1a1042, 0x1c1044,
+    0x1d1147, 0x1e1149, 0x20114b, 0x21114e, 0x221150, 0x241253, 0x251255, 0x271258,
+    0x29115a, 0x2a115c, 0x2c115f, 0x2d1161, 0x2f1163, 0x311165, 0x331067, 0x341069,
+    0x36106b, 0x38106c, 0x390f6e, 0x3b0f70, 0x3d0f71, 0x3f0f72, 0x400f74, 0x420f75,
+    0x440f76, 0x451077, 0x471078, 0x491078, 0x4a1079, 0x4c117a, 0x4e117b, 0x4f127b,
+    0x51127c, 0x52137c, 0x54137d, 0x56147d, 0x57157e, 0x59157e, 0x5a167e, 0x5c167f,
+    0x5d177f, 0x5f187f, 0x601880, 0x621980, 0x641a80, 0x651a80, 0x671b80, 0x681c81,
+    0x6a1c81, 0x6b1d81, 0x6d1d81, 0x6e1e81, 0x701f81, 0x721f81, 0x732081, 0x752181,
+    0x762181, 0x782281, 0x792282, 0x7b2382, 0x7c2382, 0x7e2482, 0x802582, 0x812581,
+    0x832681, 0x842681, 0x862781, 0x882781, 0x892881, 0x8b2981, 0x8c2981, 0x8e2a81,
+    0x902a81, 0x912b81, 0x932b80, 0x942c80, 0x962c80, 0x982d80, 0x992d80, 0x9b2e7f,
+    0x9c2e7f, 0x9e2f7f, 0xa02f7f, 0xa1307e, 0xa3307e, 0xa5317e, 0xa6317d, 0xa8327d,
+    0xaa337d, 0xab337c, 0xad347c, 0xae347b, 0xb0357b, 0xb2357b, 0xb3367a, 0xb5367a,
+    0xb73779, 0xb83779, 0xba3878, 0xbc3978, 0xbd3977, 0xbf3a77, 0xc03a76, 0xc23b75,
+    0xc43c75, 0xc53c74, 0xc73d73, 0xc83e73, 0xca3e72, 0xcc3f71, 0xcd4071, 0xcf4070,
+    0xd0416f, 0xd2426f, 0xd3436e, 0xd5446d, 0xd6456c, 0xd8456c, 0xd9466b, 0xdb476a,
+    0xdc4869, 0xde4968, 0xdf4a68, 0xe04c67, 0xe24d66, 0xe34e65, 0xe44f64, 0xe55064,
+    0xe75263, 0xe85362, 0xe95462, 0xea5661, 0xeb5760, 0xec5860, 0xed5a5f, 0xee5b5e,
+    0xef5d5e, 0xf05f5e, 0xf1605d, 0xf2625d, 0xf2645c, 0xf3655c, 0xf4675c, 0xf4695c,
+    0xf56b5c, 0xf66c5c, 0xf66e5c, 0xf7705c, 0xf7725c, 0xf8745c, 0xf8765c, 0xf9785d,
+    0xf9795d, 0xf97b5d, 0xfa7d5e, 0xfa7f5e, 0xfa815f, 0xfb835f, 0xfb8560, 0xfb8761,
+    0xfc8961, 0xfc8a62, 0xfc8c63, 0xfc8e64, 0xfc9065, 0xfd9266, 0xfd9467, 0xfd9668,
+    0xfd9869, 0xfd9a6a, 0xfd9b6b, 0xfe9d6c, 0xfe9f6d, 0xfea16e, 0xfea36f, 0xfea571,
+    0xfea772, 0xfea973, 0xfeaa74, 0xfeac76, 0xfeae77, 0xfeb078, 0xfeb27a, 0xfeb47b,
+    0xfeb67c, 0xfeb77e, 0xfeb97f, 0xfebb81, 0xfebd82, 0xfebf84, 0xfec185, 0xfec287,
+    0xfec488, 0xfec68a, 0xfec88c, 0xfeca8d, 0xfecc8f, 0xfecd90, 0xfecf92, 0xfed194,
+    0xfed395, 0xfed597, 0xfed799, 0xfed89a, 0xfdda9c, 0xfddc9e, 0xfddea0, 0xfde0a1,
+    0xfde2a3, 0xfde3a5, 0xfde5a7, 0xfde7a9, 0xfde9aa, 0xfdebac, 0xfcecae, 0xfceeb0,
+    0xfcf0b2, 0xfcf2b4, 0xfcf4b6, 0xfcf6b8, 0xfcf7b9, 0xfcf9bb, 0xfcfbbd, 0xfcfdbf,
+];
+
+/// The 256 entries of cividis, as published by Nuñez et al.
+static CIVIDIS: [u32; 256] = [
+    0x002051, 0x002153, 0x002255, 0x002356, 0x002358, 0x002459, 0x00255a, 0x00255c,
+    0x00265d, 0x00275e, 0x00275f, 0x002860, 0x002961, 0x002962, 0x002a63, 0x002b64,
+    0x012b65, 0x022c65, 0x032d66, 0x042d67, 0x052e67, 0x052f68, 0x063069, 0x073069,
+    0x08316a, 0x09326a, 0x0b326a, 0x0c336b, 0x0d346b, 0x0e346b, 0x0f356c, 0x10366c,
+    0x12376c, 0x13376d, 0x14386d, 0x15396d, 0x17396d, 0x183a6d, 0x193b6d, 0x1a3b6d,
+    0x1c3c6e, 0x1d3d6e, 0x1e3e6e, 0x203e6e, 0x213f6e, 0x23406e, 0x24406e, 0x25416e,
+    0x27426e, 0x28436e, 0x29436e, 0x2b446e, 0x2c456e, 0x2e456e, 0x2f466e, 0x30476e,
+    0x32486e, 0x33486e, 0x34496e, 0x364a6e, 0x374a6e, 0x394b6e, 0x3a4c6e, 0x3b4d6e,
+    0x3d4d6e, 0x3e4e6e, 0x3f4f6e, 0x414f6e, 0x42506e, 0x43516d, 0x44526d, 0x46526d,
+    0x47536d, 0x48546d, 0x4a546d, 0x4b556d, 0x4c566d, 0x4d576d, 0x4e576e, 0x50586e,
+    0x51596e, 0x52596e, 0x535a6e, 0x545b6e, 0x565c6e, 0x575c6e, 0x585d6e, 0x595e6e,
+    0x5a5e6e, 0x5b5f6e, 0x5c606e, 0x5d616e, 0x5e616e, 0x60626e, 0x61636f, 0x62646f,
+    0x63646f, 0x64656f, 0x65666f, 0x66666f, 0x67676f, 0x686870, 0x696970, 0x6a6970,
+    0x6b6a70, 0x6c6b70, 0x6d6c70, 0x6d6c71, 0x6e6d71, 0x6f6e71, 0x706f71, 0x716f71,
+    0x727071, 0x737172, 0x747172, 0x757272, 0x767372, 0x767472, 0x777473, 0x787573,
+    0x797673, 0x7a7773, 0x7b7774, 0x7b7874, 0x7c7974, 0x7d7a74, 0x7e7a74, 0x7f7b75,
+    0x807c75, 0x807d75, 0x817d75, 0x827e75, 0x837f76, 0x848076, 0x858076, 0x858176,
+    0x868276, 0x878376, 0x888477, 0x898477, 0x898577, 0x8a8677, 0x8b8777, 0x8c8777,
+    0x8d8877, 0x8e8978, 0x8e8a78, 0x8f8a78, 0x908b78, 0x918c78, 0x928d78, 0x938e78,
+    0x938e78, 0x948f78, 0x959078, 0x969178, 0x979278, 0x989278, 0x999378, 0x9a9478,
+    0x9b9578, 0x9b9678, 0x9c9678, 0x9d9778, 0x9e9878, 0x9f9978, 0xa09a78, 0xa19a78,
+    0xa29b78, 0xa39c78, 0xa49d78, 0xa59e77, 0xa69e77, 0xa79f77, 0xa8a077, 0xa9a177,
+    0xaaa276, 0xaba376, 0xaca376, 0xada476, 0xaea575, 0xafa675, 0xb0a775, 0xb2a874,
+    0xb3a874, 0xb4a974, 0xb5aa73, 0xb6ab73, 0xb7ac72, 0xb8ad72, 0xbaae72, 0xbbae71,
+    0xbcaf71, 0xbdb070, 0xbeb170, 0xbfb26f, 0xc1b36f, 0xc2b46e, 0xc3b56d, 0xc4b56d,
+    0xc5b66c, 0xc7b76c, 0xc8b86b, 0xc9b96a, 0xcaba6a, 0xccbb69, 0xcdbc68, 0xcebc68,
+    0xcfbd67, 0xd1be66, 0xd2bf66, 0xd3c065, 0xd4c164, 0xd6c263, 0xd7c363, 0xd8c462,
+    0xd9c561, 0xdbc660, 0xdcc660, 0xddc75f, 0xdec85e, 0xe0c95d, 0xe1ca5c, 0xe2cb5c,
+    0xe3cc5b, 0xe4cd5a, 0xe6ce59, 0xe7cf58, 0xe8d058, 0xe9d157, 0xead256, 0xebd355,
+    0xecd454, 0xedd453, 0xeed553, 0xf0d652, 0xf1d751, 0xf1d850, 0xf2d950, 0xf3da4f,
+    0xf4db4e, 0xf5dc4d, 0xf6dd4d, 0xf7de4c, 0xf8df4b, 0xf8e04b, 0xf9e14a, 0xfae249,
+    0xfae349, 0xfbe448, 0xfbe548, 0xfce647, 0xfce746, 0xfde846, 0xfde946, 0xfdea45,
+];
+
+// -----------------------------------------------------------------------------
@@ -0,0 +1,236 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use super::lbm::{Lattice, Geometry, Matrix, Scalar, State};
+
+// -----------------------------------------------------------------------------
+
+/// A scalar quantity of the flow whose convergence is tracked.
+pub struct Integral<L> {
+    pub name:     String,
+    pub evaluate: Box<Fn(&State<L>) -> Scalar>,
+}
+
+impl<L: Lattice + 'static> Integral<L> {
+    pub fn new(name: &str, evaluate: Box<Fn(&State<L>) -> Scalar>) -> Self {
+        Integral { name: name.to_string(), evaluate: evaluate }
+    }
+
+    /// The streamwise force on the solid nodes in `mask`, by momentum
+    /// exchange.
+    pub fn drag(mask: Geometry) -> Self {
+        Integral::new("drag", Box::new(move |state: &State<L>| {
+            state.momentum_exchange(&mask, (0.0, 0.0)).0.to_pair().0
+        }))
+    }
+
+    /// The cross-stream force on the solid nodes in `mask`, by momentum
+    /// exchange. It oscillates at the shedding frequency, unlike the drag
+    /// which oscillates at twice that.
+    pub fn lift(mask: Geometry) -> Self {
+        Integral::new("lift", Box::new(move |state: &State<L>| {
+            state.momentum_exchange(&mask, (0.0, 0.0)).0.to_pair().1
+        }))
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Status {
+    Running,
+    /// Every residual is below the tolerance.
+    Steady,
+    /// The first tracked integral oscillates regularly with this period.
+    Periodic { period: Scalar },
+}
+
+/// What to do once the run has converged.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Action {
+    Stop,
+    /// Keep displaying and writing output, but stop stepping.
+    OutputOnly,
+}
+
+/// The residuals at one check.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Residuals {
+    pub time:      Scalar,
+    /// The relative L2 change of the velocity since the last check.
+    pub velocity:  Scalar,
+    /// The relative L2 change of the density since the last check.
+    pub density:   Scalar,
+    /// The value of each tracked integral, and its relative change since the
+    /// last check.
+    pub integrals: Vec<(Scalar, Scalar)>,
+}
+
+/// Watches a run every `interval` steps and decides when it has converged,
+/// either to a steady state or to periodic shedding.
+pub struct ConvergenceMonitor<L> {
+    pub interval:         usize,
+    pub tolerance:        Scalar,
+    pub action:           Action,
+    /// How many regular cycles make a run periodic.
+    pub cycles:           usize,
+    /// How much the lengths and amplitudes of those cycles may vary,
+    /// relative to the largest.
+    pub period_tolerance: Scalar,
+    pub history:          Vec<Residuals>,
+    pub status:           Status,
+    /// Set by `track`, and only grown so that `shedding` always has a first
+    /// integral to watch.
+    integrals:            Vec<Integral<L>>,
+    /// Whether to look for periodic oscillations of the first integral; set
+    /// by `detect_shedding`.
+    shedding:             bool,
+    previous:             Option<(Matrix, (Matrix, Matrix))>,
+    calls:                usize,
+}
+
+impl<L: Lattice> ConvergenceMonitor<L> {
+    pub fn new(interval: usize, tolerance: Scalar, action: Action) -> Self {
+        assert!(interval > 0, "convergence check interval must be positive");
+        ConvergenceMonitor {
+            interval:         interval,
+            tolerance:        tolerance,
+            action:           action,
+            cycles:           4,
+            period_tolerance: 0.02,
+            history:          Vec::new(),
+            status:           Status::Running,
+            integrals:        Vec::new(),
+            shedding:         false,
+            previous:         None,
+            calls:            0,
+        }
+    }
+
+    pub fn track(mut self, integral: Integral<L>) -> Self {
+        self.integrals.push(integral);
+        self
+    }
+
+    /// Also stop once the first integral oscillates periodically.
+    pub fn detect_shedding(mut self) -> Self {
+        assert!(!self.integrals.is_empty(), "shedding detection needs an integral");
+        self.shedding = true;
+        self
+    }
+
+    pub fn is_converged(&self) -> bool {
+        self.status != Status::Running
+    }
+
+    pub fn should_stop(&self) -> bool {
+        self.is_converged() && (self.action == Action::Stop)
+    }
+
+    pub fn should_step(&self) -> bool {
+        !self.is_converged()
+    }
+
+    /// Call once per step; checks the state every `interval` calls.
+    pub fn check(&mut self, state: &State<L>) -> Status {
+        let due = self.calls % self.interval == 0;
+        self.calls += 1;
+        if !due || self.is_converged() { return self.status; }
+
+        let density = state.density();
+        let velocity = state.velocity();
+        let previous = self.history.last().map(|r| r.integrals.clone());
+        let integrals: Vec<(Scalar, Scalar)> = self.integrals.iter().enumerate().map(|(k, i)| {
+            let value = (i.evaluate)(state);
+            let change = match previous {
+                Some(ref p) => relative_change(value, p[k].0),
+                None => std::f32::INFINITY,
+            };
+            (value, change)
+        }).collect();
+
+        let (dv, dr) = match self.previous {
+            Some((ref rho, (ref ux, ref uy))) => {
+                let dx = &velocity.0 - ux;
+                let dy = &velocity.1 - uy;
+                let change = (dx.hadamard(&dx) + dy.hadamard(&dy)).sum();
+                let norm = (velocity.0.hadamard(&velocity.0)
+                            + velocity.1.hadamard(&velocity.1)).sum();
+                let d = &density - rho;
+                ((change / norm.max(std::f64::MIN_POSITIVE)).sqrt() as Scalar,
+                 (d.hadamard(&d).sum() / density.hadamard(&density).sum()).sqrt() as Scalar)
+            },
+            None => (std::f32::INFINITY, std::f32::INFINITY),
+        };
+
+        self.history.push(Residuals {
+            time:      state.time,
+            velocity:  dv,
+            density:   dr,
+            integrals: integrals.clone(),
+        });
+        self.previous = Some((density, velocity));
+
+        let steady = (dv < self.tolerance) && (dr < self.tolerance)
+            && integrals.iter().all(|&(_, change)| change < self.tolerance);
+        if steady {
+            self.status = Status::Steady;
+        } else if self.shedding {
+            if let Some(period) = self.period() {
+                self.status = Status::Periodic { period: period };
+            }
+        }
+        if self.status != Status::Running {
+            println!("Converged at t = {}: {:?}", state.time, self.status);
+        }
+        self.status
+    }
+
+    /// The period of the first integral, if its last `cycles` cycles have the
+    /// same length and amplitude to within `period_tolerance`.
+    fn period(&self) -> Option<Scalar> {
+        let signal: Vec<(Scalar, Scalar)>
+            = self.history.iter().map(|r| (r.time, r.integrals[0].0)).collect();
+        let n = signal.len();
+        let window = &signal[(n / 2) ..];
+        if window.len() < 4 { return None; }
+        let mean = window.iter().map(|s| s.1).sum::<Scalar>() / (window.len() as Scalar);
+
+        // The interpolated times of the upward crossings of the mean.
+        let crossings: Vec<(usize, Scalar)> = window.windows(2).enumerate()
+            .filter(|&(_, w)| (w[0].1 < mean) && (w[1].1 >= mean))
+            .map(|(k, w)| {
+                let s = (mean - w[0].1) / (w[1].1 - w[0].1);
+                (k, w[0].0 + s * (w[1].0 - w[0].0))
+            })
+            .collect();
+        if crossings.len() < self.cycles + 1 { return None; }
+
+        let recent = &crossings[(crossings.len() - self.cycles - 1) ..];
+        let periods: Vec<Scalar> = recent.windows(2).map(|c| c[1].1 - c[0].1).collect();
+        let amplitudes: Vec<Scalar> = recent.windows(2).map(|c| {
+            let cycle = &window[c[0].0 .. (c[1].0 + 1)];
+            let max = cycle.iter().map(|s| s.1).fold(std::f32::MIN, Scalar::max);
+            let min = cycle.iter().map(|s| s.1).fold(std::f32::MAX, Scalar::min);
+            max - min
+        }).collect();
+
+        let spread = |v: &[Scalar]| {
+            let max = v.iter().cloned().fold(std::f32::MIN, Scalar::max);
+            let min = v.iter().cloned().fold(std::f32::MAX, Scalar::min);
+            (max - min) / max.abs().max(std::f32::MIN_POSITIVE)
+        };
+        let allowed = self.period_tolerance;
+        if (spread(&periods) < allowed) && (spread(&amplitudes) < allowed) {
+            Some(periods.iter().sum::<Scalar>() / (periods.len() as Scalar))
+        } else {
+            None
+        }
+    }
+}
+
+fn relative_change(value: Scalar, previous: Scalar) -> Scalar {
+    (value - previous).abs() / value.abs().max(std::f32::MIN_POSITIVE)
+}
+
+// -----------------------------------------------------------------------------
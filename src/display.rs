@@ -1,3 +1,4 @@
+use std;
 use arrayfire as af;
 use piston::window::{Window, WindowSettings};
 use opengl_graphics::{GlGraphics, OpenGL, Texture, TextureSettings};
@@ -12,6 +13,9 @@ use std::sync::mpsc::sync_channel;
 use std::time::{Duration, Instant};
 use image;
 use chrono;
+use super::panel::{Command, Controls, Panel, Plot, PANEL_WIDTH};
+use super::viewport::{Magnification, Viewport};
+use super::worker::{Message, Worker};
 use glutin_window;
 use conrod::{self, widget, Colorable, Positionable, Widget};
 use opengl_graphics;
@@ -53,9 +57,49 @@ pub trait Drawable {
     /// color value.
     fn set_pixel(&mut self, pos: PixelPos, value: RGB);
 
+    /// Overwrite every pixel from row-major RGBA bytes, as produced by
+    /// `colormap::to_rgba`. The alpha channel is ignored by default.
+    fn set_pixels(&mut self, rgba: &[u8]) {
+        let (w, h) = self.dimensions();
+        assert_eq!(rgba.len(), (4 * w * h) as usize);
+        for y in 0 .. h {
+            for x in 0 .. w {
+                let i = (4 * ((y * w) + x)) as usize;
+                self.set_pixel(PixelPos(x, y), RGB(rgba[i], rgba[i + 1], rgba[i + 2]));
+            }
+        }
+    }
+
+    /// Overwrite the rectangle of the given size whose top left corner is at
+    /// `origin` from row-major RGBA bytes, e.g. those of a smaller drawable.
+    fn set_region(&mut self, origin: PixelPos, size: (u32, u32), rgba: &[u8]) {
+        let (PixelPos(x0, y0), (w, h)) = (origin, size);
+        assert_eq!(rgba.len(), (4 * w * h) as usize);
+        for y in 0 .. h {
+            for x in 0 .. w {
+                let i = (4 * ((y * w) + x)) as usize;
+                self.set_pixel(PixelPos(x0 + x, y0 + y),
+                               RGB(rgba[i], rgba[i + 1], rgba[i + 2]));
+            }
+        }
+    }
+
     /// Get the color of the pixel at the given position in the given
     /// drawable object.
     fn get_pixel(&self, pos: PixelPos) -> RGB;
+
+    /// Every pixel as row-major RGBA bytes, the inverse of `set_pixels`.
+    fn get_pixels(&self) -> Vec<u8> {
+        let (w, h) = self.dimensions();
+        let mut result = Vec::with_capacity((4 * w * h) as usize);
+        for y in 0 .. h {
+            for x in 0 .. w {
+                let RGB(r, g, b) = self.get_pixel(PixelPos(x, y));
+                result.extend(&[r, g, b, 255]);
+            }
+        }
+        result
+    }
 }
 
 impl Drawable for image::RgbaImage {
@@ -72,6 +116,28 @@ impl Drawable for image::RgbaImage {
         *(self.get_pixel_mut(x, y)) = value.to_rgba();
     }
 
+    fn set_pixels(&mut self, rgba: &[u8]) {
+        self.copy_from_slice(rgba);
+    }
+
+    fn set_region(&mut self, origin: PixelPos, size: (u32, u32), rgba: &[u8]) {
+        let (PixelPos(x0, y0), (w, h)) = (origin, size);
+        assert_eq!(rgba.len(), (4 * w * h) as usize);
+        let (width, height) = (image::ImageBuffer::width(self), image::ImageBuffer::height(self));
+        assert!((x0 + w <= width) && (y0 + h <= height));
+        let stride = 4 * width as usize;
+        let row = 4 * w as usize;
+        let buffer: &mut [u8] = &mut **self;
+        for y in 0 .. h as usize {
+            let start = (y0 as usize + y) * stride + 4 * x0 as usize;
+            buffer[start .. (start + row)].copy_from_slice(&rgba[(y * row) .. ((y + 1) * row)]);
+        }
+    }
+
+    fn get_pixels(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+
     fn get_pixel(&self, pos: PixelPos) -> RGB {
         let PixelPos(x, y) = pos;
         RGB::from_rgba(self.get_pixel(x, y))
@@ -80,22 +146,91 @@ impl Drawable for image::RgbaImage {
 
 pub trait Simulation {
     fn size(&self) -> (usize, usize);
-    fn handle(&mut self, input: &Event);
-    fn step(&mut self, elapsed: &Duration);
+
+    /// The size of the rendered image, e.g. several tiles of the lattice.
+    /// It may change between frames.
+    fn image_size(&self) -> (usize, usize) {
+        self.size()
+    }
+
+    /// React to an input event. Cursor positions are in window coordinates;
+    /// the viewport maps them to the lattice.
+    fn handle(&mut self, input: &Event, view: &Viewport);
+
+    /// Advance by one time step.
+    fn step(&mut self);
     fn render<D: Drawable>(&self, buf: &mut D);
+
+    /// Whether the simulation is holding off stepping for now, e.g. until
+    /// the user resumes it.
+    fn is_paused(&self) -> bool {
+        false
+    }
+
+    /// Whether the simulation has nothing left to do, e.g. because it has
+    /// converged.
+    fn is_finished(&self) -> bool {
+        false
+    }
+
+    /// What to show in the control panel, for simulations that have one.
+    fn controls(&self) -> Option<Controls> {
+        None
+    }
+
+    /// Act on a command given through the control panel.
+    fn command(&mut self, command: Command) {}
+
+    /// Time series for the control panel to plot over the image, in its
+    /// pixels.
+    fn plots(&self) -> Vec<Plot> {
+        Vec::new()
+    }
+}
+
+/// The largest window `example` opens at first.
+const MAX_WINDOW: (u32, u32) = (1600, 1000);
+
+fn texture_settings(magnification: Magnification) -> TextureSettings {
+    let filter = match magnification {
+        Magnification::Nearest => opengl_graphics::Filter::Nearest,
+        Magnification::Linear  => opengl_graphics::Filter::Linear,
+    };
+    TextureSettings::new().min(opengl_graphics::Filter::Linear).mag(filter)
 }
 
-pub fn example<S: Simulation>(initial: S) {
+/// How often the simulation thread renders a frame for `example`.
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Show a simulation in a window, stepping it on a `worker::Worker` thread.
+/// It is built on that thread.
+pub fn example<S, F>(build: F)
+    where S: Simulation, F: FnOnce() -> S + Send + 'static {
+    use graphics::Transformed;
+    use piston::input::{MouseCursorEvent, MouseScrollEvent, ResizeEvent};
+
     let start_time = Instant::now();
 
-    let (w, h) = initial.size();
+    let worker = Worker::spawn(build, FRAME_INTERVAL);
+    let mut snapshot = match worker.wait() {
+        Some(s) => s,
+        None => {
+            println!("The simulation stopped before its first frame");
+            return;
+        },
+    };
+
+    let (mut w, mut h) = snapshot.size;
+    let panel_width = if snapshot.controls.is_some() { PANEL_WIDTH } else { 0 };
+    let area = Viewport::initial_area((w, h), (MAX_WINDOW.0 - panel_width, MAX_WINDOW.1));
 
     let opengl = OpenGL::V3_2;
     let window_settings
-        = WindowSettings::new("Example", [w as u32, h as u32])
+        = WindowSettings::new("Example", [area.0 + panel_width, area.1])
         .srgb(false)
         .vsync(true)
         .opengl(opengl)
+        .resizable(true)
         // .fullscreen(true)
         .exit_on_esc(true);
     let mut window
@@ -103,44 +238,125 @@ pub fn example<S: Simulation>(initial: S) {
         .expect("Failed to make window");
     let mut gl = GlGraphics::new(opengl);
 
-    let image = Image::new().rect([0.0, 0.0, w as f64, h as f64]);
-    let mut rgba_image: image::RgbaImage
-        = image::ImageBuffer::new(w as u32, h as u32);
-
-    for (_, _, pixel) in rgba_image.enumerate_pixels_mut() {
-        pixel.data = [0, 0, 0, 255];
-    }
+    let mut view = Viewport::new((w, h), (area.0 as f64, area.1 as f64));
+    let mut image = Image::new().rect([0.0, 0.0, w as f64, h as f64]);
+    let mut rgba_image: image::RgbaImage = Drawable::new((w as u32, h as u32), RGB(0, 0, 0));
+    rgba_image.set_pixels(&snapshot.pixels);
 
-    let mut texture = Texture::from_image(&rgba_image, &TextureSettings::new());
-    let mut state = initial;
-    let mut last_draw = Instant::now();
+    let mut texture = Texture::from_image(&rgba_image, &texture_settings(view.magnification));
+    let mut panel = if panel_width > 0 {
+        Some(Panel::new((area.0 + panel_width, area.1)))
+    } else {
+        None
+    };
     let mut events = Events::new(EventSettings::new());
+    // Where the cursor is in the window, and whether the view is being
+    // dragged with the right button.
+    let mut cursor = [0.0, 0.0];
+    let mut panning = false;
 
     let mut frames: u32 = 0;
+    let mut steps: usize = 0;
 
     while let Some(e) = events.next(&mut window) {
-        state.handle(&e);
+        let input = e.button_args().is_some() || e.mouse_cursor_args().is_some()
+            || e.mouse_scroll_args().is_some();
+        if let Event::Input(ref i) = e {
+            if input && !worker.send(Message::Input(i.clone(), view)) {
+                println!("The simulation stopped");
+                window.set_should_close(true);
+            }
+        }
+
+        if let Some(ref mut panel) = panel {
+            let size = window.size();
+            panel.handle(&e, (size.width as f64, size.height as f64));
+            if e.update_args().is_some() {
+                if let Some(ref controls) = snapshot.controls {
+                    // The plots follow the image as it is zoomed and panned.
+                    let plots: Vec<Plot> = snapshot.plots.iter().cloned().map(|mut plot| {
+                        let corner = view.to_window([plot.rect[0], plot.rect[1]]);
+                        let scale = view.scale();
+                        plot.rect = [corner[0], corner[1],
+                                     scale * plot.rect[2], scale * plot.rect[3]];
+                        plot
+                    }).collect();
+                    for command in panel.update(controls, &plots) {
+                        worker.send(Message::Command(command));
+                    }
+                }
+            }
+        }
+
+        if e.resize_args().is_some() {
+            let size = window.size();
+            view.resize((size.width.saturating_sub(panel_width) as f64, size.height as f64));
+        }
+
+        if let Some(position) = e.mouse_cursor_args() {
+            if panning { view.pan([position[0] - cursor[0], position[1] - cursor[1]]); }
+            cursor = position;
+        }
+
+        if let Some(scroll) = e.mouse_scroll_args() {
+            if view.to_lattice(cursor).is_some() {
+                view.zoom_at((1.2 as f64).powf(scroll[1]), cursor);
+            }
+        }
 
         if let Some(b) = e.button_args() {
-            use input::{Button, ButtonState};
+            use input::{Button, ButtonState, MouseButton};
             use input::keyboard::Key;
+            if let Button::Mouse(MouseButton::Right) = b.button {
+                panning = (b.state == ButtonState::Press) && view.to_lattice(cursor).is_some();
+            }
             if let Button::Keyboard(k) = b.button {
                 // println!("Key received: {:?}", k);
                 if (k == Key::Q) && (b.state == ButtonState::Release) {
                     println!("Quitting!");
                     window.set_should_close(true);
                 }
+                if (k == Key::Home) && (b.state == ButtonState::Release) {
+                    view.reset();
+                    println!("View: {}", view);
+                }
+                if (k == Key::N) && (b.state == ButtonState::Release) {
+                    view.magnification = view.magnification.next();
+                    texture = Texture::from_image(&rgba_image,
+                                                  &texture_settings(view.magnification));
+                    println!("View: {}", view);
+                }
             }
         }
 
         if let Some(r) = e.render_args() {
-            state.step(&last_draw.elapsed());
-            last_draw = Instant::now();
-            state.render(&mut rgba_image);
-            texture.update(&rgba_image);
+            if let Some(latest) = worker.latest() {
+                snapshot = latest;
+                steps += snapshot.steps;
+                if snapshot.size != (w, h) {
+                    // Start afresh, e.g. with tiles added or removed.
+                    w = snapshot.size.0;
+                    h = snapshot.size.1;
+                    image = Image::new().rect([0.0, 0.0, w as f64, h as f64]);
+                    rgba_image = Drawable::new((w as u32, h as u32), RGB(0, 0, 0));
+                    texture = Texture::from_image(&rgba_image,
+                                                  &texture_settings(view.magnification));
+                    view.lattice = (w, h);
+                    view.reset();
+                }
+                rgba_image.set_pixels(&snapshot.pixels);
+                texture.update(&rgba_image);
+                if snapshot.finished {
+                    println!("Simulation finished");
+                    window.set_should_close(true);
+                }
+            }
             gl.draw(r.viewport(), |c, gl| {
                 clear([0.0, 0.0, 0.0, 1.0], gl);
-                image.draw(&texture, &Default::default(), c.transform, gl);
+                let clip = [0, 0, view.area.0 as u32, view.area.1 as u32];
+                let transform = c.transform.append_transform(view.transform());
+                image.draw(&texture, &c.draw_state.scissor(clip), transform, gl);
+                if let Some(ref mut panel) = panel { panel.draw(c, gl); }
             });
             frames += 1;
         }
@@ -148,6 +364,8 @@ pub fn example<S: Simulation>(initial: S) {
 
     println!("Average frames per second: {}",
              frames as f64 / start_time.elapsed().as_float_secs());
+    println!("Average steps per frame: {}",
+             steps as f64 / std::cmp::max(frames, 1) as f64);
 }
 
 extern crate gif;
@@ -160,7 +378,7 @@ pub fn record<S: Simulation, W: Write>(
     encoder: &mut gif::Encoder<W>
 ) -> Result<()>
 {
-    let (w, h) = initial.size();
+    let (w, h) = initial.image_size();
 
     let mut rgba_image: image::RgbaImage
         = image::ImageBuffer::new(w as u32, h as u32);
@@ -170,11 +388,10 @@ pub fn record<S: Simulation, W: Write>(
     }
 
     let mut state = initial;
-    let mut last_draw = Instant::now();
 
     for _ in 0 .. steps {
-        state.step(&last_draw.elapsed());
-        last_draw = Instant::now();
+        if state.is_finished() { break; }
+        state.step();
         state.render(&mut rgba_image);
         let frame = gif::Frame::from_rgba(w as u16, h as u16,
                                           &mut (rgba_image.clone().into_raw()));
@@ -183,123 +400,3 @@ pub fn record<S: Simulation, W: Write>(
 
     Ok(())
 }
-
-extern crate ttf_noto_sans;
-
-pub fn conrod() {
-    let w: usize = 500;
-    let h: usize = 500;
-
-    // Create the window.
-    let opengl = OpenGL::V3_2;
-    let window_settings
-        = WindowSettings::new("Example", [w as u32, h as u32])
-        .srgb(false)
-        .vsync(true)
-        .opengl(opengl)
-        .exit_on_esc(true);
-    let mut window
-        = glutin_window::GlutinWindow::new(&window_settings)
-        .expect("Failed to make window");
-    let mut gl = GlGraphics::new(opengl);
-
-    let mut ui = conrod::UiBuilder::new([w as f64, h as f64])
-        .theme(super::theme::theme())
-        .build();
-
-    // Add a `Font` to the `Ui`'s `font::Map` from file.
-    use conrod::text::FontCollection;
-    ui.fonts.insert(FontCollection::from_bytes(ttf_noto_sans::REGULAR)
-                    .expect("failed to FontCollection::from_bytes")
-                    .into_font()
-                    .expect("failed to into_font"));
-
-    // Create a texture to use for efficiently caching text on the GPU.
-    let mut text_vertex_data = Vec::new();
-    let (mut glyph_cache, mut text_texture_cache) = {
-        let scale_tolerance:    f32 = 0.1;
-        let position_tolerance: f32 = 0.1;
-        let cache = conrod::text::GlyphCache::builder()
-            .dimensions(w as u32, h as u32)
-            .scale_tolerance(scale_tolerance)
-            .position_tolerance(position_tolerance)
-            .build();
-        let init = vec![128; w * h];
-        let settings = TextureSettings::new();
-        let texture = Texture::from_memory_alpha(&init, w as u32, h as u32, &settings).unwrap();
-        (cache, texture)
-    };
-
-    // Instantiate the generated list of widget identifiers.
-    let ids = super::theme::Ids::new(ui.widget_id_generator());
-
-    // Load the rust logo from file to a Texture.
-    let rust_logo: Texture = {
-        let path = Path::new("/home/remy/Downloads/rust.png");
-        let settings = TextureSettings::new();
-        Texture::from_path(&path, &settings).unwrap()
-    };
-
-    // Create our `conrod::image::Map` which describes each of our widget->image mappings.
-    // In our case we only have one image, however the macro may be used to list multiple.
-    let mut image_map = conrod::image::Map::new();
-    let rust_logo = image_map.insert(rust_logo);
-
-    // A demonstration of some state that we'd like to control with the App.
-    let mut app = super::theme::DemoApp::new(rust_logo);
-
-    let mut events = Events::new(EventSettings::new());
-
-    while let Some(event) = events.next(&mut window) {
-        let size = window.size();
-        let (win_w, win_h) = (size.width  as conrod::Scalar,
-                              size.height as conrod::Scalar);
-        if let Some(e) = conrod_piston::event::convert(event.clone(), win_w, win_h) {
-            ui.handle_event(e);
-        }
-
-        event.update(|_| {
-            let mut ui = ui.set_widgets();
-            super::theme::gui(&mut ui, &ids, &mut app);
-        });
-
-        if let Some(r) = event.render_args() {
-            gl.draw(r.viewport(), |context, graphics| {
-                if let Some(primitives) = ui.draw_if_changed() {
-                    use conrod::text::rt::Rect;
-                    let cache_queued_glyphs
-                        = |graphics: &mut GlGraphics, cache: &mut Texture, rect: Rect<u32>, data: &[u8]| {
-                            text_vertex_data.clear();
-                            text_vertex_data.extend(
-                                data.iter()
-                                    .flat_map(|&b| vec![255, 255, 255, b])
-                            );
-                            opengl_graphics::UpdateTexture::update(
-                                cache, &mut (),
-                                opengl_graphics::Format::Rgba8,
-                                &text_vertex_data[..],
-                                [rect.min.x, rect.min.y],
-                                [rect.width(), rect.height()],
-                            ).expect("failed to update texture")
-                        };
-
-                    // Specify how to get the drawable texture from the image.
-                    // In this case, the image *is* the texture.
-                    fn texture_from_image<T>(img: &T) -> &T { img }
-
-                    // Draw the conrod `render::Primitives`.
-                    conrod_piston::draw::primitives(
-                        primitives,
-                        context,
-                        graphics,
-                        &mut text_texture_cache,
-                        &mut glyph_cache,
-                        &image_map,
-                        cache_queued_glyphs,
-                        texture_from_image,
-                    );
-                }
-            });
-        }
-    }
-}
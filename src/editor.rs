@@ -0,0 +1,332 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use image;
+use arrayfire as af;
+use super::lbm::{self, Geometry, Lattice, Matrix, Scalar, State};
+use super::overlay::draw_line;
+use super::display::{Drawable, RGB};
+
+// -----------------------------------------------------------------------------
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Tool {
+    /// Paints along the cursor while the button is held.
+    Brush,
+    /// From where the button is pressed to where it is released, as thick
+    /// as the brush.
+    Line,
+    /// Filled, with opposite corners where the button is pressed and
+    /// released.
+    Rectangle,
+    /// Filled, centred where the button is pressed.
+    Circle,
+}
+
+impl Tool {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Tool::Brush     => "brush",
+            Tool::Line      => "line",
+            Tool::Rectangle => "rectangle",
+            Tool::Circle    => "circle",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match *self {
+            Tool::Brush     => Tool::Line,
+            Tool::Line      => Tool::Rectangle,
+            Tool::Rectangle => Tool::Circle,
+            Tool::Circle    => Tool::Brush,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Mode {
+    Add,
+    Erase,
+}
+
+// -----------------------------------------------------------------------------
+
+/// The nodes within `radius` of the segment from `a` to `b`, in lattice node
+/// coordinates.
+pub fn segment(dims: af::Dim4, a: [Scalar; 2], b: [Scalar; 2], radius: Scalar) -> Geometry {
+    let xs = Matrix::unsafe_new(af::range::<f32>(dims, 1)).shift(-a[0]);
+    let ys = Matrix::unsafe_new(af::range::<f32>(dims, 0)).shift(-a[1]);
+    let (ex, ey) = (b[0] - a[0], b[1] - a[1]);
+    let length = ex * ex + ey * ey;
+    // The parameter of the closest point on the segment.
+    let t = if length > 0.0 {
+        (xs.scale(ex) + ys.scale(ey)).scale(1.0 / length).clamp(0.0, 1.0)
+    } else {
+        xs.scale(0.0)
+    };
+    let dx = &xs - t.scale(ex);
+    let dy = &ys - t.scale(ey);
+    let distance = dx.hadamard(&dx) + dy.hadamard(&dy);
+    af::le(distance.get_array(), &(radius * radius), false)
+}
+
+/// The nodes of the rectangle with the given opposite corners.
+pub fn rectangle(dims: af::Dim4, a: [Scalar; 2], b: [Scalar; 2]) -> Geometry {
+    let xs = af::range::<f32>(dims, 1);
+    let ys = af::range::<f32>(dims, 0);
+    let within = |coords: &af::Array<f32>, p: Scalar, q: Scalar| {
+        af::and(&af::ge(coords, &p.min(q).round(), false),
+                &af::le(coords, &p.max(q).round(), false),
+                false)
+    };
+    af::and(&within(&xs, a[0], b[0]), &within(&ys, a[1], b[1]), false)
+}
+
+/// The nodes of the disk centred on `center` that reaches `edge`.
+pub fn disk(dims: af::Dim4, center: [Scalar; 2], edge: [Scalar; 2]) -> Geometry {
+    let radius = ((edge[0] - center[0]).powi(2) + (edge[1] - center[1]).powi(2)).sqrt();
+    segment(dims, center, center, radius)
+}
+
+/// The density and velocity to refill nodes with as they stop being solid:
+/// those of their fluid neighbours on average, or unit density at rest where
+/// there are none.
+pub fn local_equilibrium<L: Lattice>(state: &State<L>) -> (Matrix, (Matrix, Matrix)) {
+    let size = state.size();
+    let fluid = Matrix::unsafe_new(af::eq(&state.geometry, &false, false).cast::<f32>());
+    let neighbours = |field: &Matrix| {
+        let weighted = field.hadamard(&fluid);
+        let mut sum = Matrix::new_filled(0.0, size);
+        for dir in state.directions() {
+            let c = dir.c_vector();
+            if c.to_pair() == (0.0, 0.0) { continue; }
+            sum += Matrix::unsafe_new(lbm::translate(weighted.get_array(), c));
+        }
+        sum
+    };
+    let count = neighbours(&Matrix::new_filled(1.0, size));
+    let average = |field: &Matrix| {
+        neighbours(field).divide(&count.clamp(1.0, std::f32::MAX))
+    };
+    let (ux, uy) = state.velocity();
+    // One where any neighbour is fluid, zero elsewhere.
+    let reached = count.clamp(0.0, 1.0);
+    let density = average(&state.density()) + reached.scale(-1.0).shift(1.0);
+    (density, (average(&ux), average(&uy)))
+}
+
+/// Replace the geometry of the state, refilling the nodes that stop being
+/// solid with the local equilibrium.
+pub fn set_geometry<L: Lattice>(state: &mut State<L>, geometry: Geometry) {
+    let uncovered = af::and(&state.geometry, &af::eq(&geometry, &false, false), false);
+    if af::count_all(&uncovered).0 > 0.0 {
+        let (density, velocity) = local_equilibrium(state);
+        state.refill(&uncovered, &density, &velocity);
+    }
+    state.geometry = geometry;
+}
+
+// -----------------------------------------------------------------------------
+
+/// Edits the solid nodes of a state with the mouse, keeping the geometry
+/// before each edit so that it can be undone.
+///
+/// The history is a pair of bounded stacks rather than a
+/// `piston-history_tree`: every entry is a whole geometry on the device, so
+/// the oldest ones must be dropped after `depth` edits, and the branches a
+/// tree would keep after an undo are never revisited.
+pub struct Editor {
+    pub tool:   Tool,
+    pub mode:   Mode,
+    /// The radius of the brush, and half the thickness of lines.
+    pub radius: Scalar,
+    /// How many edits can be undone.
+    pub depth:  usize,
+    undo:       Vec<Geometry>,
+    redo:       Vec<Geometry>,
+    /// Where the current stroke started and where it is now.
+    stroke:     Option<([Scalar; 2], [Scalar; 2])>,
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Editor {
+            tool:   Tool::Brush,
+            mode:   Mode::Add,
+            radius: 5.0,
+            depth:  64,
+            undo:   Vec::new(),
+            redo:   Vec::new(),
+            stroke: None,
+        }
+    }
+
+    /// Whether a stroke is being drawn.
+    pub fn is_drawing(&self) -> bool {
+        self.stroke.is_some()
+    }
+
+    /// Forget every edit, e.g. when the state they were made on is replaced.
+    pub fn clear_history(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.stroke = None;
+    }
+
+    fn checkpoint<L: Lattice>(&mut self, state: &State<L>) {
+        self.undo.push(state.geometry.copy());
+        if self.undo.len() > self.depth { self.undo.remove(0); }
+        self.redo.clear();
+    }
+
+    /// Add the mask to the solid nodes, or erase it from them.
+    pub fn apply<L: Lattice>(&self, mask: &Geometry, state: &mut State<L>) {
+        let geometry = match self.mode {
+            Mode::Add   => af::or(&state.geometry, mask, false),
+            Mode::Erase => af::and(&state.geometry, &af::eq(mask, &false, false), false),
+        };
+        set_geometry(state, geometry);
+    }
+
+    /// Start a stroke at the given point, e.g. when the button is pressed.
+    pub fn press<L: Lattice>(&mut self, point: [Scalar; 2], state: &mut State<L>) {
+        self.checkpoint(state);
+        self.stroke = Some((point, point));
+        if self.tool == Tool::Brush {
+            let mask = segment(state.geometry.dims(), point, point, self.radius);
+            self.apply(&mask, state);
+        }
+    }
+
+    /// Carry the stroke to the given point, e.g. when the cursor moves.
+    pub fn drag<L: Lattice>(&mut self, point: [Scalar; 2], state: &mut State<L>) {
+        let (start, last) = match self.stroke {
+            Some(stroke) => stroke,
+            None => return,
+        };
+        if self.tool == Tool::Brush {
+            let mask = segment(state.geometry.dims(), last, point, self.radius);
+            self.apply(&mask, state);
+        }
+        self.stroke = Some((start, point));
+    }
+
+    /// Finish the stroke, drawing the shape it spans.
+    pub fn release<L: Lattice>(&mut self, state: &mut State<L>) {
+        let (start, end) = match self.stroke.take() {
+            Some(stroke) => stroke,
+            None => return,
+        };
+        let dims = state.geometry.dims();
+        let mask = match self.tool {
+            Tool::Brush     => return,
+            Tool::Line      => segment(dims, start, end, self.radius),
+            Tool::Rectangle => rectangle(dims, start, end),
+            Tool::Circle    => disk(dims, start, end),
+        };
+        self.apply(&mask, state);
+    }
+
+    /// Go back to the geometry before the last edit. Returns whether there
+    /// was one.
+    pub fn undo<L: Lattice>(&mut self, state: &mut State<L>) -> bool {
+        match self.undo.pop() {
+            Some(geometry) => {
+                self.redo.push(state.geometry.copy());
+                set_geometry(state, geometry);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Redo the last edit undone. Returns whether there was one.
+    pub fn redo<L: Lattice>(&mut self, state: &mut State<L>) -> bool {
+        match self.redo.pop() {
+            Some(geometry) => {
+                self.undo.push(state.geometry.copy());
+                set_geometry(state, geometry);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Save the geometry as an image, with the solid nodes in black on
+    /// white.
+    pub fn save<L: Lattice, P: AsRef<std::path::Path>>(
+        &self,
+        state: &State<L>,
+        path:  P,
+    ) -> std::io::Result<()> {
+        let (w, h) = state.size();
+        let mut solid = vec![false; w * h];
+        af::transpose(&state.geometry, false).host(&mut solid);
+        let pixels = solid.iter().map(|&s| if s { 0 } else { 255 }).collect();
+        let image = image::GrayImage::from_raw(w as u32, h as u32, pixels)
+            .expect("the geometry does not match the size of the state");
+        image.save(path).map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
+        })
+    }
+
+    /// Replace the geometry with one saved by `save`: the dark pixels of the
+    /// image are solid. This can be undone.
+    pub fn load<L: Lattice, P: AsRef<std::path::Path>>(
+        &mut self,
+        state: &mut State<L>,
+        path:  P,
+    ) -> std::io::Result<()> {
+        let image = image::open(path).map_err(|e| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
+        })?.to_luma();
+        let (w, h) = state.size();
+        if image.dimensions() != (w as u32, h as u32) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("the image is {:?} but the lattice is {:?}",
+                        image.dimensions(), (w, h))));
+        }
+        let solid: Vec<bool> = image.into_raw().iter().map(|&p| p < 128).collect();
+        let dim4 = af::Dim4::new(&[w as u64, h as u64, 1, 1]);
+        self.checkpoint(state);
+        set_geometry(state, af::transpose(&af::Array::new(&solid[..], dim4), false));
+        Ok(())
+    }
+
+    /// Outline the shape of the current stroke.
+    pub fn draw_preview<D: Drawable>(&self, buf: &mut D) {
+        let (a, b) = match self.stroke {
+            Some(stroke) => stroke,
+            None => return,
+        };
+        let color = match self.mode {
+            Mode::Add   => RGB(255, 255, 255),
+            Mode::Erase => RGB(255, 80, 40),
+        };
+        match self.tool {
+            Tool::Brush => {},
+            Tool::Line  => draw_line(buf, a, b, color, 1.0),
+            Tool::Rectangle => {
+                let corners = [a, [b[0], a[1]], b, [a[0], b[1]]];
+                for k in 0 .. 4 {
+                    draw_line(buf, corners[k], corners[(k + 1) % 4], color, 1.0);
+                }
+            },
+            Tool::Circle => {
+                let radius = ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt();
+                let segments = 64;
+                let point = |k: usize| {
+                    let angle = 2.0 * std::f32::consts::PI * (k as Scalar)
+                        / (segments as Scalar);
+                    [a[0] + radius * angle.cos(), a[1] + radius * angle.sin()]
+                };
+                for k in 0 .. segments {
+                    draw_line(buf, point(k), point(k + 1), color, 1.0);
+                }
+            },
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
@@ -0,0 +1,281 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use arrayfire as af;
+use super::lbm::{self, Lattice, Geometry, Matrix, Populations, Scalar, State};
+
+// -----------------------------------------------------------------------------
+
+/// The partition of the non-solid nodes of a free-surface lattice.
+#[derive(Clone)]
+pub struct Cells {
+    pub gas:       Geometry,
+    pub interface: Geometry,
+    pub liquid:    Geometry,
+}
+
+fn not(mask: &Geometry) -> Geometry {
+    af::eq(mask, &false, false)
+}
+
+fn to_matrix(mask: &Geometry) -> Matrix {
+    Matrix::unsafe_new(mask.cast::<f32>())
+}
+
+/// The nodes with at least one neighbour in `mask`.
+fn neighbours(mask: &Geometry, directions: &[lbm::Direction]) -> Geometry {
+    let mut result = af::constant(false, mask.dims());
+    for dir in directions {
+        result = af::or(&result, &lbm::translate(mask, dir.c_vector()), false);
+    }
+    result
+}
+
+// -----------------------------------------------------------------------------
+
+/// A free-surface lattice, following "Lattice Boltzmann model for free
+/// surface flow for modeling foaming" by Körner et al.
+///
+/// Every non-solid node is gas, liquid, or on the interface between them. The
+/// liquid and interface nodes carry a mass, and the fill fraction of a node is
+/// its mass over its density. Populations are only simulated in the liquid and
+/// interface nodes: those that would stream in from the gas are rebuilt from
+/// the equilibrium at the atmospheric density, and the mass that interface
+/// nodes exchange with their neighbours moves the interface.
+///
+/// This drives its own `State`, whose geometry only holds the walls.
+pub struct FreeSurface<L> {
+    pub state:               State<L>,
+    pub cells:               Cells,
+    pub mass:                Matrix,
+    /// The gravitational acceleration, in physical units.
+    pub gravity:             [Scalar; 2],
+    pub atmospheric_density: Scalar,
+    /// How far past full or empty an interface node must get before it turns
+    /// into liquid or gas.
+    pub threshold:           Scalar,
+}
+
+impl<L: Lattice> FreeSurface<L> {
+    /// Fill the given non-solid nodes of the lattice with liquid and the rest
+    /// with gas.
+    pub fn new(state: State<L>, liquid: &Geometry, gravity: [Scalar; 2]) -> Self {
+        let directions = state.directions();
+        let fluid = not(&state.geometry);
+        let liquid = af::and(liquid, &fluid, false);
+        let gas = af::and(&not(&liquid), &fluid, false);
+        let interface = af::and(&liquid, &neighbours(&gas, &directions), false);
+        let liquid = af::and(&liquid, &not(&interface), false);
+        let cells = Cells { gas: gas, interface: interface, liquid: liquid };
+
+        let density = state.density();
+        let mass = density.hadamard(&to_matrix(&cells.liquid))
+            + density.scale(0.5).hadamard(&to_matrix(&cells.interface));
+
+        let mut result = FreeSurface {
+            state:               state,
+            cells:               cells,
+            mass:                mass,
+            gravity:             gravity,
+            atmospheric_density: 1.0,
+            threshold:           1.0e-3,
+        };
+        result.reset_gas();
+        result
+    }
+
+    /// The fraction of each node filled with liquid.
+    pub fn fill_fraction(&self) -> Matrix {
+        self.mass.divide(&self.state.density())
+            .hadamard(&to_matrix(&af::or(&self.cells.liquid,
+                                         &self.cells.interface,
+                                         false)))
+            .clamp(0.0, 1.0)
+    }
+
+    pub fn step(&mut self) {
+        let directions = self.state.directions();
+        let f_post: Populations = self.state.populations().clone();
+        let f_post_swapped = self.state.lattice.swap_populations();
+        let velocity = self.state.velocity();
+
+        self.exchange_mass(&f_post, &f_post_swapped);
+
+        self.state.stream();
+        self.state.bounce_back();
+
+        self.reconstruct(&f_post_swapped, &velocity);
+
+        self.state.collide();
+
+        {
+            let wet = to_matrix(&af::or(&self.cells.liquid,
+                                        &self.cells.interface,
+                                        false));
+            let density = self.state.density().hadamard(&wet);
+            let force = (density.scale(self.gravity[0]),
+                         density.scale(self.gravity[1]));
+            self.state.apply_force(&force);
+        }
+
+        self.convert_cells(&directions);
+
+        self.reset_gas();
+        self.state.time += self.state.delta_t();
+    }
+
+    /// Move mass across the links of the interface nodes.
+    fn exchange_mass(&mut self, f_post: &Populations, f_post_swapped: &Populations) {
+        let fill = self.fill_fraction();
+        let interface = to_matrix(&self.cells.interface);
+        let liquid = to_matrix(&self.cells.liquid);
+
+        let mut delta = Matrix::new_filled(0.0, self.state.size());
+        for ((dir, f_out), (_, f_opp)) in f_post.iter().zip(f_post_swapped) {
+            let (cx, cy) = dir.c_vector().to_pair();
+            let back = lbm::Vector::new(-cx, -cy);
+            let neighbour = |m: &Matrix| Matrix::unsafe_new(
+                lbm::translate(m.get_array(), back));
+
+            // The population coming back from the neighbour at x + c is the
+            // one it holds in the opposite direction.
+            let incoming = neighbour(f_opp);
+            let weight
+                = neighbour(&liquid)
+                + neighbour(&interface)
+                .hadamard(&(neighbour(&fill) + &fill))
+                .scale(0.5);
+            delta += (incoming - f_out).hadamard(&weight);
+        }
+
+        self.mass += delta.hadamard(&interface);
+    }
+
+    /// Rebuild the populations that streamed into interface nodes from gas.
+    fn reconstruct(&mut self, f_post_swapped: &Populations, velocity: &(Matrix, Matrix)) {
+        let size = self.state.size();
+        let directions = self.state.directions();
+        let atmosphere = Matrix::new_filled(self.atmospheric_density, size);
+        let f_eq = lbm::compute_equilibrium(atmosphere, velocity.clone(),
+                                            &directions,
+                                            self.state.discretization);
+        let opposite = self.state.lattice.descriptor().opposite;
+
+        let pops = self.state.lattice.populations_mut();
+        for (i, pair) in pops.iter_mut().enumerate() {
+            let c = pair.0.c_vector();
+            let from_gas = af::and(&self.cells.interface,
+                                   &lbm::translate(&self.cells.gas, c),
+                                   false);
+            let j = opposite[i];
+            // `f_post_swapped[i]` holds the post-collision population in the
+            // direction opposite to `i`, which left towards the gas.
+            let rebuilt = &f_eq[i].1 + &f_eq[j].1 - &f_post_swapped[i].1;
+            af::replace(pair.1.get_array_mut(), &not(&from_gas), rebuilt.get_array());
+        }
+    }
+
+    /// Turn full interface nodes into liquid and empty ones into gas, moving
+    /// the interface and redistributing the excess mass to it.
+    fn convert_cells(&mut self, directions: &[lbm::Direction]) {
+        let size = self.state.size();
+        let density = self.state.density();
+        let full = af::and(
+            &self.cells.interface,
+            &af::gt(self.mass.get_array(),
+                    density.scale(1.0 + self.threshold).get_array(), false),
+            false);
+        let empty = af::and(
+            &self.cells.interface,
+            &af::lt(self.mass.get_array(),
+                    density.scale(-self.threshold).get_array(), false),
+            false);
+
+        // Gas next to a node that filled up, and liquid next to one that
+        // emptied, join the interface.
+        let new_interface_from_gas = af::and(&self.cells.gas,
+                                             &neighbours(&full, directions),
+                                             false);
+        let new_interface_from_liquid = af::and(&self.cells.liquid,
+                                                &neighbours(&empty, directions),
+                                                false);
+
+        {
+            let (vx, vy) = self.state.velocity();
+            let wet = to_matrix(&not(&af::or(&self.cells.gas,
+                                             &self.state.geometry,
+                                             false)));
+            let mut count = Matrix::new_filled(0.0, size);
+            let mut rho = Matrix::new_filled(0.0, size);
+            let mut ux = Matrix::new_filled(0.0, size);
+            let mut uy = Matrix::new_filled(0.0, size);
+            for dir in directions {
+                let c = dir.c_vector();
+                let shift = |m: &Matrix| Matrix::unsafe_new(
+                    lbm::translate(m.get_array(), c));
+                count += shift(&wet);
+                rho += shift(&density.hadamard(&wet));
+                ux += shift(&vx.hadamard(&wet));
+                uy += shift(&vy.hadamard(&wet));
+            }
+            let inverse = count.clamp(1.0, std::f32::MAX).recip();
+            self.state.refill(&new_interface_from_gas,
+                              &rho.hadamard(&inverse),
+                              &(ux.hadamard(&inverse), uy.hadamard(&inverse)));
+        }
+
+        let excess
+            = (&self.mass - &density).hadamard(&to_matrix(&full))
+            + self.mass.hadamard(&to_matrix(&empty));
+
+        let liquid = af::or(
+            &af::and(&self.cells.liquid, &not(&new_interface_from_liquid), false),
+            &full, false);
+        let gas = af::or(
+            &af::and(&self.cells.gas, &not(&new_interface_from_gas), false),
+            &empty, false);
+        let interface = af::and(
+            &af::or(&af::or(&self.cells.interface, &new_interface_from_gas, false),
+                    &new_interface_from_liquid, false),
+            &not(&af::or(&full, &empty, false)),
+            false);
+
+        self.mass = &self.mass - &excess;
+
+        // Each converted node shares its excess equally among the interface
+        // nodes around it.
+        let interface_matrix = to_matrix(&interface);
+        let mut receivers = Matrix::new_filled(0.0, size);
+        for dir in directions {
+            let back = {
+                let (cx, cy) = dir.c_vector().to_pair();
+                lbm::Vector::new(-cx, -cy)
+            };
+            receivers += Matrix::unsafe_new(
+                lbm::translate(interface_matrix.get_array(), back));
+        }
+        let share = excess.divide(&receivers.clamp(1.0, std::f32::MAX));
+        let mut received = Matrix::new_filled(0.0, size);
+        for dir in directions {
+            received += Matrix::unsafe_new(
+                lbm::translate(share.get_array(), dir.c_vector()));
+        }
+        self.mass += received.hadamard(&interface_matrix);
+
+        self.mass = self.mass.hadamard(&to_matrix(&interface))
+            + self.state.density().hadamard(&to_matrix(&liquid));
+        self.cells = Cells { gas: gas, interface: interface, liquid: liquid };
+    }
+
+    /// Keep the gas at rest at the atmospheric density, so that its
+    /// populations stay well-defined.
+    fn reset_gas(&mut self) {
+        let size = self.state.size();
+        let atmosphere = Matrix::new_filled(self.atmospheric_density, size);
+        let rest = (Matrix::new_filled(0.0, size), Matrix::new_filled(0.0, size));
+        let gas = self.cells.gas.clone();
+        self.state.refill(&gas, &atmosphere, &rest);
+    }
+}
+
+// -----------------------------------------------------------------------------
@@ -0,0 +1,193 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use image;
+use super::lbm::{Lattice, State};
+use super::layout::Layout;
+use super::legend::TextRenderer;
+use super::overlay::{self, Overlay};
+use super::render::render_geometry;
+use super::display::{Drawable, Simulation, RGB};
+
+// -----------------------------------------------------------------------------
+
+/// Where the frames of a sequence go: `directory/prefix_000042.extension`.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Naming {
+    pub directory: std::path::PathBuf,
+    #[serde(default = "default_prefix")]
+    pub prefix:    String,
+    /// Any format the `image` crate writes, e.g. "png" or "jpg".
+    #[serde(default = "default_extension")]
+    pub extension: String,
+}
+
+fn default_prefix() -> String { "frame".to_string() }
+
+fn default_extension() -> String { "png".to_string() }
+
+impl Naming {
+    pub fn new<P: AsRef<std::path::Path>>(directory: P) -> Self {
+        Naming {
+            directory: directory.as_ref().to_path_buf(),
+            prefix:    default_prefix(),
+            extension: default_extension(),
+        }
+    }
+
+    pub fn path(&self, frame: usize) -> std::path::PathBuf {
+        self.directory.join(format!("{}_{:06}.{}", self.prefix, frame, self.extension))
+    }
+}
+
+/// Write the image in the format given by the extension of the path.
+pub fn save_image<P: AsRef<std::path::Path>>(
+    image: &image::RgbaImage,
+    path:  P,
+) -> std::io::Result<()> {
+    image.save(path).map_err(|e| {
+        std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
+    })
+}
+
+// -----------------------------------------------------------------------------
+
+/// The images of a case file: which fields to draw and how often.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct ImageSettings {
+    pub directory: std::path::PathBuf,
+    #[serde(default = "default_extension")]
+    pub extension: String,
+    /// In steps.
+    pub interval:  usize,
+    /// As for `layout::Content::from_name`, e.g. "density" or "vorticity".
+    pub fields:    Vec<String>,
+    #[serde(default = "default_columns")]
+    pub columns:   usize,
+    /// As for `overlay::Overlay::from_name`, drawn over every field.
+    #[serde(default)]
+    pub overlays:  Vec<String>,
+}
+
+fn default_columns() -> usize { 2 }
+
+/// Renders fields of a state with `layout::Layout`, including overlays, the
+/// solid nodes and colour bars, to numbered image files every `interval`
+/// steps. It draws on the host alone, so it needs no window or OpenGL
+/// context. Series tiles are left blank, as only the control panel plots
+/// them.
+pub struct ImageSequence {
+    pub naming:   Naming,
+    pub interval: usize,
+    pub layout:   Layout,
+    pub overlays: Vec<Overlay>,
+    text:         TextRenderer,
+    calls:        usize,
+    frames:       usize,
+}
+
+impl ImageSequence {
+    pub fn new(naming: Naming, mut layout: Layout, interval: usize) -> Self {
+        assert!(interval > 0, "image interval must be positive");
+        layout.highlight = false;
+        ImageSequence {
+            naming:   naming,
+            interval: interval,
+            layout:   layout,
+            overlays: Vec::new(),
+            text:     TextRenderer::new(),
+            calls:    0,
+            frames:   0,
+        }
+    }
+
+    /// The images described by a case file, for a lattice of the given size,
+    /// in the given subdirectory of the one the settings name.
+    pub fn from_settings(
+        settings:     &ImageSettings,
+        lattice:      (usize, usize),
+        subdirectory: &str,
+    ) -> std::io::Result<Self> {
+        let invalid = |e: String| std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+        let columns = std::cmp::max(settings.columns, 1);
+        let layout = Layout::of_fields(lattice, &settings.fields, columns).map_err(invalid)?;
+        let mut naming = Naming::new(settings.directory.join(subdirectory));
+        naming.extension = settings.extension.clone();
+        let mut sequence = ImageSequence::new(naming, layout, settings.interval);
+        for name in &settings.overlays {
+            match Overlay::from_name(name, lattice) {
+                Some(o) => sequence = sequence.with_overlay(o),
+                None => return Err(invalid(format!("unknown overlay {:?}", name))),
+            }
+        }
+        Ok(sequence)
+    }
+
+    pub fn with_overlay(mut self, overlay: Overlay) -> Self {
+        self.overlays.push(overlay);
+        self
+    }
+
+    /// How many images have been written.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Call once per step; writes an image of the state every `interval`
+    /// calls, starting with the first.
+    pub fn record<L: Lattice>(&mut self, state: &State<L>) -> std::io::Result<()> {
+        let due = self.calls % self.interval == 0;
+        self.calls += 1;
+        if due { self.write(state)?; }
+        Ok(())
+    }
+
+    /// Write an image of the state now, and return where it went.
+    pub fn write<L: Lattice>(&mut self, state: &State<L>) -> std::io::Result<std::path::PathBuf> {
+        if self.frames == 0 { std::fs::create_dir_all(&self.naming.directory)?; }
+        let (w, h) = self.layout.size();
+        let mut image: image::RgbaImage = Drawable::new((w as u32, h as u32), RGB(0, 0, 0));
+        let overlays = &self.overlays;
+        self.layout.render(state, &self.text, &mut image, |tile| {
+            overlay::draw_overlays(overlays, &state.velocity(), &state.geometry, tile);
+            render_geometry(&state.geometry, tile);
+        });
+        let path = self.naming.path(self.frames);
+        save_image(&image, &path)?;
+        self.frames += 1;
+        Ok(path)
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// Step a simulation without a window, writing what it renders every
+/// `interval` steps, starting before the first. Stops early if the
+/// simulation finishes, and returns how many images were written.
+pub fn run<S: Simulation>(
+    simulation: &mut S,
+    steps:      usize,
+    interval:   usize,
+    naming:     &Naming,
+) -> std::io::Result<usize> {
+    assert!(interval > 0, "image interval must be positive");
+    std::fs::create_dir_all(&naming.directory)?;
+    let mut frames = 0;
+    let write = |simulation: &S, frames: &mut usize| -> std::io::Result<()> {
+        let (w, h) = simulation.image_size();
+        let mut image: image::RgbaImage = Drawable::new((w as u32, h as u32), RGB(0, 0, 0));
+        simulation.render(&mut image);
+        save_image(&image, naming.path(*frames))?;
+        *frames += 1;
+        Ok(())
+    };
+    for step in 0 .. steps {
+        if simulation.is_finished() { break; }
+        if step % interval == 0 { write(simulation, &mut frames)?; }
+        simulation.step();
+    }
+    write(simulation, &mut frames)?;
+    Ok(frames)
+}
+
+// -----------------------------------------------------------------------------
@@ -0,0 +1,354 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use super::lbm::{Lattice, Matrix, Scalar, State};
+
+// -----------------------------------------------------------------------------
+
+/// A position or vector in lattice node coordinates.
+pub type Point = [Scalar; 2];
+
+// -----------------------------------------------------------------------------
+
+/// The regularized delta functions used to couple the markers to the lattice,
+/// as tabulated in "The immersed boundary method" by Peskin.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Kernel {
+    /// Linear interpolation, with a support of two nodes.
+    Hat,
+    /// The three-point kernel of Roma, Peskin and Berger.
+    Peskin3,
+    /// The four-point kernel of Peskin.
+    Peskin4,
+}
+
+impl Kernel {
+    /// The radius beyond which the kernel vanishes, in lattice nodes.
+    pub fn support(&self) -> Scalar {
+        match *self {
+            Kernel::Hat     => 1.0,
+            Kernel::Peskin3 => 1.5,
+            Kernel::Peskin4 => 2.0,
+        }
+    }
+
+    /// The one-dimensional kernel at the given distance, in lattice nodes.
+    pub fn phi(&self, r: Scalar) -> Scalar {
+        let r = r.abs();
+        match *self {
+            Kernel::Hat => {
+                if r < 1.0 { 1.0 - r } else { 0.0 }
+            },
+            Kernel::Peskin3 => {
+                if r <= 0.5 {
+                    (1.0 + Scalar::sqrt(1.0 - 3.0 * r * r)) / 3.0
+                } else if r <= 1.5 {
+                    let s = 1.0 - r;
+                    (5.0 - 3.0 * r - Scalar::sqrt(1.0 - 3.0 * s * s)) / 6.0
+                } else {
+                    0.0
+                }
+            },
+            Kernel::Peskin4 => {
+                if r <= 1.0 {
+                    (3.0 - 2.0 * r + Scalar::sqrt(1.0 + 4.0 * r - 4.0 * r * r)) / 8.0
+                } else if r <= 2.0 {
+                    (5.0 - 2.0 * r - Scalar::sqrt(-7.0 + 12.0 * r - 4.0 * r * r)) / 8.0
+                } else {
+                    0.0
+                }
+            },
+        }
+    }
+
+    /// The nodes within the support of the kernel centered on the given
+    /// point, along with their weights.
+    pub fn stencil(&self, point: Point, size: (usize, usize)) -> Vec<(usize, Scalar)> {
+        let (w, h) = size;
+        let s = self.support();
+        let x_min = Scalar::max(0.0, (point[0] - s).ceil()) as usize;
+        let y_min = Scalar::max(0.0, (point[1] - s).ceil()) as usize;
+        let x_max = Scalar::min((w - 1) as Scalar, (point[0] + s).floor()) as usize;
+        let y_max = Scalar::min((h - 1) as Scalar, (point[1] + s).floor()) as usize;
+        let mut result = Vec::new();
+        for y in y_min .. (y_max + 1) {
+            for x in x_min .. (x_max + 1) {
+                let weight
+                    = self.phi(x as Scalar - point[0])
+                    * self.phi(y as Scalar - point[1]);
+                if weight > 0.0 { result.push(((y * w) + x, weight)); }
+            }
+        }
+        result
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Marker {
+    /// The position of the marker, in lattice node coordinates.
+    pub position: Point,
+    /// The velocity of the marker, in lattice nodes per time step.
+    pub velocity: Point,
+    /// The force density the marker exerts on the fluid, in lattice units:
+    /// the momentum density it adds per time step.
+    pub force:    Point,
+}
+
+// -----------------------------------------------------------------------------
+
+/// The stiffnesses of an elastic filament, whose markers move with the fluid,
+/// in lattice units.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Filament {
+    pub stretching: Scalar,
+    pub bending:    Scalar,
+    /// Stiffness of the springs pinning the tethered markers to their
+    /// reference positions.
+    pub tether:     Scalar,
+    pub tethered:   Vec<usize>,
+}
+
+/// How the markers of an immersed body move.
+pub enum Motion {
+    /// The position and velocity of a marker as a function of time and of its
+    /// reference position. The fluid is forced to follow the markers.
+    Prescribed(Box<Fn(Scalar, Point) -> (Point, Point)>),
+    /// The markers move with the fluid and push back on it elastically.
+    Elastic(Filament),
+}
+
+impl Motion {
+    pub fn fixed() -> Self {
+        Motion::Prescribed(Box::new(|_, r| (r, [0.0, 0.0])))
+    }
+
+    /// Harmonic translation with the given amplitude (in lattice nodes) and
+    /// period (in physical time).
+    pub fn oscillating(amplitude: Point, period: Scalar) -> Self {
+        let omega = 2.0 * std::f32::consts::PI / period;
+        Motion::Prescribed(Box::new(move |t, r| {
+            let (s, c) = (omega * t).sin_cos();
+            ([r[0] + amplitude[0] * s, r[1] + amplitude[1] * s],
+             [amplitude[0] * omega * c, amplitude[1] * omega * c])
+        }))
+    }
+
+    /// Harmonic rotation about the given pivot with the given angular
+    /// amplitude (in radians) and period (in physical time).
+    pub fn flapping(pivot: Point, amplitude: Scalar, period: Scalar) -> Self {
+        let omega = 2.0 * std::f32::consts::PI / period;
+        Motion::Prescribed(Box::new(move |t, r| {
+            let theta = amplitude * (omega * t).sin();
+            let theta_dot = amplitude * omega * (omega * t).cos();
+            let (s, c) = theta.sin_cos();
+            let (dx, dy) = (r[0] - pivot[0], r[1] - pivot[1]);
+            let (rx, ry) = (c * dx - s * dy, s * dx + c * dy);
+            ([pivot[0] + rx, pivot[1] + ry],
+             [-theta_dot * ry, theta_dot * rx])
+        }))
+    }
+
+    pub fn elastic(filament: Filament) -> Self {
+        Motion::Elastic(filament)
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+pub struct ImmersedBody {
+    pub markers:   Vec<Marker>,
+    pub reference: Vec<Point>,
+    pub motion:    Motion,
+    /// The rest distance between neighbouring markers, in lattice nodes.
+    pub spacing:   Scalar,
+}
+
+impl ImmersedBody {
+    pub fn new(reference: Vec<Point>, spacing: Scalar, motion: Motion) -> Self {
+        let markers = reference.iter().map(|r| {
+            Marker { position: *r, velocity: [0.0, 0.0], force: [0.0, 0.0] }
+        }).collect();
+        ImmersedBody {
+            markers:   markers,
+            reference: reference,
+            motion:    motion,
+            spacing:   spacing,
+        }
+    }
+
+    /// A closed circle of markers, spaced roughly one lattice node apart.
+    pub fn circle(center: Point, radius: Scalar, motion: Motion) -> Self {
+        let count = Scalar::ceil(2.0 * std::f32::consts::PI * radius) as usize;
+        let mut reference = Vec::with_capacity(count);
+        for k in 0 .. count {
+            let angle = 2.0 * std::f32::consts::PI * (k as Scalar) / (count as Scalar);
+            reference.push([center[0] + radius * angle.cos(),
+                            center[1] + radius * angle.sin()]);
+        }
+        let spacing = 2.0 * std::f32::consts::PI * radius / (count as Scalar);
+        ImmersedBody::new(reference, spacing, motion)
+    }
+
+    /// An open line of markers, spaced roughly one lattice node apart.
+    pub fn segment(start: Point, end: Point, motion: Motion) -> Self {
+        let (dx, dy) = (end[0] - start[0], end[1] - start[1]);
+        let length = (dx * dx + dy * dy).sqrt();
+        let count = Scalar::ceil(length) as usize + 1;
+        let mut reference = Vec::with_capacity(count);
+        for k in 0 .. count {
+            let s = (k as Scalar) / ((count - 1) as Scalar);
+            reference.push([start[0] + s * dx, start[1] + s * dy]);
+        }
+        let spacing = length / ((count - 1) as Scalar);
+        ImmersedBody::new(reference, spacing, motion)
+    }
+
+    fn elastic_forces(&self, filament: &Filament) -> Vec<Point> {
+        let n = self.markers.len();
+        let x = |k: usize| self.markers[k].position;
+        let mut forces = vec![[0.0, 0.0]; n];
+
+        for k in 0 .. n.saturating_sub(1) {
+            let (a, b) = (x(k), x(k + 1));
+            let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+            let length = (dx * dx + dy * dy).sqrt().max(std::f32::EPSILON);
+            let tension = filament.stretching * (length / self.spacing - 1.0);
+            let (tx, ty) = (tension * dx / length, tension * dy / length);
+            forces[k][0]     += tx;
+            forces[k][1]     += ty;
+            forces[k + 1][0] -= tx;
+            forces[k + 1][1] -= ty;
+        }
+
+        let kb = filament.bending / self.spacing.powi(4);
+        for k in 2 .. n.saturating_sub(2) {
+            for d in 0 .. 2 {
+                let fourth
+                    = x(k - 2)[d] - 4.0 * x(k - 1)[d] + 6.0 * x(k)[d]
+                    - 4.0 * x(k + 1)[d] + x(k + 2)[d];
+                forces[k][d] -= kb * fourth;
+            }
+        }
+
+        for &k in &filament.tethered {
+            for d in 0 .. 2 {
+                forces[k][d] += filament.tether * (self.reference[k][d] - x(k)[d]);
+            }
+        }
+
+        forces
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// Immersed boundary coupling between a set of bodies and a lattice.
+///
+/// Prescribed bodies use the direct forcing of Uhlmann, "An immersed boundary
+/// method with direct forcing for the simulation of particulate flows":
+/// each marker asks for the force that brings the interpolated fluid velocity
+/// to its own in one time step. Elastic bodies exert the force derived from
+/// their energy and are then advected with the fluid.
+pub struct ImmersedBoundary {
+    pub bodies: Vec<ImmersedBody>,
+    pub kernel: Kernel,
+}
+
+impl ImmersedBoundary {
+    pub fn new(kernel: Kernel) -> Self {
+        ImmersedBoundary { bodies: Vec::new(), kernel: kernel }
+    }
+
+    /// Advance the lattice and the bodies by one time step. The fluid steps
+    /// first, and the markers then force the state it reached.
+    pub fn step<L: Lattice>(&mut self, state: &mut State<L>) {
+        state.step();
+
+        let size = state.size();
+        let (w, h) = size;
+        let dt = state.delta_t();
+        let time = state.time;
+
+        let density = state.density().get_underlying();
+        let (vx, vy) = {
+            let (vx, vy) = state.velocity();
+            (vx.get_underlying(), vy.get_underlying())
+        };
+
+        let mut fx = vec![0.0; w * h];
+        let mut fy = vec![0.0; w * h];
+
+        for body in &mut self.bodies {
+            let elastic = match body.motion {
+                Motion::Prescribed(ref f) => {
+                    for (marker, r) in body.markers.iter_mut().zip(&body.reference) {
+                        let (position, velocity) = f(time, *r);
+                        marker.position = position;
+                        // From nodes per unit of physical time to nodes per step.
+                        marker.velocity = [velocity[0] * dt, velocity[1] * dt];
+                    }
+                    None
+                },
+                Motion::Elastic(ref filament) => {
+                    Some(body.elastic_forces(filament))
+                },
+            };
+
+            for (k, marker) in body.markers.iter_mut().enumerate() {
+                let stencil = self.kernel.stencil(marker.position, size);
+                let mut rho = 0.0;
+                let mut u = [0.0, 0.0];
+                for &(i, weight) in &stencil {
+                    rho  += weight * density[i];
+                    u[0] += weight * vx[i];
+                    u[1] += weight * vy[i];
+                }
+
+                marker.force = match elastic {
+                    Some(ref forces) => forces[k],
+                    None => [rho * (marker.velocity[0] - u[0]),
+                             rho * (marker.velocity[1] - u[1])],
+                };
+
+                for &(i, weight) in &stencil {
+                    fx[i] += marker.force[0] * weight * body.spacing;
+                    fy[i] += marker.force[1] * weight * body.spacing;
+                }
+            }
+        }
+
+        // `State::apply_force` takes the force per unit of physical time.
+        let force = (Matrix::new(&fx, size).unwrap().scale(1.0 / dt),
+                     Matrix::new(&fy, size).unwrap().scale(1.0 / dt));
+        state.apply_force(&force);
+
+        // Elastic markers move with the fluid they have just forced.
+        let elastic = self.bodies.iter().any(|b| match b.motion {
+            Motion::Elastic(_) => true,
+            _ => false,
+        });
+        if !elastic { return; }
+        let (vx, vy) = {
+            let (vx, vy) = state.velocity();
+            (vx.get_underlying(), vy.get_underlying())
+        };
+        for body in &mut self.bodies {
+            if let Motion::Elastic(_) = body.motion {
+                for marker in &mut body.markers {
+                    let mut u = [0.0, 0.0];
+                    for (i, weight) in self.kernel.stencil(marker.position, size) {
+                        u[0] += weight * vx[i];
+                        u[1] += weight * vy[i];
+                    }
+                    marker.velocity = u;
+                    marker.position[0] += u[0];
+                    marker.position[1] += u[1];
+                }
+            }
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
@@ -0,0 +1,328 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use super::lbm::{Lattice, Scalar, State};
+use super::probe::Field;
+use super::colormap::{ColorScale, Colormap, Range};
+use super::legend::{self, TextRenderer};
+use super::render::{render_scalar_field, render_vector_field};
+use super::panel::Plot;
+use super::display::{Drawable, RGB, PixelPos};
+
+// -----------------------------------------------------------------------------
+
+/// The recent values of a quantity over time, e.g. the drag.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Series {
+    pub title:    String,
+    /// `(time, value)`, oldest first.
+    pub points:   std::collections::VecDeque<(Scalar, Scalar)>,
+    /// The oldest points are dropped beyond this many.
+    pub capacity: usize,
+}
+
+impl Series {
+    pub fn new(title: &str, capacity: usize) -> Self {
+        Series {
+            title:    title.to_string(),
+            points:   std::collections::VecDeque::new(),
+            capacity: capacity,
+        }
+    }
+
+    pub fn push(&mut self, time: Scalar, value: Scalar) {
+        self.points.push_back((time, value));
+        while self.points.len() > self.capacity { self.points.pop_front(); }
+    }
+}
+
+/// What a tile shows.
+pub enum Content {
+    /// Coloured with the scale of the tile.
+    Scalar(Field),
+    /// Hue for the direction and brightness for the magnitude.
+    Velocity,
+    MomentumDensity,
+    /// Plotted over the tile by the control panel.
+    Series(Series),
+}
+
+impl Content {
+    /// The contents a field tile cycles through.
+    pub fn fields() -> Vec<Content> {
+        vec![Content::Scalar(Field::Density),
+             Content::Scalar(Field::Speed),
+             Content::Scalar(Field::Vorticity),
+             Content::Scalar(Field::Pressure),
+             Content::Velocity,
+             Content::MomentumDensity]
+    }
+
+    /// The field of the given name, e.g. `"vorticity"` or
+    /// `"momentum_density"`, ignoring case.
+    pub fn from_name(name: &str) -> Option<Content> {
+        let key = |s: &str| s.to_lowercase().replace(' ', "_");
+        Content::fields().into_iter().find(|c| key(&c.name()) == key(name))
+    }
+
+    pub fn name(&self) -> String {
+        match *self {
+            Content::Scalar(ref field) => {
+                let name = field.name().replace('_', " ");
+                let mut chars = name.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => name,
+                }
+            },
+            Content::Velocity        => "Velocity".to_string(),
+            Content::MomentumDensity => "Momentum density".to_string(),
+            Content::Series(ref s)   => s.title.clone(),
+        }
+    }
+
+    /// The label of the colour bar. The vector fields are coloured by
+    /// their magnitude.
+    pub fn label(&self) -> String {
+        match *self {
+            Content::Velocity => "Speed".to_string(),
+            _ => self.name(),
+        }
+    }
+
+    pub fn is_field(&self) -> bool {
+        match *self {
+            Content::Series(_) => false,
+            _ => true,
+        }
+    }
+
+    /// Whether the field changes sign about a meaningful zero.
+    pub fn is_signed(&self) -> bool {
+        match *self {
+            Content::Scalar(Field::Vorticity)
+                | Content::Scalar(Field::VelocityX)
+                | Content::Scalar(Field::VelocityY) => true,
+            _ => false,
+        }
+    }
+}
+
+pub struct Tile {
+    pub content: Content,
+    pub scale:   ColorScale,
+    /// Those the field was mapped with in the last frame.
+    pub limits:  std::cell::Cell<(Scalar, Scalar)>,
+}
+
+impl Tile {
+    pub fn new(content: Content) -> Self {
+        let mut tile = Tile {
+            content: Content::Velocity,
+            scale:   ColorScale::new(Colormap::Viridis, Range::default()),
+            limits:  std::cell::Cell::new((0.0, 1.0)),
+        };
+        tile.set_content(content);
+        tile
+    }
+
+    /// Show something else, with a range to match: signed fields get a
+    /// diverging colour map centred on zero.
+    pub fn set_content(&mut self, content: Content) {
+        if content.is_signed() {
+            self.scale.set_colormap(Colormap::RdBu);
+            self.scale.range = Range::Symmetric(None);
+        } else {
+            if self.scale.lut.colormap.is_diverging() {
+                self.scale.set_colormap(Colormap::Viridis);
+            }
+            // A fixed range of one field means nothing for another.
+            self.scale.range = Range::default();
+        }
+        self.scale.label = content.label();
+        self.content = content;
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// Tiles of the lattice side by side, row by row, each with its own field
+/// and colour scale.
+pub struct Layout {
+    pub lattice:   (usize, usize),
+    pub columns:   usize,
+    pub tiles:     Vec<Tile>,
+    /// The tile that keys and the control panel act on.
+    pub selected:  usize,
+    /// Whether to outline the selected tile.
+    pub highlight: bool,
+}
+
+impl Layout {
+    pub fn new(lattice: (usize, usize), columns: usize) -> Self {
+        assert!(columns > 0, "a layout needs at least one column");
+        Layout {
+            lattice:   lattice,
+            columns:   columns,
+            tiles:     Vec::new(),
+            selected:  0,
+            highlight: true,
+        }
+    }
+
+    /// The named fields, as by `Content::from_name`, in rows of at most
+    /// `columns` tiles.
+    pub fn of_fields(lattice: (usize, usize), names: &[String], columns: usize)
+                     -> Result<Self, String> {
+        let mut layout = Layout::new(lattice, columns);
+        for name in names {
+            match Content::from_name(name) {
+                Some(content) => layout = layout.with_tile(content),
+                None => return Err(format!("unknown field {:?}", name)),
+            }
+        }
+        if layout.tiles.is_empty() { return Err("no fields to show".to_string()); }
+        Ok(layout)
+    }
+
+    pub fn single(lattice: (usize, usize), content: Content) -> Self {
+        Layout::new(lattice, 1).with_tile(content)
+    }
+
+    pub fn with_tile(mut self, content: Content) -> Self {
+        self.tiles.push(Tile::new(content));
+        self
+    }
+
+    pub fn rows(&self) -> usize {
+        std::cmp::max((self.tiles.len() + self.columns - 1) / self.columns, 1)
+    }
+
+    /// The size in pixels of the whole layout.
+    pub fn size(&self) -> (usize, usize) {
+        let columns = std::cmp::max(std::cmp::min(self.columns, self.tiles.len()), 1);
+        (columns * self.lattice.0, self.rows() * self.lattice.1)
+    }
+
+    /// The top left pixel of a tile.
+    pub fn origin(&self, tile: usize) -> (usize, usize) {
+        ((tile % self.columns) * self.lattice.0, (tile / self.columns) * self.lattice.1)
+    }
+
+    /// The tile under a pixel, and the lattice point under it within the
+    /// tile.
+    pub fn locate(&self, pixel: [f64; 2]) -> Option<(usize, [f64; 2])> {
+        let (w, h) = (self.lattice.0 as f64, self.lattice.1 as f64);
+        if (pixel[0] < 0.0) || (pixel[1] < 0.0) { return None; }
+        let (column, row) = ((pixel[0] / w) as usize, (pixel[1] / h) as usize);
+        let tile = row * self.columns + column;
+        if (column >= self.columns) || (tile >= self.tiles.len()) { return None; }
+        Some((tile, self.relative(tile, pixel)))
+    }
+
+    /// The lattice point under a pixel, relative to a tile, even if the
+    /// pixel lies outside of it, e.g. while dragging.
+    pub fn relative(&self, tile: usize, pixel: [f64; 2]) -> [f64; 2] {
+        let (x, y) = self.origin(tile);
+        [pixel[0] - x as f64, pixel[1] - y as f64]
+    }
+
+    pub fn selected_tile(&self) -> &Tile {
+        &self.tiles[self.selected]
+    }
+
+    pub fn selected_tile_mut(&mut self) -> &mut Tile {
+        &mut self.tiles[self.selected]
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.tiles.len();
+    }
+
+    /// Add a point to every series with the given title.
+    pub fn record(&mut self, title: &str, time: Scalar, value: Scalar) {
+        for tile in &mut self.tiles {
+            if let Content::Series(ref mut series) = tile.content {
+                if series.title == title { series.push(time, value); }
+            }
+        }
+    }
+
+    /// Draw every field tile, each decorated e.g. with the geometry once its
+    /// field is drawn. The series are left to `plots`.
+    pub fn render<L, D, F>(&self, state: &State<L>, text: &TextRenderer, buf: &mut D,
+                           decorate: F)
+        where L: Lattice, D: Drawable, F: Fn(&mut D) {
+        let (w, h) = (self.lattice.0 as u32, self.lattice.1 as u32);
+        if (self.tiles.len() == 1) && (buf.dimensions() == (w, h)) {
+            self.render_tile(&self.tiles[0], state, text, buf, &decorate);
+            return;
+        }
+        for (k, tile) in self.tiles.iter().enumerate() {
+            let (x0, y0) = self.origin(k);
+            let mut target = D::new((w, h), RGB(0, 0, 0));
+            if tile.content.is_field() {
+                self.render_tile(tile, state, text, &mut target, &decorate);
+            }
+            if self.highlight && (k == self.selected) {
+                outline(&mut target, RGB(255, 255, 255));
+            }
+            buf.set_region(PixelPos(x0 as u32, y0 as u32), (w, h), &target.get_pixels());
+        }
+    }
+
+    fn render_tile<L, D, F>(&self, tile: &Tile, state: &State<L>, text: &TextRenderer,
+                            buf: &mut D, decorate: &F)
+        where L: Lattice, D: Drawable, F: Fn(&mut D) {
+        let limits = match tile.content {
+            Content::Scalar(ref field) => {
+                render_scalar_field(&field.evaluate(state), &tile.scale, buf)
+            },
+            Content::Velocity => {
+                render_vector_field(&state.velocity(), &tile.scale, buf)
+            },
+            Content::MomentumDensity => {
+                render_vector_field(&state.momentum_density(), &tile.scale, buf)
+            },
+            Content::Series(_) => return,
+        };
+        tile.limits.set(limits);
+        decorate(buf);
+        // The vector fields are coloured by direction, so their colours are
+        // not those of the colour map.
+        if let Content::Scalar(_) = tile.content {
+            legend::draw_colorbar(buf, text, &tile.scale, limits);
+        }
+    }
+
+    /// The series to plot over their tiles, in pixels of the layout.
+    pub fn plots(&self) -> Vec<Plot> {
+        let (w, h) = (self.lattice.0 as f64, self.lattice.1 as f64);
+        self.tiles.iter().enumerate().filter_map(|(k, tile)| match tile.content {
+            Content::Series(ref series) => {
+                let (x, y) = self.origin(k);
+                Some(Plot {
+                    title:  series.title.clone(),
+                    rect:   [x as f64, y as f64, w, h],
+                    points: series.points.iter().cloned().collect(),
+                })
+            },
+            _ => None,
+        }).collect()
+    }
+}
+
+/// A one pixel frame around the edge of the drawable.
+fn outline<D: Drawable>(buf: &mut D, color: RGB) {
+    let (w, h) = buf.dimensions();
+    for x in 0 .. w {
+        buf.set_pixel(PixelPos(x, 0), color);
+        buf.set_pixel(PixelPos(x, h - 1), color);
+    }
+    for y in 0 .. h {
+        buf.set_pixel(PixelPos(0, y), color);
+        buf.set_pixel(PixelPos(w - 1, y), color);
+    }
+}
+
+// -----------------------------------------------------------------------------
@@ -3,6 +3,8 @@
 use std;
 use arrayfire as af;
 use super::matrix;
+use super::refinement::RefinedBlock;
+use super::schedule::{Inlet, ScheduledForce};
 
 use arrayfire::device_mem_info;
 
@@ -18,6 +20,11 @@ pub type Scalar = f32;
 pub struct Vector(Scalar, Scalar);
 
 impl Vector {
+    #[inline(always)]
+    pub fn new(x: Scalar, y: Scalar) -> Self {
+        Vector(x, y)
+    }
+
     #[inline(always)]
     pub fn to_complex(&self) -> matrix::Complex<Scalar> {
         matrix::Complex::new(self.0, self.1)
@@ -51,7 +58,7 @@ pub fn compute_equilibrium(
     assert_eq!(size, vx.get_shape());
     assert_eq!(size, vy.get_shape());
     let v2 = vx.hadamard(&vx) + vy.hadamard(&vy);
-    let cs = discretization.isothermal_speed_of_sound();
+    let cs = discretization.speed_of_sound(lattice_speed_of_sound_squared(directions));
     let cs2 = cs * cs;
     let cs4 = cs2 * cs2;
     let mut result = Vec::with_capacity(directions.len());
@@ -70,6 +77,68 @@ pub fn compute_equilibrium(
     result
 }
 
+/// The Hermite expansion of the Maxwellian at the given density, velocity and
+/// temperature (relative to the reference temperature of the lattice),
+/// truncated at the given order, following "Kinetic theory representation of
+/// hydrodynamics: a way beyond the Navier-Stokes equation" by Shan, Yuan and
+/// Chen.
+///
+/// At second order and unit temperature this is `compute_equilibrium`. The
+/// third order removes the cubic velocity error in the momentum flux, and the
+/// fourth order, with a varying temperature, recovers the energy equation.
+/// The directions must integrate Hermite polynomials of that order exactly.
+pub fn compute_hermite_equilibrium(
+    density:        Matrix,
+    velocity:       (Matrix, Matrix),
+    temperature:    Matrix,
+    order:          usize,
+    directions:     &[Direction],
+    discretization: Discretization,
+) -> Populations {
+    assert!((order >= 1) && (order <= 4), "unsupported equilibrium order {}", order);
+    let size = density.get_shape();
+    let (vx, vy) = velocity;
+    assert_eq!(size, vx.get_shape());
+    assert_eq!(size, vy.get_shape());
+    assert_eq!(size, temperature.get_shape());
+    let v2 = vx.hadamard(&vx) + vy.hadamard(&vy);
+    let theta = temperature.shift(-1.0);
+    let cs = discretization.speed_of_sound(lattice_speed_of_sound_squared(directions));
+    let a = cs * cs;
+    let d = 2.0;
+    let mut result = Vec::with_capacity(directions.len());
+    for dir in directions {
+        let (cx, cy) = dir.c_vector.to_pair();
+        let c2 = cx * cx + cy * cy;
+        let vc = vx.scale(cx) + vy.scale(cy);
+        let vc2 = vc.hadamard(&vc);
+        let mut sum = Matrix::new_filled(1.0, size) + vc.scale(1.0 / a);
+        if order >= 2 {
+            let h2 = &vc2 - &v2.scale(a) + theta.scale(a * (c2 - a * d));
+            sum += h2.scale(1.0 / (2.0 * a.powi(2)));
+        }
+        if order >= 3 {
+            let h3 = vc2.hadamard(&vc)
+                - vc.hadamard(&v2).scale(3.0 * a)
+                + theta.hadamard(&vc).scale(3.0 * a * (c2 - a * (d + 2.0)));
+            sum += h3.scale(1.0 / (6.0 * a.powi(3)));
+        }
+        if order >= 4 {
+            let mixed = vc2.scale(c2 - a * (d + 4.0)) + v2.scale(a * (a * (d + 2.0) - c2));
+            let h4 = vc2.hadamard(&vc2)
+                - vc2.hadamard(&v2).scale(6.0 * a)
+                + v2.hadamard(&v2).scale(3.0 * a * a)
+                + theta.hadamard(&mixed).scale(6.0 * a)
+                + theta.hadamard(&theta).scale(
+                    3.0 * a * a * (c2 * c2 - 2.0 * a * (d + 2.0) * c2 + a * a * d * (d + 2.0)));
+            sum += h4.scale(1.0 / (24.0 * a.powi(4)));
+        }
+        let pop = density.scale(dir.w_scalar).hadamard(&sum);
+        result.push((dir.clone(), pop));
+    }
+    result
+}
+
 // -----------------------------------------------------------------------------
 
 #[derive(PartialEq, PartialOrd, Debug, Clone, Copy)]
@@ -83,6 +152,13 @@ impl Discretization {
     pub fn isothermal_speed_of_sound(&self) -> Scalar {
         self.delta_x / (Scalar::sqrt(3.0) * self.delta_t)
     }
+
+    /// The speed of sound of a velocity set whose squared speed of sound in
+    /// lattice units is `cs2`.
+    #[inline(always)]
+    pub fn speed_of_sound(&self, cs2: Scalar) -> Scalar {
+        cs2.sqrt() * self.delta_x / self.delta_t
+    }
 }
 
 // -----------------------------------------------------------------------------
@@ -91,13 +167,264 @@ impl Discretization {
 pub struct Direction {
     w_scalar: Scalar,
     c_vector: Vector,
-    stencil:  Matrix,
+}
+
+impl Direction {
+    #[inline(always)]
+    pub fn weight(&self) -> Scalar {
+        self.w_scalar
+    }
+
+    #[inline(always)]
+    pub fn c_vector(&self) -> Vector {
+        self.c_vector
+    }
+}
+
+/// The squared speed of sound of a set of directions, in lattice units.
+pub fn lattice_speed_of_sound_squared(directions: &[Direction]) -> Scalar {
+    directions.iter().map(|d| d.w_scalar * d.c_vector.0 * d.c_vector.0).sum()
+}
+
+// -----------------------------------------------------------------------------
+
+/// A discrete velocity set, from which lattices are derived.
+#[derive(PartialEq, Debug)]
+pub struct Descriptor {
+    pub name:       &'static str,
+    pub dimension:  usize,
+    /// The lattice velocities, with the unused components set to zero.
+    pub velocities: &'static [[i8; 3]],
+    pub weights:    &'static [Scalar],
+    /// The index of the velocity opposite to each velocity.
+    pub opposite:   &'static [usize],
+    /// The squared speed of sound, in lattice units.
+    pub cs2:        Scalar,
+    /// The highest order of Hermite polynomials whose moments the velocity
+    /// set integrates exactly, and so the highest order of equilibrium it
+    /// supports.
+    pub order:      usize,
+}
+
+impl Descriptor {
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.velocities.len()
+    }
+
+    /// The directions of a two-dimensional velocity set.
+    pub fn directions(&self) -> Vec<Direction> {
+        assert!(self.dimension == 2, "{} is not two-dimensional", self.name);
+        self.velocities.iter().zip(self.weights).map(|(c, w)| {
+            Direction {
+                w_scalar: *w,
+                c_vector: Vector(c[0] as Scalar, c[1] as Scalar),
+            }
+        }).collect()
+    }
+}
+
+/// A type-level handle on a descriptor.
+pub trait VelocitySet: Clone {
+    const DESCRIPTOR: &'static Descriptor;
+}
+
+/// The standard velocity sets, as tabulated in "The Lattice Boltzmann Method:
+/// Principles and Practice" by Krüger et al.
+pub mod velocity_set {
+    use super::{Descriptor, VelocitySet};
+
+    #[derive(Clone)] pub struct D2Q5;
+    #[derive(Clone)] pub struct D2Q9;
+    // The three-dimensional sets are data only: `DescriptorLattice` rejects
+    // them.
+    #[derive(Clone)] pub struct D3Q15;
+    #[derive(Clone)] pub struct D3Q19;
+    #[derive(Clone)] pub struct D3Q27;
+    #[derive(Clone)] pub struct D2V17;
+    #[derive(Clone)] pub struct D2V37;
+
+    impl VelocitySet for D2Q5 {
+        const DESCRIPTOR: &'static Descriptor = &Descriptor {
+            name:       "D2Q5",
+            dimension:  2,
+            velocities: &[[0, 0, 0],
+                          [1, 0, 0], [0, 1, 0], [-1, 0, 0], [0, -1, 0]],
+            weights:    &[1.0 / 3.0,
+                          1.0 / 6.0, 1.0 / 6.0, 1.0 / 6.0, 1.0 / 6.0],
+            opposite:   &[0, 3, 4, 1, 2],
+            cs2:        1.0 / 3.0,
+            order:      1,
+        };
+    }
+
+    impl VelocitySet for D2Q9 {
+        const DESCRIPTOR: &'static Descriptor = &Descriptor {
+            name:       "D2Q9",
+            dimension:  2,
+            velocities: &[[0, 0, 0],
+                          [1, 0, 0], [0, 1, 0], [-1, 0, 0], [0, -1, 0],
+                          [1, 1, 0], [-1, 1, 0], [-1, -1, 0], [1, -1, 0]],
+            weights:    &[4.0 / 9.0,
+                          1.0 / 9.0, 1.0 / 9.0, 1.0 / 9.0, 1.0 / 9.0,
+                          1.0 / 36.0, 1.0 / 36.0, 1.0 / 36.0, 1.0 / 36.0],
+            opposite:   &[0, 3, 4, 1, 2, 7, 8, 5, 6],
+            cs2:        1.0 / 3.0,
+            order:      2,
+        };
+    }
+
+    impl VelocitySet for D3Q15 {
+        const DESCRIPTOR: &'static Descriptor = &Descriptor {
+            name:       "D3Q15",
+            dimension:  3,
+            velocities: &[[0, 0, 0],
+                          [1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0],
+                          [0, 0, 1], [0, 0, -1],
+                          [1, 1, 1], [-1, -1, -1], [1, 1, -1], [-1, -1, 1],
+                          [1, -1, 1], [-1, 1, -1], [-1, 1, 1], [1, -1, -1]],
+            weights:    &[2.0 / 9.0,
+                          1.0 / 9.0, 1.0 / 9.0, 1.0 / 9.0, 1.0 / 9.0,
+                          1.0 / 9.0, 1.0 / 9.0,
+                          1.0 / 72.0, 1.0 / 72.0, 1.0 / 72.0, 1.0 / 72.0,
+                          1.0 / 72.0, 1.0 / 72.0, 1.0 / 72.0, 1.0 / 72.0],
+            opposite:   &[0, 2, 1, 4, 3, 6, 5, 8, 7, 10, 9, 12, 11, 14, 13],
+            cs2:        1.0 / 3.0,
+            order:      2,
+        };
+    }
+
+    impl VelocitySet for D3Q19 {
+        const DESCRIPTOR: &'static Descriptor = &Descriptor {
+            name:       "D3Q19",
+            dimension:  3,
+            velocities: &[[0, 0, 0],
+                          [1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0],
+                          [0, 0, 1], [0, 0, -1],
+                          [1, 1, 0], [-1, -1, 0], [1, 0, 1], [-1, 0, -1],
+                          [0, 1, 1], [0, -1, -1], [1, -1, 0], [-1, 1, 0],
+                          [1, 0, -1], [-1, 0, 1], [0, 1, -1], [0, -1, 1]],
+            weights:    &[1.0 / 3.0,
+                          1.0 / 18.0, 1.0 / 18.0, 1.0 / 18.0, 1.0 / 18.0,
+                          1.0 / 18.0, 1.0 / 18.0,
+                          1.0 / 36.0, 1.0 / 36.0, 1.0 / 36.0, 1.0 / 36.0,
+                          1.0 / 36.0, 1.0 / 36.0, 1.0 / 36.0, 1.0 / 36.0,
+                          1.0 / 36.0, 1.0 / 36.0, 1.0 / 36.0, 1.0 / 36.0],
+            opposite:   &[0, 2, 1, 4, 3, 6, 5, 8, 7, 10, 9,
+                          12, 11, 14, 13, 16, 15, 18, 17],
+            cs2:        1.0 / 3.0,
+            order:      2,
+        };
+    }
+
+    impl VelocitySet for D3Q27 {
+        const DESCRIPTOR: &'static Descriptor = &Descriptor {
+            name:       "D3Q27",
+            dimension:  3,
+            velocities: &[[0, 0, 0],
+                          [1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0],
+                          [0, 0, 1], [0, 0, -1],
+                          [1, 1, 0], [-1, -1, 0], [1, 0, 1], [-1, 0, -1],
+                          [0, 1, 1], [0, -1, -1], [1, -1, 0], [-1, 1, 0],
+                          [1, 0, -1], [-1, 0, 1], [0, 1, -1], [0, -1, 1],
+                          [1, 1, 1], [-1, -1, -1], [1, 1, -1], [-1, -1, 1],
+                          [1, -1, 1], [-1, 1, -1], [-1, 1, 1], [1, -1, -1]],
+            weights:    &[8.0 / 27.0,
+                          2.0 / 27.0, 2.0 / 27.0, 2.0 / 27.0, 2.0 / 27.0,
+                          2.0 / 27.0, 2.0 / 27.0,
+                          1.0 / 54.0, 1.0 / 54.0, 1.0 / 54.0, 1.0 / 54.0,
+                          1.0 / 54.0, 1.0 / 54.0, 1.0 / 54.0, 1.0 / 54.0,
+                          1.0 / 54.0, 1.0 / 54.0, 1.0 / 54.0, 1.0 / 54.0,
+                          1.0 / 216.0, 1.0 / 216.0, 1.0 / 216.0, 1.0 / 216.0,
+                          1.0 / 216.0, 1.0 / 216.0, 1.0 / 216.0, 1.0 / 216.0],
+            opposite:   &[0, 2, 1, 4, 3, 6, 5, 8, 7, 10, 9,
+                          12, 11, 14, 13, 16, 15, 18, 17,
+                          20, 19, 22, 21, 24, 23, 26, 25],
+            cs2:        1.0 / 3.0,
+            order:      2,
+        };
+    }
+
+    /// The weights and speed of sound of the extended velocity sets are those
+    /// of "Kinetic theory representation of hydrodynamics: a way beyond the
+    /// Navier-Stokes equation" by Shan, Yuan and Chen, rescaled to integer
+    /// velocities.
+    impl VelocitySet for D2V17 {
+        const DESCRIPTOR: &'static Descriptor = &Descriptor {
+            name:       "D2V17",
+            dimension:  2,
+            velocities: &[[0, 0, 0],
+                          [1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0],
+                          [1, 1, 0], [-1, -1, 0], [1, -1, 0], [-1, 1, 0],
+                          [2, 2, 0], [-2, -2, 0], [2, -2, 0], [-2, 2, 0],
+                          [3, 0, 0], [-3, 0, 0], [0, 3, 0], [0, -3, 0]],
+            weights:    &[0.402005146909,
+                          0.116154866498, 0.116154866498,
+                          0.116154866498, 0.116154866498,
+                          0.033006353623, 0.033006353623,
+                          0.033006353623, 0.033006353623,
+                          0.0000790786021659, 0.0000790786021659,
+                          0.0000790786021659, 0.0000790786021659,
+                          0.000258414549787, 0.000258414549787,
+                          0.000258414549787, 0.000258414549787],
+            opposite:   &[0, 2, 1, 4, 3, 6, 5, 8, 7, 10, 9, 12, 11, 14, 13, 16, 15],
+            cs2:        0.370251867018,
+            order:      3,
+        };
+    }
+
+    impl VelocitySet for D2V37 {
+        const DESCRIPTOR: &'static Descriptor = &Descriptor {
+            name:       "D2V37",
+            dimension:  2,
+            velocities: &[[0, 0, 0],
+                          [1, 0, 0], [-1, 0, 0], [0, 1, 0], [0, -1, 0],
+                          [1, 1, 0], [-1, -1, 0], [1, -1, 0], [-1, 1, 0],
+                          [2, 0, 0], [-2, 0, 0], [0, 2, 0], [0, -2, 0],
+                          [2, 1, 0], [-2, -1, 0], [2, -1, 0], [-2, 1, 0],
+                          [1, 2, 0], [-1, -2, 0], [1, -2, 0], [-1, 2, 0],
+                          [2, 2, 0], [-2, -2, 0], [2, -2, 0], [-2, 2, 0],
+                          [3, 0, 0], [-3, 0, 0], [0, 3, 0], [0, -3, 0],
+                          [3, 1, 0], [-3, -1, 0], [3, -1, 0], [-3, 1, 0],
+                          [1, 3, 0], [-1, -3, 0], [1, -3, 0], [-1, 3, 0]],
+            weights:    &[0.233150669132,
+                          0.107306091542, 0.107306091542,
+                          0.107306091542, 0.107306091542,
+                          0.0576678598888, 0.0576678598888,
+                          0.0576678598888, 0.0576678598888,
+                          0.0142082161585, 0.0142082161585,
+                          0.0142082161585, 0.0142082161585,
+                          0.00535304900051, 0.00535304900051,
+                          0.00535304900051, 0.00535304900051,
+                          0.00535304900051, 0.00535304900051,
+                          0.00535304900051, 0.00535304900051,
+                          0.00101193759267, 0.00101193759267,
+                          0.00101193759267, 0.00101193759267,
+                          0.000245301027758, 0.000245301027758,
+                          0.000245301027758, 0.000245301027758,
+                          0.000283414252994, 0.000283414252994,
+                          0.000283414252994, 0.000283414252994,
+                          0.000283414252994, 0.000283414252994,
+                          0.000283414252994, 0.000283414252994],
+            opposite:   &[0, 2, 1, 4, 3, 6, 5, 8, 7, 10, 9, 12, 11, 14, 13, 16, 15,
+                          18, 17, 20, 19, 22, 21, 24, 23, 26, 25, 28, 27, 30, 29,
+                          32, 31, 34, 33, 36, 35],
+            cs2:        0.697953322020,
+            order:      4,
+        };
+    }
 }
 
 // -----------------------------------------------------------------------------
 
 pub type Geometry = af::Array<bool>;
 
+/// Move every value of an array by the given lattice velocity, so that the
+/// value at `x` ends up at `x + c`, wrapping around the edges.
+pub fn translate<T: af::HasAfEnum>(array: &af::Array<T>, c: Vector) -> af::Array<T> {
+    af::shift(array, &[c.1 as i32, c.0 as i32, 0, 0])
+}
+
 // -----------------------------------------------------------------------------
 
 pub type Population = Matrix;
@@ -109,10 +436,25 @@ pub type Populations = Vec<(Direction, Population)>;
 // -----------------------------------------------------------------------------
 
 pub trait Lattice {
+    fn from_populations(populations: Populations) -> Self where Self: Sized;
+
+    /// A lattice like this one, e.g. with the same equilibrium, over the
+    /// given populations.
+    fn with_populations(&self, populations: Populations) -> Self where Self: Sized;
+
+    /// The descriptor of every lattice of this type, e.g. to build a
+    /// collision operator before there is a lattice.
+    fn velocity_set() -> &'static Descriptor where Self: Sized;
+
+    fn descriptor(&self) -> &'static Descriptor;
     fn size(&self) -> (usize, usize);
     fn populations(&self) -> &Populations;
     fn populations_mut(&mut self) -> &mut Populations;
-    fn swap_populations(&self) -> Populations;
+
+    /// The populations, each moved to the slot of the opposite direction.
+    fn swap_populations(&self) -> Populations {
+        swap(self.populations(), self.descriptor())
+    }
 
     fn density(&self) -> Matrix {
         let mut result = Matrix::new_filled(0.0, self.size());
@@ -153,10 +495,47 @@ pub trait Lattice {
         (v_x.hadamard(&v_x) + v_y.hadamard(&v_y)).sqrt()
     }
 
+    /// The order of the Hermite expansion of the equilibrium.
+    fn equilibrium_order(&self) -> usize {
+        2
+    }
+
+    /// Whether the equilibrium follows the local temperature rather than the
+    /// reference one.
+    fn is_thermal(&self) -> bool {
+        false
+    }
+
+    /// The temperature relative to the reference one, from the spread of
+    /// the populations about the local velocity.
+    fn temperature(&self, disc: &Discretization) -> Matrix {
+        let cs = disc.speed_of_sound(self.descriptor().cs2);
+        let dimension = self.descriptor().dimension as Scalar;
+        let density = self.density();
+        let (ux, uy) = self.velocity();
+        let mut energy = Matrix::new_filled(0.0, self.size());
+        for (dir, f_i) in self.populations() {
+            let (cx, cy) = dir.c_vector.to_pair();
+            energy += f_i.scale(cx * cx + cy * cy);
+        }
+        (energy.divide(&density) - ux.hadamard(&ux) - uy.hadamard(&uy))
+            .scale(1.0 / (dimension * cs * cs))
+    }
+
     fn equilibrium(&self, disc: &Discretization) -> Populations {
         let directions: Vec<Direction>
             = self.populations().iter().map(|(dir, _)| dir.clone()).collect();
-        compute_equilibrium(self.density(), self.velocity(), &directions, *disc)
+        if (self.equilibrium_order() == 2) && !self.is_thermal() {
+            return compute_equilibrium(self.density(), self.velocity(),
+                                       &directions, *disc);
+        }
+        let temperature = if self.is_thermal() {
+            self.temperature(disc)
+        } else {
+            Matrix::new_filled(1.0, self.size())
+        };
+        compute_hermite_equilibrium(self.density(), self.velocity(), temperature,
+                                    self.equilibrium_order(), &directions, *disc)
     }
 
     fn non_equilibrium(&self, disc: &Discretization) -> Populations {
@@ -172,20 +551,40 @@ pub trait Lattice {
         f_neq
     }
 
-    fn swap_equilibrium(&self, disc: &Discretization) -> Populations;
+    fn swap_equilibrium(&self, disc: &Discretization) -> Populations {
+        swap(&self.equilibrium(disc), self.descriptor())
+    }
+}
+
+fn swap(populations: &Populations, descriptor: &Descriptor) -> Populations {
+    populations.iter().zip(descriptor.opposite).map(|((dir, _), j)| {
+        (dir.clone(), populations[*j].1.clone())
+    }).collect()
 }
 
 // -----------------------------------------------------------------------------
 
+/// A two-dimensional lattice over the velocity set `V`, which must be
+/// two-dimensional too.
 #[derive(Clone)]
-pub struct D2Q9 {
-    size:        (usize, usize),
-    populations: Populations,
+pub struct DescriptorLattice<V> {
+    size:         (usize, usize),
+    populations:  Populations,
+    order:        usize,
+    thermal:      bool,
+    velocity_set: std::marker::PhantomData<V>,
 }
 
-impl D2Q9 {
+pub type D2Q5  = DescriptorLattice<velocity_set::D2Q5>;
+pub type D2Q9  = DescriptorLattice<velocity_set::D2Q9>;
+pub type D2V17 = DescriptorLattice<velocity_set::D2V17>;
+pub type D2V37 = DescriptorLattice<velocity_set::D2V37>;
+
+impl<V: VelocitySet> DescriptorLattice<V> {
     pub fn new(populations: &[Population]) -> Self {
-        assert!(populations.len() == 9);
+        assert!(V::DESCRIPTOR.dimension == 2,
+                "{} cannot make a two-dimensional lattice", V::DESCRIPTOR.name);
+        assert!(populations.len() == V::DESCRIPTOR.len());
 
         let size = populations[0].get_shape();
         for pop in populations { assert_eq!(size, pop.get_shape()); }
@@ -196,93 +595,53 @@ impl D2Q9 {
             vec.push((dir.clone(), pop.clone()))
         }
 
-        D2Q9 { size: size, populations: vec }
+        DescriptorLattice {
+            size:         size,
+            populations:  vec,
+            order:        2,
+            thermal:      false,
+            velocity_set: std::marker::PhantomData,
+        }
     }
 
-    pub fn directions() -> [Direction; 9] {
-        let make_m = |vec: &[i8; 9]| -> Matrix {
-            let mut temp = Vec::new();
-            for x in vec { temp.push(*x as Scalar); }
-            Matrix::new(&temp, (3, 3)).unwrap()
-        };
-
-        let ws: Vec<Scalar> = vec![
-            16.0 / 36.0,
-            4.0  / 36.0,
-            4.0  / 36.0,
-            4.0  / 36.0,
-            4.0  / 36.0,
-            1.0  / 36.0,
-            1.0  / 36.0,
-            1.0  / 36.0,
-            1.0  / 36.0,
-        ];
-
-        let cs: Vec<Vector> = vec![
-            Vector( 0.0,  0.0),
-            Vector( 1.0,  0.0),
-            Vector( 0.0,  1.0),
-            Vector(-1.0,  0.0),
-            Vector( 0.0, -1.0),
-            Vector( 1.0,  1.0),
-            Vector(-1.0,  1.0),
-            Vector(-1.0, -1.0),
-            Vector( 1.0, -1.0),
-        ];
-
-        let ms: Vec<Matrix> = vec![
-            make_m(&[ 0, 0, 0,
-                      0, 1, 0,
-                      0, 0, 0, ]),
-
-            make_m(&[ 0, 0, 0,
-                      1, 0, 0,
-                      0, 0, 0, ]),
-
-            make_m(&[ 0, 0, 0,
-                      0, 0, 0,
-                      0, 1, 0, ]),
-
-            make_m(&[ 0, 0, 0,
-                      0, 0, 1,
-                      0, 0, 0, ]),
-
-            make_m(&[ 0, 1, 0,
-                      0, 0, 0,
-                      0, 0, 0, ]),
+    /// Relax towards the Hermite equilibrium of the given order, at the local
+    /// temperature if `thermal` is set. A thermal equilibrium needs at least
+    /// the third order, and the fourth for the correct energy flux.
+    pub fn with_equilibrium(mut self, order: usize, thermal: bool) -> Self {
+        let descriptor = V::DESCRIPTOR;
+        assert!((order == 2) || (order <= descriptor.order),
+                "{} does not support equilibria of order {}", descriptor.name, order);
+        assert!(!thermal || (order >= 3),
+                "thermal equilibria need at least the third order");
+        self.order = order;
+        self.thermal = thermal;
+        self
+    }
 
-            make_m(&[ 0, 0, 0,
-                      0, 0, 0,
-                      1, 0, 0, ]),
+    pub fn directions() -> Vec<Direction> {
+        V::DESCRIPTOR.directions()
+    }
+}
 
-            make_m(&[ 0, 0, 0,
-                      0, 0, 0,
-                      0, 0, 1, ]),
+impl<V: VelocitySet> Lattice for DescriptorLattice<V> {
+    fn from_populations(populations: Populations) -> Self {
+        let pops: Vec<Population>
+            = populations.into_iter().map(|(_, pop)| pop).collect();
+        Self::new(&pops)
+    }
 
-            make_m(&[ 0, 0, 1,
-                      0, 0, 0,
-                      0, 0, 0, ]),
+    fn with_populations(&self, populations: Populations) -> Self {
+        Self::from_populations(populations).with_equilibrium(self.order, self.thermal)
+    }
 
-            make_m(&[ 1, 0, 0,
-                      0, 0, 0,
-                      0, 0, 0, ]),
-        ];
+    fn velocity_set() -> &'static Descriptor {
+        V::DESCRIPTOR
+    }
 
-        [
-            (Direction { w_scalar: ws[0], c_vector: cs[0], stencil: ms[0].clone() }),
-            (Direction { w_scalar: ws[1], c_vector: cs[1], stencil: ms[1].clone() }),
-            (Direction { w_scalar: ws[2], c_vector: cs[2], stencil: ms[2].clone() }),
-            (Direction { w_scalar: ws[3], c_vector: cs[3], stencil: ms[3].clone() }),
-            (Direction { w_scalar: ws[4], c_vector: cs[4], stencil: ms[4].clone() }),
-            (Direction { w_scalar: ws[5], c_vector: cs[5], stencil: ms[5].clone() }),
-            (Direction { w_scalar: ws[6], c_vector: cs[6], stencil: ms[6].clone() }),
-            (Direction { w_scalar: ws[7], c_vector: cs[7], stencil: ms[7].clone() }),
-            (Direction { w_scalar: ws[8], c_vector: cs[8], stencil: ms[8].clone() }),
-        ]
+    fn descriptor(&self) -> &'static Descriptor {
+        V::DESCRIPTOR
     }
-}
 
-impl Lattice for D2Q9 {
     fn size(&self) -> (usize, usize) {
         self.size.clone()
     }
@@ -295,30 +654,12 @@ impl Lattice for D2Q9 {
         &mut self.populations
     }
 
-    fn swap_populations(&self) -> Populations {
-        let mut new_pops = self.populations.clone();
-        new_pops[1].1 = self.populations[3].1.clone();
-        new_pops[2].1 = self.populations[4].1.clone();
-        new_pops[3].1 = self.populations[1].1.clone();
-        new_pops[4].1 = self.populations[2].1.clone();
-        new_pops[5].1 = self.populations[7].1.clone();
-        new_pops[6].1 = self.populations[8].1.clone();
-        new_pops[7].1 = self.populations[5].1.clone();
-        new_pops[8].1 = self.populations[6].1.clone();
-        new_pops
+    fn equilibrium_order(&self) -> usize {
+        self.order
     }
 
-    fn swap_equilibrium(&self, disc: &Discretization) -> Populations {
-        let mut new_pops = self.equilibrium(disc).clone();
-        new_pops[1].1 = self.populations[3].1.clone();
-        new_pops[2].1 = self.populations[4].1.clone();
-        new_pops[3].1 = self.populations[1].1.clone();
-        new_pops[4].1 = self.populations[2].1.clone();
-        new_pops[5].1 = self.populations[7].1.clone();
-        new_pops[6].1 = self.populations[8].1.clone();
-        new_pops[7].1 = self.populations[5].1.clone();
-        new_pops[8].1 = self.populations[6].1.clone();
-        new_pops
+    fn is_thermal(&self) -> bool {
+        self.thermal
     }
 }
 
@@ -338,6 +679,12 @@ pub trait CollisionOperator<L> {
     fn kinematic_bulk_viscosity(&self, disc: &Discretization) -> Scalar {
         2.0 * self.kinematic_shear_viscosity(disc) / 3.0
     }
+
+    /// The statistics of the over-relaxation parameter in the last
+    /// collision, for operators that solve for one.
+    fn alpha_statistics(&self) -> Option<AlphaStatistics> {
+        None
+    }
 }
 
 // -----------------------------------------------------------------------------
@@ -364,8 +711,8 @@ impl<L: Lattice> CollisionOperator<L> for BGK {
     }
 
     fn kinematic_shear_viscosity(&self, disc: &Discretization) -> Scalar {
-        let (dx, dt) = (disc.delta_x, disc.delta_t);
-        (dx * dx / (3.0 * dt * dt)) * (self.tau - dt / 2.0)
+        let cs = disc.speed_of_sound(L::velocity_set().cs2);
+        cs * cs * (self.tau - disc.delta_t / 2.0)
     }
 }
 
@@ -377,13 +724,17 @@ pub struct TRT {
 }
 
 impl TRT {
+    /// The operator with the given magic parameter and kinematic shear
+    /// viscosity, on a velocity set with the given squared speed of sound in
+    /// lattice units.
     pub fn new(
         lambda:       Scalar,
         ks_viscosity: Scalar,
+        cs2:          Scalar,
         disc:         &Discretization,
     ) -> Self {
         let (dx, dt) = (disc.delta_x, disc.delta_t);
-        let cs = disc.isothermal_speed_of_sound();
+        let cs = disc.speed_of_sound(cs2);
         let tau_plus  = dt * ((ks_viscosity / (cs * cs)) + 0.5);
         let tau_minus = dt * ((lambda / ((tau_plus / dt) - 0.5)) + 0.5);
         TRT { tau_minus: tau_minus, tau_plus: tau_plus }
@@ -445,7 +796,7 @@ impl<L: Lattice> CollisionOperator<L> for TRT {
 
     fn kinematic_shear_viscosity(&self, disc: &Discretization) -> Scalar {
         let (dx, dt) = (disc.delta_x, disc.delta_t);
-        let cs = disc.isothermal_speed_of_sound();
+        let cs = disc.speed_of_sound(L::velocity_set().cs2);
         cs * cs * (self.tau_plus / dt - 0.5)
     }
 }
@@ -455,86 +806,67 @@ impl<L: Lattice> CollisionOperator<L> for TRT {
 /// Entropic multirelaxation Lattice Boltzmann, as described in
 /// "Parallel implementation of Entropic lattice Boltzmann method for flow
 /// past a circular cylinder at high Reynolds number" by Badarch et al.
+///
+/// The shear part of the populations is taken from the Hermite projection of
+/// the stress, as in "Gibbs' principle for the lattice-kinetic theory of
+/// fluid dynamics" by Karlin et al., so any velocity set will do.
 pub struct KBC {
     ks_viscosity: Scalar
 }
 
 impl KBC {
+    /// How far from zero the entropy condition may be at any node. The closed
+    /// form of the stabiliser only satisfies it to leading order.
+    pub const ENTROPY_TOLERANCE: f64 = 1.0e-3;
+
     pub fn new(ks_viscosity: Scalar) -> Self {
         KBC { ks_viscosity: ks_viscosity }
     }
+
+    /// The shear part of the non-equilibrium populations: the Hermite
+    /// projection of the deviatoric non-equilibrium stress, which does not
+    /// depend on the velocity set.
+    fn shear_part<L: Lattice>(lattice: &L, f_eq: &Populations) -> Vec<Matrix> {
+        let f    = lattice.populations();
+        let size = lattice.size();
+        let mut pi_xx = Matrix::new_filled(0.0, size);
+        let mut pi_xy = Matrix::new_filled(0.0, size);
+        let mut pi_yy = Matrix::new_filled(0.0, size);
+        for ((dir, f_i), (_, f_eq_i)) in f.iter().zip(f_eq) {
+            let (cx, cy) = dir.c_vector.to_pair();
+            let f_neq_i = f_i - f_eq_i;
+            pi_xx += f_neq_i.scale(cx * cx);
+            pi_xy += f_neq_i.scale(cx * cy);
+            pi_yy += f_neq_i.scale(cy * cy);
+        }
+        let trace = (&pi_xx + &pi_yy).scale(0.5);
+        let dev_xx = &pi_xx - &trace;
+        let dev_yy = &pi_yy - &trace;
+
+        let cs2 = lattice.descriptor().cs2;
+        f.iter().map(|(dir, _)| {
+            let (cx, cy) = dir.c_vector.to_pair();
+            (dev_xx.scale(cx * cx) + pi_xy.scale(2.0 * cx * cy) + dev_yy.scale(cy * cy))
+                .scale(dir.w_scalar / (2.0 * cs2 * cs2))
+        }).collect()
+    }
 }
 
-impl CollisionOperator<D2Q9> for KBC {
+impl<L: Lattice> CollisionOperator<L> for KBC {
     fn evaluate(
         &self,
-        lattice:        &D2Q9,
+        lattice:        &L,
         equilibrium:    &Populations,
         discretization: &Discretization,
     ) -> Populations {
         let f    = lattice.populations();
         let f_eq = lattice.equilibrium(discretization);
+        let cs2  = lattice.descriptor().cs2;
 
-        let dx = discretization.delta_x;
-
-        let rho  = lattice.density();
-
-        let (u, v) = lattice.velocity();
-        let uv = u.hadamard(&v);
-        let u_squared = u.hadamard(&u);
-        let v_squared = v.hadamard(&v);
-
-        let mut temp = Matrix::new_filled(0.0, lattice.size());
-        for (_, pop) in f {
-            temp += pop.scale(dx * dx);
-        }
-
-        let pi_tilde = &temp - &uv;
-        let n_tilde = &v_squared - &u_squared;
-
-        let delta_s = {
-            let delta_s_0 = {
-                (uv.scale(8.0).hadamard(&pi_tilde)
-                 + &n_tilde.hadamard(&n_tilde))
-                    .hadamard(&rho)
-                .scale(0.5)
-            };
-            let delta_s_1_3 = {
-                (
-                    ((u.scale(dx) - &n_tilde).shift(1.0)).hadamard(&n_tilde)
-                        - (v.scale(dx * 4.0) + uv.scale(8.0)).hadamard(&pi_tilde)
-                ).hadamard(&rho).scale(0.25)
-            };
-            let delta_s_2_4 = {
-                (
-                    ((v.scale(-dx) - &n_tilde).shift(-1.0)).hadamard(&n_tilde)
-                        - (u.scale(dx * 4.0) + uv.scale(8.0)).hadamard(&pi_tilde)
-                ).hadamard(&rho).scale(0.25)
-            };
-            let delta_s_5_6_7_8 = {
-                (
-                    (uv.scale(8.0) + u.scale(4.0 * dx)).shift(2.0 * dx * dx).hadamard(&pi_tilde)
-                        + (&n_tilde - (v - u).scale(dx)).hadamard(&n_tilde)
-                ).hadamard(&rho).scale(0.125)
-            };
-
-            let mut temp: Vec<Matrix> = Vec::with_capacity(9);
-            temp.push(delta_s_0.clone());
-            temp.push(delta_s_1_3.clone());
-            temp.push(delta_s_2_4.clone());
-            temp.push(delta_s_1_3.clone());
-            temp.push(delta_s_2_4.clone());
-            temp.push(delta_s_5_6_7_8.clone());
-            temp.push(delta_s_5_6_7_8.clone());
-            temp.push(delta_s_5_6_7_8.clone());
-            temp.push(delta_s_5_6_7_8.clone());
-
-            temp
-        };
+        let delta_s = KBC::shear_part(lattice, &f_eq);
 
         let delta_h = {
-            let mut temp: Vec<Matrix>
-                = Vec::with_capacity(lattice.populations.len());
+            let mut temp: Vec<Matrix> = Vec::with_capacity(f.len());
 
             for (((_, ref f_i), (_, ref f_eq_i)), delta_s_i)
                 in f.iter().zip(&f_eq).zip(&delta_s) {
@@ -545,8 +877,9 @@ impl CollisionOperator<D2Q9> for KBC {
         };
 
         let beta: Scalar = {
-            let c_s = discretization.isothermal_speed_of_sound();
-            1.0 / ((2.0 * self.ks_viscosity / (c_s * c_s)) + 1.0)
+            let c_s = discretization.speed_of_sound(cs2);
+            let dt = discretization.delta_t;
+            1.0 / ((2.0 * self.ks_viscosity / (c_s * c_s * dt)) + 1.0)
         };
 
         let gamma_star: Matrix = {
@@ -564,28 +897,352 @@ impl CollisionOperator<D2Q9> for KBC {
                 .scale(-1.0)
         };
 
-        let mut result = Vec::with_capacity(lattice.populations.len());
-        for i in 0 .. lattice.populations.len() {
+        let mut result = Vec::with_capacity(f.len());
+        for i in 0 .. f.len() {
             let omega
                 = delta_s[i].scale(2.0 * -beta)
                 + delta_h[i].hadamard(&gamma_star).scale(-beta);
             result.push((f[i].0.clone(), &f[i].1 + omega));
         }
 
-        // Check that the analytic solution is correct
-        let mut total = Matrix::new_filled(0.0, lattice.size());
-        for i in 0 .. lattice.populations().len() {
-            let foo = Matrix::new_filled(1.0, lattice.size()) - gamma_star.scale(beta);
-            let bar = (delta_h[i].hadamard(&foo) - delta_s[i].scale(2.0 * beta - 1.0)).divide(&f_eq[i].1).shift(1.0).log();
-            total += delta_h[i].hadamard(&bar);
+        result
+    }
+
+    fn kinematic_shear_viscosity(&self, disc: &Discretization) -> Scalar {
+        self.ks_viscosity
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// Statistics of the over-relaxation parameter of an `Entropic` collision,
+/// over every node of the lattice.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct AlphaStatistics {
+    pub mean:             f64,
+    pub std_dev:          f64,
+    pub min:              f64,
+    pub max:              f64,
+    /// The fraction of nodes too close to equilibrium to solve for alpha,
+    /// which are given the BGK value of 2.
+    pub near_equilibrium: f64,
+    /// The fraction of nodes where the Newton iteration did not converge.
+    pub unconverged:      f64,
+    /// The distribution of alpha over `Entropic::HISTOGRAM_RANGE`.
+    pub histogram:        Vec<u32>,
+}
+
+/// The entropic lattice Boltzmann method of "Entropic lattice Boltzmann
+/// methods" by Karlin, Ferrante and Öttinger: the populations relax by
+/// `alpha * beta` towards equilibrium, where `alpha` is the non-trivial root
+/// of `H(f + alpha (f_eq - f)) = H(f)`, found at each node by Newton
+/// iteration. It is 2 in well-resolved regions, where this reduces to BGK.
+pub struct Entropic {
+    pub tau: Scalar,
+    last:    std::cell::RefCell<Option<AlphaField>>,
+}
+
+/// The over-relaxation parameter of a collision, kept on the device so that
+/// its statistics cost nothing unless asked for.
+struct AlphaField {
+    alpha:            af::Array<f64>,
+    near_equilibrium: af::Array<bool>,
+    unconverged:      af::Array<bool>,
+}
+
+impl AlphaField {
+    fn statistics(&self) -> AlphaStatistics {
+        let nodes = self.alpha.dims().elements() as f64;
+        let fraction = |mask: &af::Array<bool>| af::sum_all(mask).0 / nodes;
+        let mut histogram = vec![0u32; Entropic::HISTOGRAM_BINS as usize];
+        let (low, high) = Entropic::HISTOGRAM_RANGE;
+        af::histogram(&self.alpha, Entropic::HISTOGRAM_BINS, low, high).host(&mut histogram);
+        AlphaStatistics {
+            mean:             af::mean_all(&self.alpha).0,
+            std_dev:          af::var_all(&self.alpha, false).0.sqrt(),
+            min:              af::min_all(&self.alpha).0,
+            max:              af::max_all(&self.alpha).0,
+            near_equilibrium: fraction(&self.near_equilibrium),
+            unconverged:      fraction(&self.unconverged),
+            histogram:        histogram,
+        }
+    }
+}
+
+impl Entropic {
+    pub const NEWTON_ITERATIONS: usize = 20;
+    pub const NEWTON_TOLERANCE: f64 = 1.0e-10;
+    /// The largest relative deviation from equilibrium at which a node is
+    /// left at `alpha = 2`, since the entropy barely changes there.
+    pub const EQUILIBRIUM_TOLERANCE: f64 = 1.0e-5;
+    pub const HISTOGRAM_BINS: u32 = 16;
+    pub const HISTOGRAM_RANGE: (f64, f64) = (1.0, 3.0);
+
+    /// The operator with the given kinematic shear viscosity, on a velocity
+    /// set with the given squared speed of sound in lattice units.
+    pub fn new(ks_viscosity: Scalar, cs2: Scalar, disc: &Discretization) -> Self {
+        let cs = disc.speed_of_sound(cs2);
+        Entropic {
+            tau:  ks_viscosity / (cs * cs) + disc.delta_t / 2.0,
+            last: std::cell::RefCell::new(None),
+        }
+    }
+}
+
+/// The H-function `sum_i f_i ln(f_i / w_i)`.
+fn h_function(f: &[af::Array<f64>], weights: &[f64]) -> af::Array<f64> {
+    let mut result = af::constant(0.0f64, f[0].dims());
+    for (f_i, w_i) in f.iter().zip(weights) {
+        let log = af::log(&af::div(f_i, w_i, false));
+        result = af::add(&result, &af::mul(f_i, &log, false), false);
+    }
+    result
+}
+
+impl<L: Lattice> CollisionOperator<L> for Entropic {
+    fn evaluate(
+        &self,
+        lattice:        &L,
+        equilibrium:    &Populations,
+        discretization: &Discretization,
+    ) -> Populations {
+        let f: Vec<af::Array<f64>> = lattice.populations().iter()
+            .map(|(_, pop)| pop.get_array().cast::<f64>())
+            .collect();
+        let delta: Vec<af::Array<f64>> = f.iter().zip(equilibrium)
+            .map(|(f_i, (_, eq))| af::sub(&eq.get_array().cast::<f64>(), f_i, false))
+            .collect();
+        let weights: Vec<f64> = equilibrium.iter()
+            .map(|(dir, _)| dir.w_scalar as f64)
+            .collect();
+        let dims = f[0].dims();
+
+        // The largest alpha that keeps every population positive, and how far
+        // each node is from equilibrium.
+        let mut alpha_max = af::constant(std::f64::INFINITY, dims);
+        let mut deviation = af::constant(0.0f64, dims);
+        for (f_i, delta_i) in f.iter().zip(&delta) {
+            let ratio = af::div(f_i, delta_i, false);
+            let limit = af::select(&af::mul(&ratio, &-1.0f64, false),
+                                   &af::lt(delta_i, &0.0f64, false),
+                                   &alpha_max);
+            alpha_max = af::minof(&alpha_max, &limit, false);
+            deviation = af::maxof(&deviation, &af::abs(&af::div(delta_i, f_i, false)), false);
+        }
+        let near_equilibrium = af::lt(&deviation, &Entropic::EQUILIBRIUM_TOLERANCE, false);
+
+        let h = h_function(&f, &weights);
+        let mut alpha = af::minof(&af::constant(2.0f64, dims), &alpha_max, false);
+        let mut converged = af::constant(false, dims);
+        for _ in 0 .. Entropic::NEWTON_ITERATIONS {
+            let g: Vec<af::Array<f64>> = f.iter().zip(&delta)
+                .map(|(f_i, delta_i)| af::add(f_i, &af::mul(delta_i, &alpha, false), false))
+                .collect();
+            let value = af::sub(&h_function(&g, &weights), &h, false);
+            let mut slope = af::constant(0.0f64, dims);
+            for ((g_i, delta_i), w_i) in g.iter().zip(&delta).zip(&weights) {
+                let log = af::add(&af::log(&af::div(g_i, w_i, false)), &1.0f64, false);
+                slope = af::add(&slope, &af::mul(delta_i, &log, false), false);
+            }
+            let step = af::div(&value, &slope, false);
+            converged = af::lt(&af::abs(&step), &Entropic::NEWTON_TOLERANCE, false);
+            let next = af::sub(&alpha, &step, false);
+            alpha = af::select(&alpha, &converged, &next);
+            alpha = af::minof(&alpha, &af::mul(&alpha_max, &0.999f64, false), false);
+        }
+        let fallback = af::minof(&af::constant(2.0f64, dims), &alpha_max, false);
+        let unconverged = af::and(&af::eq(&converged, &false, false),
+                                  &af::eq(&near_equilibrium, &false, false),
+                                  false);
+        let invalid = af::or(&near_equilibrium,
+                             &af::or(&unconverged, &af::isnan(&alpha), false),
+                             false);
+        alpha = af::select(&fallback, &invalid, &alpha);
+
+        let beta = (discretization.delta_t / (2.0 * self.tau)) as f64;
+        let factor = af::mul(&alpha, &beta, false);
+        *self.last.borrow_mut() = Some(AlphaField {
+            alpha:            alpha,
+            near_equilibrium: near_equilibrium,
+            unconverged:      unconverged,
+        });
+        let mut result = Vec::with_capacity(f.len());
+        for ((f_i, delta_i), (dir, _)) in f.iter().zip(&delta).zip(lattice.populations()) {
+            let relaxed = af::add(f_i, &af::mul(delta_i, &factor, false), false);
+            result.push((dir.clone(), Matrix::unsafe_new(relaxed.cast::<f32>())));
+        }
+        result
+    }
+
+    fn kinematic_shear_viscosity(&self, disc: &Discretization) -> Scalar {
+        let cs = disc.speed_of_sound(L::velocity_set().cs2);
+        cs * cs * (self.tau - disc.delta_t / 2.0)
+    }
+
+    fn alpha_statistics(&self) -> Option<AlphaStatistics> {
+        self.last.borrow().as_ref().map(AlphaField::statistics)
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// The cumulant operator of "The cumulant lattice Boltzmann equation in three
+/// dimensions: Theory and validation" by Geier et al., restricted to D2Q9.
+///
+/// The populations are turned into central moments with the chimera
+/// transform, and from those into the cumulants of each order, which are
+/// relaxed at their own rate: the off-diagonal and deviatoric second-order
+/// cumulants set the shear viscosity, their trace the bulk viscosity, and the
+/// third-order cumulants and the fourth-order `c22` relax towards zero at
+/// `omega_3` and `omega_4` respectively.
+///
+/// The fourth-order accurate diffusion of "Parametrization of the cumulant
+/// lattice Boltzmann method for fourth order accurate diffusion part I" by
+/// Geier et al. is not available: it splits the third-order cumulants across
+/// three rates and corrects the fourth-order equilibria, both in terms of
+/// cumulants of D3Q27 that D2Q9 does not carry.
+pub struct Cumulant {
+    pub omega_shear: Scalar,
+    pub omega_bulk:  Scalar,
+    pub omega_3:     Scalar,
+    pub omega_4:     Scalar,
+}
+
+impl Cumulant {
+    /// The operator with the given kinematic shear viscosity, relaxing every
+    /// other cumulant to equilibrium in one step. The squared speed of sound
+    /// of the velocity set is in lattice units.
+    pub fn new(ks_viscosity: Scalar, cs2: Scalar, disc: &Discretization) -> Self {
+        let cs = disc.speed_of_sound(cs2);
+        let omega = 1.0 / ((ks_viscosity / (cs * cs * disc.delta_t)) + 0.5);
+        Cumulant {
+            omega_shear: omega,
+            omega_bulk:  1.0,
+            omega_3:     1.0,
+            omega_4:     1.0,
         }
-        println!("DEBUG: {}", total.abs().maximum_real());
+    }
 
+    /// The operator with the given kinematic shear viscosity and the rate
+    /// `omega_3` of the symmetric third-order cumulants in the paper by Geier
+    /// et al. above, with every other rate as in `new`.
+    pub fn optimal_third_order(
+        ks_viscosity: Scalar,
+        cs2:          Scalar,
+        disc:         &Discretization,
+    ) -> Self {
+        let mut result = Cumulant::new(ks_viscosity, cs2, disc);
+        let (w1, w2) = (result.omega_shear, result.omega_bulk);
+        result.omega_3
+            = 8.0 * (w1 - 2.0) * (w2 * (3.0 * w1 - 1.0) - 5.0 * w1)
+            / (8.0 * (5.0 - 2.0 * w1) * w1 + w2 * (8.0 + w1 * (9.0 * w1 - 26.0)));
+        result
+    }
+}
+
+/// The central moments of order 0, 1 and 2 along one axis of the
+/// populations with velocities -1, 0 and 1 along that axis.
+fn chimera_forward(m: &Matrix, z: &Matrix, p: &Matrix, u: &Matrix)
+                   -> (Matrix, Matrix, Matrix) {
+    let k0 = m + z + p;
+    let k1 = (p - m) - u.hadamard(&k0);
+    let k2 = (p + m) - u.hadamard(&(p - m)).scale(2.0) + u.hadamard(u).hadamard(&k0);
+    (k0, k1, k2)
+}
+
+/// The inverse of `chimera_forward`.
+fn chimera_backward(k0: &Matrix, k1: &Matrix, k2: &Matrix, u: &Matrix)
+                    -> (Matrix, Matrix, Matrix) {
+    let u2 = u.hadamard(u);
+    let z = k0 - k0.hadamard(&u2) - u.hadamard(k1).scale(2.0) - k2;
+    let p = (k0.hadamard(&(&u2 + u)) + k1.hadamard(&u.scale(2.0).shift(1.0)) + k2)
+        .scale(0.5);
+    let m = (k0.hadamard(&(&u2 - u)) + k1.hadamard(&u.scale(2.0).shift(-1.0)) + k2)
+        .scale(0.5);
+    (m, z, p)
+}
+
+impl<L: Lattice> CollisionOperator<L> for Cumulant {
+    fn evaluate(
+        &self,
+        lattice:        &L,
+        equilibrium:    &Populations,
+        discretization: &Discretization,
+    ) -> Populations {
+        let f = lattice.populations();
+        assert!(f.len() == 9, "the cumulant operator needs a D2Q9 lattice");
+
+        // `slot[3 * (cy + 1) + (cx + 1)]` is the index of the population with
+        // lattice velocity (cx, cy).
+        let mut slot = [0; 9];
+        for (i, (dir, _)) in f.iter().enumerate() {
+            let (cx, cy) = dir.c_vector.to_pair();
+            slot[(3.0 * (cy + 1.0) + (cx + 1.0)) as usize] = i;
+        }
+
+        let rho = lattice.density();
+        let (ux, uy) = lattice.velocity();
+
+        // After both passes `k[3 * b + a]` is the central moment of order a
+        // in x and b in y.
+        let mut k: Vec<Matrix> = slot.iter().map(|&i| f[i].1.clone()).collect();
+        for b in 0 .. 3 {
+            let (k0, k1, k2) = chimera_forward(&k[3 * b], &k[3 * b + 1], &k[3 * b + 2], &ux);
+            k[3 * b] = k0; k[3 * b + 1] = k1; k[3 * b + 2] = k2;
+        }
+        for a in 0 .. 3 {
+            let (k0, k1, k2) = chimera_forward(&k[a], &k[3 + a], &k[6 + a], &uy);
+            k[a] = k0; k[3 + a] = k1; k[6 + a] = k2;
+        }
+
+        let inverse_rho = rho.recip();
+        let (c20, c02, c11) = (k[2].clone(), k[6].clone(), k[4].clone());
+        let c22 = &k[8]
+            - (c20.hadamard(&c02) + c11.hadamard(&c11).scale(2.0)).hadamard(&inverse_rho);
+
+        let cs2 = lattice.descriptor().cs2;
+        let deviator = (&c20 - &c02).scale(1.0 - self.omega_shear);
+        let trace = (&c20 + &c02).scale(1.0 - self.omega_bulk)
+            + rho.scale(2.0 * cs2 * self.omega_bulk);
+        let c20 = (&trace + &deviator).scale(0.5);
+        let c02 = (&trace - &deviator).scale(0.5);
+        let c11 = c11.scale(1.0 - self.omega_shear);
+        let c21 = k[5].scale(1.0 - self.omega_3);
+        let c12 = k[7].scale(1.0 - self.omega_3);
+        let c22 = c22.scale(1.0 - self.omega_4);
+
+        let zero = Matrix::new_filled(0.0, lattice.size());
+        k[0] = rho.clone();
+        k[1] = zero.clone();
+        k[3] = zero;
+        k[8] = c22 + (c20.hadamard(&c02) + c11.hadamard(&c11).scale(2.0))
+            .hadamard(&inverse_rho);
+        k[2] = c20; k[6] = c02; k[4] = c11; k[5] = c21; k[7] = c12;
+
+        for a in 0 .. 3 {
+            let (m, z, p) = chimera_backward(&k[a], &k[3 + a], &k[6 + a], &uy);
+            k[a] = m; k[3 + a] = z; k[6 + a] = p;
+        }
+        for b in 0 .. 3 {
+            let (m, z, p) = chimera_backward(&k[3 * b], &k[3 * b + 1], &k[3 * b + 2], &ux);
+            k[3 * b] = m; k[3 * b + 1] = z; k[3 * b + 2] = p;
+        }
+
+        let mut result: Populations = f.clone();
+        for (s, &i) in slot.iter().enumerate() {
+            result[i].1 = k[s].clone();
+        }
         result
     }
 
     fn kinematic_shear_viscosity(&self, disc: &Discretization) -> Scalar {
-        self.ks_viscosity
+        let cs = disc.speed_of_sound(L::velocity_set().cs2);
+        cs * cs * disc.delta_t * (1.0 / self.omega_shear - 0.5)
+    }
+
+    fn kinematic_bulk_viscosity(&self, disc: &Discretization) -> Scalar {
+        let cs = disc.speed_of_sound(L::velocity_set().cs2);
+        cs * cs * disc.delta_t * (1.0 / self.omega_bulk - 0.5)
     }
 }
 
@@ -614,8 +1271,7 @@ where L: Lattice, C: CollisionOperator<L> {
         let f = lattice.populations();
         let f_eq = lattice.equilibrium(discretization);
         let f_neq = lattice.non_equilibrium(discretization);
-        let cs = discretization.isothermal_speed_of_sound();
-        let cs2 = cs * cs;
+        let cs2 = lattice.descriptor().cs2;
         let cs4 = cs2 * cs2;
 
         let mut dev_stress_neq_xx = Matrix::new_filled(0.0, lattice.size());
@@ -667,12 +1323,93 @@ where L: Lattice, C: CollisionOperator<L> {
 
 // -----------------------------------------------------------------------------
 
+/// The collision operators that can be built from a viscosity alone.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash, Serialize, Deserialize)]
+pub enum OperatorKind {
+    BGK,
+    TRT,
+    RegularizedBGK,
+    KBC,
+    /// KBC on the regularized populations, which the interactive example
+    /// runs by default.
+    RegularizedKBC,
+    Entropic,
+    Cumulant,
+}
+
+impl OperatorKind {
+    pub fn all() -> [OperatorKind; 7] {
+        [OperatorKind::BGK,
+         OperatorKind::TRT,
+         OperatorKind::RegularizedBGK,
+         OperatorKind::KBC,
+         OperatorKind::RegularizedKBC,
+         OperatorKind::Entropic,
+         OperatorKind::Cumulant]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            OperatorKind::BGK            => "BGK",
+            OperatorKind::TRT            => "TRT",
+            OperatorKind::RegularizedBGK => "Regularized BGK",
+            OperatorKind::KBC            => "KBC",
+            OperatorKind::RegularizedKBC => "Regularized KBC",
+            OperatorKind::Entropic       => "Entropic",
+            OperatorKind::Cumulant       => "Cumulant",
+        }
+    }
+
+    /// The operator with the given kinematic shear viscosity.
+    pub fn build<L: Lattice>(
+        &self,
+        ks_viscosity: Scalar,
+        disc:         &Discretization,
+    ) -> Box<CollisionOperator<L>> {
+        let cs2 = L::velocity_set().cs2;
+        let cs = disc.speed_of_sound(cs2);
+        let bgk = BGK { tau: ks_viscosity / (cs * cs) + disc.delta_t / 2.0 };
+        match *self {
+            OperatorKind::BGK            => Box::new(bgk),
+            OperatorKind::TRT            => Box::new(TRT::new(0.25, ks_viscosity, cs2, disc)),
+            OperatorKind::RegularizedBGK => Box::new(Regularized::new(bgk)),
+            OperatorKind::KBC            => Box::new(KBC::new(ks_viscosity)),
+            OperatorKind::RegularizedKBC => Box::new(Regularized::new(KBC::new(ks_viscosity))),
+            OperatorKind::Entropic       => Box::new(Entropic::new(ks_viscosity, cs2, disc)),
+            OperatorKind::Cumulant       => {
+                Box::new(Cumulant::optimal_third_order(ks_viscosity, cs2, disc))
+            },
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// An edge of the lattice; the bottom one is the row `y = 0`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Edge {
+    Left,
+    Right,
+    Bottom,
+    Top,
+}
+
+// -----------------------------------------------------------------------------
+
 pub struct State<L> {
     pub time:           Scalar,
     pub lattice:        Box<L>,
     pub geometry:       Geometry,
     pub collision:      Box<CollisionOperator<L>>,
     pub discretization: Discretization,
+    pub blocks:         Vec<RefinedBlock<L>>,
+    pub body_force:     Option<(Matrix, Matrix)>,
+    pub wall_velocity:  Option<(Matrix, Matrix)>,
+    pub periodic:       (bool, bool),
+    pub inlets:         Vec<Inlet>,
+    /// Edges across which the flow leaves, on axes that are not periodic.
+    pub outlets:        Vec<Edge>,
+    pub forces:         Vec<ScheduledForce>,
 }
 
 impl<L: Lattice> State<L> {
@@ -688,58 +1425,286 @@ impl<L: Lattice> State<L> {
             geometry:       geometry,
             collision:      collision,
             discretization: discretization,
+            blocks:         Vec::new(),
+            body_force:     None,
+            wall_velocity:  None,
+            periodic:       (false, false),
+            inlets:         Vec::new(),
+            outlets:        Vec::new(),
+            forces:         Vec::new(),
         }
     }
 
+    /// Nest a block with twice the resolution inside this lattice, covering
+    /// the given region (in lattice nodes of `self`).
+    pub fn refine(
+        &mut self,
+        origin:    (usize, usize),
+        extent:    (usize, usize),
+        collision: Box<CollisionOperator<L>>,
+    ) {
+        let block = RefinedBlock::new(self, origin, extent, collision);
+        self.blocks.push(block);
+    }
+
     pub fn step(&mut self) {
+        self.propagate();
+        self.step_blocks();
+        self.relax();
+    }
+
+    /// Stream, fill in the outlets and bounce back, leaving the populations
+    /// as they are before collision.
+    pub fn propagate(&mut self) {
         {
             let timer = std::time::Instant::now();
             self.stream();
             println!("> Streaming took {} ms", timer.elapsed().as_millis());
         }
 
+        if !self.outlets.is_empty() {
+            self.extrapolate_outlets();
+        }
+
         {
             let timer = std::time::Instant::now();
             self.bounce_back();
             println!("> Bounce-back took {} ms", timer.elapsed().as_millis());
         }
+    }
 
+    /// Collide, apply the forces and inlets, and finish the time step.
+    pub fn relax(&mut self) {
         {
             let timer = std::time::Instant::now();
             self.collide();
             println!("> Colliding took {} ms", timer.elapsed().as_millis());
         }
 
+        if let Some(force) = self.body_force.clone() {
+            self.apply_force(&force);
+        }
+
+        // Schedules are evaluated at the time the step starts from.
+        if !self.forces.is_empty() {
+            let size = self.size();
+            let mut total = (Matrix::new_filled(0.0, size), Matrix::new_filled(0.0, size));
+            for force in &self.forces {
+                let (fx, fy) = force.force_at(self.time, size);
+                total.0 += fx;
+                total.1 += fy;
+            }
+            self.apply_force(&total);
+        }
+
+        for k in 0 .. self.inlets.len() {
+            let (nodes, density, velocity) = {
+                let inlet = &self.inlets[k];
+                (inlet.nodes.clone(),
+                 Matrix::new_filled(inlet.density, self.size()),
+                 inlet.velocity_at(self.time, self.size()))
+            };
+            self.refill(&nodes, &density, &velocity);
+        }
+
         self.time += self.discretization.delta_t;
     }
 
+    /// Advance the refined blocks by one time step of this lattice. The
+    /// populations must be those before collision, as `propagate` leaves
+    /// them.
+    pub fn step_blocks(&mut self) {
+        if self.blocks.is_empty() { return; }
+        let mut blocks = std::mem::replace(&mut self.blocks, Vec::new());
+        for block in blocks.iter_mut() {
+            block.advance(self);
+        }
+        self.blocks = blocks;
+    }
+
+    /// Move every population to the neighbour its lattice velocity points at.
+    /// Along a periodic axis populations leaving the lattice come back in on
+    /// the other side; otherwise they are lost and nothing comes in.
     pub fn stream(&mut self) {
+        let (w, h) = self.size();
+        let (periodic_x, periodic_y) = self.periodic;
         for pair in self.lattice.populations_mut() {
-            let transposed = pair.0.stencil.transpose();
-            let new_f_i = {
-                let f_i = pair.1.get_array();
-                let stencil = transposed.get_array();
-                let arr = af::convolve2(&f_i, &stencil,
-                                        af::ConvMode::DEFAULT,
-                                        af::ConvDomain::SPATIAL);
-                Matrix::unsafe_new(arr)
-            };
+            let c = pair.0.c_vector;
+            let mut new_f_i = Matrix::unsafe_new(translate(pair.1.get_array(), c));
+            let (cx, cy) = (c.0 as i64, c.1 as i64);
+            if !periodic_x && (cx != 0) {
+                let x = if cx > 0 { 0 } else { (w as i64 + cx) as usize };
+                let empty = Matrix::new_filled(0.0, (cx.abs() as usize, h));
+                new_f_i.set_subregion((x, 0), &empty);
+            }
+            if !periodic_y && (cy != 0) {
+                let y = if cy > 0 { 0 } else { (h as i64 + cy) as usize };
+                let empty = Matrix::new_filled(0.0, (w, cy.abs() as usize));
+                new_f_i.set_subregion((0, y), &empty);
+            }
             *(&mut pair.1) = new_f_i;
         }
     }
 
+    /// Give the populations that streaming leaves empty on each outlet the
+    /// values of the first row or column inwards that received them, so that
+    /// the flow leaves with a zero gradient across the edge.
+    pub fn extrapolate_outlets(&mut self) {
+        let (w, h) = self.size();
+        let outlets = self.outlets.clone();
+        let periodic = self.periodic;
+        for edge in outlets {
+            let along_x = (edge == Edge::Left) || (edge == Edge::Right);
+            assert!(!(if along_x { periodic.0 } else { periodic.1 }),
+                    "{:?} outlet on a periodic axis", edge);
+            for pair in self.lattice.populations_mut() {
+                let c = pair.0.c_vector;
+                let unknown = match edge {
+                    Edge::Left   => c.0,
+                    Edge::Right  => -c.0,
+                    Edge::Bottom => c.1,
+                    Edge::Top    => -c.1,
+                } as i64;
+                if unknown <= 0 { continue; }
+
+                let k = unknown as usize;
+                let (source, targets) = match edge {
+                    Edge::Left | Edge::Bottom => (k, 0 .. k),
+                    Edge::Right               => (w - k - 1, (w - k) .. w),
+                    Edge::Top                 => (h - k - 1, (h - k) .. h),
+                };
+                let f_i = &mut pair.1;
+                if along_x {
+                    let column = f_i.subregion((source, 0), (1, h));
+                    for x in targets { f_i.set_subregion((x, 0), &column); }
+                } else {
+                    let row = f_i.subregion((0, source), (w, 1));
+                    for y in targets { f_i.set_subregion((0, y), &row); }
+                }
+            }
+        }
+    }
+
+    /// Relax the populations of the fluid nodes. Solid nodes are left alone,
+    /// so that bounce-back returns exactly what it received.
     pub fn collide(&mut self) {
         use std::borrow::Borrow;
-        let f_star = self.collision.evaluate(
+        let mut f_star = self.collision.evaluate(
             self.lattice.borrow(),
             &self.equilibrium(),
             &self.discretization,
         );
+        let fluid = af::eq(&self.geometry, &false, false);
+        for (pair, pair_star) in self.populations().iter().zip(&mut f_star) {
+            af::replace(pair_star.1.get_array_mut(), &fluid, pair.1.get_array());
+        }
         *(self.lattice.populations_mut()) = f_star;
     }
 
+    /// Apply a body force density to the populations with the exact
+    /// difference method of Kupershtokh et al., which works with any
+    /// collision operator: each population is shifted by the difference
+    /// between the equilibria at the velocity before and after the force has
+    /// acted for one time step.
+    pub fn apply_force(&mut self, force: &(Matrix, Matrix)) {
+        let (fx, fy) = force;
+        let density = self.density();
+        let (vx, vy) = self.velocity();
+        let fluid = Matrix::unsafe_new(
+            af::eq(&self.geometry, &false, false).cast::<f32>());
+        let impulse = density.recip().scale(self.delta_t()).hadamard(&fluid);
+        let forced = (&vx + fx.hadamard(&impulse), &vy + fy.hadamard(&impulse));
+        let directions = self.directions();
+        let disc = self.discretization;
+        let f_eq = compute_equilibrium(density.clone(), (vx, vy),
+                                       &directions, disc);
+        let f_eq_forced = compute_equilibrium(density, forced,
+                                              &directions, disc);
+        let pops = self.lattice.populations_mut();
+        for ((pair, (_, eq)), (_, eq_forced)) in pops.iter_mut().zip(f_eq).zip(f_eq_forced) {
+            pair.1 += eq_forced - eq;
+        }
+    }
+
+    /// Reset the populations on the given nodes to the equilibrium with the
+    /// given density and velocity, e.g. when they stop being solid.
+    pub fn refill(
+        &mut self,
+        nodes:    &Geometry,
+        density:  &Matrix,
+        velocity: &(Matrix, Matrix),
+    ) {
+        let f_eq = compute_equilibrium(density.clone(),
+                                       velocity.clone(),
+                                       &self.directions(),
+                                       self.discretization);
+        let keep = af::eq(nodes, &false, false);
+        for (pair, (_, eq)) in self.lattice.populations_mut().iter_mut().zip(f_eq) {
+            af::replace(pair.1.get_array_mut(), &keep, eq.get_array());
+        }
+    }
+
+    /// The force exerted by the fluid on the solid nodes in `mask`, and its
+    /// torque about `center` (in lattice node coordinates), measured by
+    /// momentum exchange on the links between those nodes and the fluid.
+    ///
+    /// The populations are expected to be post-collision, i.e. about to be
+    /// streamed into the solid and bounced back.
+    pub fn momentum_exchange(
+        &self,
+        mask:   &Geometry,
+        center: (Scalar, Scalar),
+    ) -> (Vector, Scalar) {
+        let dims = mask.dims();
+        let xs = Matrix::unsafe_new(af::range::<f32>(dims, 1)).shift(-center.0);
+        let ys = Matrix::unsafe_new(af::range::<f32>(dims, 0)).shift(-center.1);
+        let fluid = af::eq(&self.geometry, &false, false);
+        let cs2 = self.lattice.descriptor().cs2;
+
+        let (mut fx, mut fy, mut torque) = (0.0, 0.0, 0.0);
+        for (dir, f_i) in self.populations() {
+            let c = dir.c_vector;
+            if c == Vector(0.0, 0.0) { continue; }
+
+            // Everything below is seen from the solid end of each link.
+            let links = af::and(&translate(&fluid, c), mask, false);
+            let incoming = Matrix::unsafe_new(translate(f_i.get_array(), c));
+            let mut transfer = incoming.scale(2.0);
+            if let Some((ref ux, ref uy)) = self.wall_velocity {
+                let cu = ux.scale(c.0) + uy.scale(c.1);
+                transfer = transfer - cu.scale(2.0 * dir.w_scalar / cs2);
+            }
+            let transfer = transfer.hadamard(
+                &Matrix::unsafe_new(links.cast::<f32>()));
+
+            let total = transfer.sum() as Scalar;
+            fx += total * c.0;
+            fy += total * c.1;
+            let arm = xs.scale(c.1) - ys.scale(c.0);
+            torque += transfer.hadamard(&arm).sum() as Scalar;
+        }
+
+        let scale = self.delta_x().powi(3) / self.delta_t().powi(2);
+        (Vector(fx * scale, fy * scale), torque * scale * self.delta_x())
+    }
+
+    /// Bounce back the populations on the solid nodes given by `geometry`.
+    ///
+    /// If `wall_velocity` is set, the reflected populations are corrected as
+    /// in "Numerical simulations of particulate suspensions via a discretized
+    /// Boltzmann equation" by Ladd, so that the walls move with that velocity
+    /// (assuming a fluid density of 1 next to them).
     pub fn bounce_back(&mut self) {
         let mut sw_pops = self.lattice.swap_populations();
+        if let Some((ref ux, ref uy)) = self.wall_velocity {
+            let cs2 = self.lattice.descriptor().cs2;
+            let solid = Matrix::unsafe_new(self.geometry.cast::<f32>());
+            for (dir, sw_pop) in sw_pops.iter_mut() {
+                let c = dir.c_vector;
+                let cu = ux.scale(c.0) + uy.scale(c.1);
+                *sw_pop += cu.scale(2.0 * dir.w_scalar / cs2).hadamard(&solid);
+            }
+        }
         for (pair, mut sw_pair) in self.populations().iter().zip(&mut sw_pops) {
             let (dir, pop, sw_pop) = (pair.0.clone(), &pair.1, &mut sw_pair.1);
             af::replace(sw_pop.get_array_mut(),
@@ -755,6 +1720,11 @@ impl<L: Lattice> State<L> {
         self.lattice.size()
     }
 
+    #[inline(always)]
+    pub fn directions(&self) -> Vec<Direction> {
+        self.populations().iter().map(|(dir, _)| dir.clone()).collect()
+    }
+
     #[inline(always)]
     pub fn delta_x(&self) -> Scalar {
         self.discretization.delta_x
@@ -782,7 +1752,7 @@ impl<L: Lattice> State<L> {
 
     #[inline(always)]
     pub fn pressure(&self) -> Matrix {
-        let cs = self.isothermal_speed_of_sound();
+        let cs = self.discretization.speed_of_sound(self.lattice.descriptor().cs2);
         self.density().scale(cs * cs)
     }
 
@@ -796,6 +1766,17 @@ impl<L: Lattice> State<L> {
         self.lattice.velocity()
     }
 
+    /// The statistics of alpha in the last collision, if the operator solves
+    /// for it. They are read back from the device only when asked for.
+    pub fn alpha_statistics(&self) -> Option<AlphaStatistics> {
+        self.collision.alpha_statistics()
+    }
+
+    #[inline(always)]
+    pub fn temperature(&self) -> Matrix {
+        self.lattice.temperature(&self.discretization)
+    }
+
     #[inline(always)]
     pub fn speed(&self) -> Matrix {
         self.lattice.speed()
@@ -819,3 +1800,251 @@ impl<L: Lattice> State<L> {
 }
 
 // -----------------------------------------------------------------------------
+
+/// Property checks of the lattice and the collision operators.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use proptest::collection;
+    use proptest::test_runner::{Config, TestRunner};
+
+    const SIZE: (usize, usize) = (8, 8);
+    const TOLERANCE: f64 = 1.0e-4;
+
+    fn disc() -> Discretization {
+        Discretization { delta_x: 1.0, delta_t: 1.0 }
+    }
+
+    fn runner() -> TestRunner {
+        TestRunner::new(Config::with_cases(32))
+    }
+
+    fn max_difference(a: &Matrix, b: &Matrix) -> f64 {
+        (a - b).abs().maximum_real()
+    }
+
+    /// Densities and velocities at every node, in the weakly compressible
+    /// regime, and relative perturbations of every population.
+    fn nodes() -> BoxedStrategy<(Vec<Scalar>, Vec<Scalar>, Vec<Scalar>, Vec<Scalar>)> {
+        let n = SIZE.0 * SIZE.1;
+        (collection::vec(0.8f32 .. 1.2, n),
+         collection::vec(-0.1f32 .. 0.1, n),
+         collection::vec(-0.1f32 .. 0.1, n),
+         collection::vec(-0.05f32 .. 0.05, 9 * n)).boxed()
+    }
+
+    fn macroscopic(rho: &[Scalar], ux: &[Scalar], uy: &[Scalar])
+                   -> (Matrix, (Matrix, Matrix)) {
+        (Matrix::new(rho, SIZE).unwrap(),
+         (Matrix::new(ux, SIZE).unwrap(), Matrix::new(uy, SIZE).unwrap()))
+    }
+
+    /// A lattice away from equilibrium.
+    fn perturbed(rho: &[Scalar], ux: &[Scalar], uy: &[Scalar], noise: &[Scalar]) -> D2Q9 {
+        let (density, velocity) = macroscopic(rho, ux, uy);
+        let n = SIZE.0 * SIZE.1;
+        let f_eq = compute_equilibrium(density, velocity, &D2Q9::directions(), disc());
+        let pops = f_eq.into_iter().enumerate().map(|(i, (dir, pop))| {
+            let factor = Matrix::new(&noise[(i * n) .. ((i + 1) * n)], SIZE)
+                .unwrap()
+                .shift(1.0);
+            (dir, pop.hadamard(&factor))
+        }).collect();
+        D2Q9::from_populations(pops)
+    }
+
+    #[test]
+    #[ignore]
+    fn test_equilibrium_moments() {
+        runner().run(&nodes(), |(rho, ux, uy, _)| {
+            let (density, velocity) = macroscopic(&rho, &ux, &uy);
+            let f_eq = compute_equilibrium(density.clone(), velocity.clone(),
+                                           &D2Q9::directions(), disc());
+            let lattice = D2Q9::from_populations(f_eq);
+            let (mx, my) = lattice.momentum_density();
+            prop_assert!(max_difference(&lattice.density(), &density) < TOLERANCE);
+            prop_assert!(max_difference(&mx, &density.hadamard(&velocity.0)) < TOLERANCE);
+            prop_assert!(max_difference(&my, &density.hadamard(&velocity.1)) < TOLERANCE);
+            Ok(())
+        }).unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn test_swap_populations_involution() {
+        runner().run(&nodes(), |(rho, ux, uy, noise)| {
+            let lattice = perturbed(&rho, &ux, &uy, &noise);
+            let swapped = D2Q9::from_populations(lattice.swap_populations());
+            let twice = swapped.swap_populations();
+            for ((_, f), (_, g)) in lattice.populations().iter().zip(&twice) {
+                prop_assert!(max_difference(f, g) == 0.0);
+            }
+            Ok(())
+        }).unwrap();
+    }
+
+    /// The weights of every velocity set sum to one, its opposite map pairs
+    /// opposite velocities, and its second moment is isotropic with the
+    /// tabulated speed of sound.
+    #[test]
+    fn test_descriptors() {
+        let descriptors = [
+            <velocity_set::D2Q5  as VelocitySet>::DESCRIPTOR,
+            <velocity_set::D2Q9  as VelocitySet>::DESCRIPTOR,
+            <velocity_set::D3Q15 as VelocitySet>::DESCRIPTOR,
+            <velocity_set::D3Q19 as VelocitySet>::DESCRIPTOR,
+            <velocity_set::D3Q27 as VelocitySet>::DESCRIPTOR,
+            <velocity_set::D2V17 as VelocitySet>::DESCRIPTOR,
+            <velocity_set::D2V37 as VelocitySet>::DESCRIPTOR,
+        ];
+        for d in descriptors.iter() {
+            assert_eq!(d.weights.len(), d.len(), "{}", d.name);
+            assert_eq!(d.opposite.len(), d.len(), "{}", d.name);
+            let total: Scalar = d.weights.iter().sum();
+            assert!(relative_eq!(total, 1.0, epsilon = 1.0e-6), "{}", d.name);
+            for (i, &j) in d.opposite.iter().enumerate() {
+                for k in 0 .. 3 {
+                    assert_eq!(d.velocities[i][k], -d.velocities[j][k], "{}", d.name);
+                }
+            }
+            for a in 0 .. d.dimension {
+                for b in 0 .. d.dimension {
+                    let moment: Scalar = d.velocities.iter().zip(d.weights).map(|(c, w)| {
+                        w * (c[a] as Scalar) * (c[b] as Scalar)
+                    }).sum();
+                    let expected = if a == b { d.cs2 } else { 0.0 };
+                    assert!(relative_eq!(moment, expected, epsilon = 1.0e-6),
+                            "{} is not isotropic", d.name);
+                }
+            }
+        }
+    }
+
+    /// The three-dimensional velocity sets are only data.
+    #[test]
+    #[should_panic(expected = "D3Q19 cannot make a two-dimensional lattice")]
+    fn test_three_dimensional_lattice() {
+        DescriptorLattice::<velocity_set::D3Q19>::new(&[]);
+    }
+
+    /// The fourth-order thermal equilibrium on D2V37 has the density,
+    /// momentum and temperature it was built from.
+    #[test]
+    #[ignore]
+    fn test_hermite_equilibrium_moments() {
+        runner().run(&nodes(), |(rho, ux, uy, noise)| {
+            let (density, velocity) = macroscopic(&rho, &ux, &uy);
+            let n = SIZE.0 * SIZE.1;
+            let temperature = Matrix::new(&noise[.. n], SIZE).unwrap().scale(4.0).shift(1.0);
+            let f_eq = compute_hermite_equilibrium(
+                density.clone(), velocity.clone(), temperature.clone(), 4,
+                &D2V37::directions(), disc());
+            let lattice = D2V37::from_populations(f_eq).with_equilibrium(4, true);
+            let (mx, my) = lattice.momentum_density();
+            prop_assert!(max_difference(&lattice.density(), &density) < TOLERANCE);
+            prop_assert!(max_difference(&mx, &density.hadamard(&velocity.0)) < TOLERANCE);
+            prop_assert!(max_difference(&my, &density.hadamard(&velocity.1)) < TOLERANCE);
+            prop_assert!(max_difference(&lattice.temperature(&disc()), &temperature) < TOLERANCE);
+
+            let rebuilt = lattice.with_populations(lattice.populations().clone());
+            prop_assert_eq!(rebuilt.equilibrium_order(), 4);
+            prop_assert!(rebuilt.is_thermal());
+            Ok(())
+        }).unwrap();
+    }
+
+    /// The post-collision populations `f*` of KBC satisfy the entropy
+    /// condition `sum_i dh_i ln(f*_i / f_eq_i) = 0` at every node, up to the
+    /// tolerance of its closed form.
+    #[test]
+    #[ignore]
+    fn test_kbc_entropy_condition() {
+        let kbc = KBC::new(0.1);
+        runner().run(&nodes(), |(rho, ux, uy, noise)| {
+            let lattice = perturbed(&rho, &ux, &uy, &noise);
+            let f_eq = lattice.equilibrium(&disc());
+            let delta_s = KBC::shear_part(&lattice, &f_eq);
+            let f_star = kbc.evaluate(&lattice, &f_eq, &disc());
+            let mut total = Matrix::new_filled(0.0, SIZE);
+            for (((f_i, f_eq_i), s_i), f_star_i)
+                in lattice.populations().iter().zip(&f_eq).zip(&delta_s).zip(&f_star) {
+                    let delta_h = &(&f_i.1 - &f_eq_i.1) - s_i;
+                    total += delta_h.hadamard(&f_star_i.1.divide(&f_eq_i.1).log());
+                }
+            let residual = total.abs().maximum_real();
+            prop_assert!(residual <= KBC::ENTROPY_TOLERANCE,
+                         "the entropy condition is violated by {}", residual);
+            Ok(())
+        }).unwrap();
+    }
+
+    /// The entropic operator reports alpha only once it has collided, and
+    /// its histogram covers every node.
+    #[test]
+    #[ignore]
+    fn test_entropic_alpha_statistics() {
+        let entropic = Entropic::new(0.1, D2Q9::velocity_set().cs2, &disc());
+        assert_eq!(CollisionOperator::<D2Q9>::alpha_statistics(&entropic), None);
+        runner().run(&nodes(), |(rho, ux, uy, noise)| {
+            let lattice = perturbed(&rho, &ux, &uy, &noise);
+            let f_eq = lattice.equilibrium(&disc());
+            entropic.evaluate(&lattice, &f_eq, &disc());
+            let stats = CollisionOperator::<D2Q9>::alpha_statistics(&entropic).unwrap();
+            let total: u32 = stats.histogram.iter().sum();
+            prop_assert_eq!(total as usize, SIZE.0 * SIZE.1);
+            prop_assert!((stats.min <= stats.mean) && (stats.mean <= stats.max));
+            Ok(())
+        }).unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn test_collision_conservation() {
+        for kind in OperatorKind::all().iter() {
+            let collision: Box<CollisionOperator<D2Q9>> = kind.build(0.1, &disc());
+            runner().run(&nodes(), |(rho, ux, uy, noise)| {
+                let lattice = perturbed(&rho, &ux, &uy, &noise);
+                let f_eq = lattice.equilibrium(&disc());
+                let f_star = collision.evaluate(&lattice, &f_eq, &disc());
+                let collided = D2Q9::from_populations(f_star);
+                let (mx, my) = lattice.momentum_density();
+                let (mx_star, my_star) = collided.momentum_density();
+                prop_assert!(max_difference(&lattice.density(), &collided.density()) < TOLERANCE,
+                             "{} does not conserve mass", kind.name());
+                prop_assert!(max_difference(&mx, &mx_star) < TOLERANCE,
+                             "{} does not conserve momentum", kind.name());
+                prop_assert!(max_difference(&my, &my_star) < TOLERANCE,
+                             "{} does not conserve momentum", kind.name());
+                Ok(())
+            }).unwrap();
+        }
+    }
+
+    /// Streaming through an outlet leaves the populations entering from
+    /// outside equal to those of the column next to it.
+    #[test]
+    #[ignore]
+    fn test_outlet_zero_gradient() {
+        runner().run(&nodes(), |(rho, ux, uy, noise)| {
+            let lattice = perturbed(&rho, &ux, &uy, &noise);
+            let dims = af::Dim4::new(&[SIZE.1 as u64, SIZE.0 as u64, 1, 1]);
+            let geometry = af::constant(false, dims);
+            let mut state = State::initial(Box::new(lattice), geometry,
+                                           OperatorKind::BGK.build(0.1, &disc()), disc());
+            state.outlets.push(Edge::Right);
+            state.stream();
+            state.extrapolate_outlets();
+            let (w, h) = SIZE;
+            for (dir, f_i) in state.populations() {
+                if dir.c_vector.0 >= 0.0 { continue; }
+                let edge = f_i.subregion((w - 1, 0), (1, h));
+                let inner = f_i.subregion((w - 2, 0), (1, h));
+                prop_assert!(max_difference(&edge, &inner) == 0.0);
+            }
+            Ok(())
+        }).unwrap();
+    }
+}
+
+// -----------------------------------------------------------------------------
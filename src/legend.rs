@@ -0,0 +1,151 @@
+// -----------------------------------------------------------------------------
+
+extern crate ttf_noto_sans;
+
+use std;
+use rusttype;
+use super::lbm::Scalar;
+use super::colormap::ColorScale;
+use super::display::{Drawable, RGB, PixelPos};
+
+// -----------------------------------------------------------------------------
+
+/// Draws text into a `Drawable` with the bundled Noto Sans font.
+pub struct TextRenderer {
+    font: rusttype::Font<'static>,
+}
+
+impl TextRenderer {
+    pub fn new() -> Self {
+        let font = rusttype::Font::from_bytes(ttf_noto_sans::REGULAR)
+            .expect("failed to load the Noto Sans font");
+        TextRenderer { font: font }
+    }
+
+    /// The width in pixels of the text at the given height in pixels.
+    pub fn width(&self, text: &str, size: Scalar) -> u32 {
+        let scale = rusttype::Scale::uniform(size);
+        self.font.layout(text, scale, rusttype::point(0.0, 0.0))
+            .filter_map(|g| g.pixel_bounding_box())
+            .map(|b| b.max.x)
+            .max()
+            .unwrap_or(0)
+            .max(0) as u32
+    }
+
+    /// Blend the text over the drawable, with its top left corner at the
+    /// given pixel. Whatever falls outside the drawable is clipped.
+    pub fn draw<D: Drawable>(&self, buf: &mut D, text: &str, position: (i32, i32),
+                             size: Scalar, color: RGB) {
+        let (w, h) = buf.dimensions();
+        let scale = rusttype::Scale::uniform(size);
+        let ascent = self.font.v_metrics(scale).ascent;
+        let origin = rusttype::point(position.0 as f32, position.1 as f32 + ascent);
+        for glyph in self.font.layout(text, scale, origin) {
+            let bounds = match glyph.pixel_bounding_box() {
+                Some(b) => b,
+                None => continue,
+            };
+            glyph.draw(|gx, gy, coverage| {
+                let x = bounds.min.x + gx as i32;
+                let y = bounds.min.y + gy as i32;
+                if (x < 0) || (y < 0) || (x >= w as i32) || (y >= h as i32) { return; }
+                let pos = PixelPos(x as u32, y as u32);
+                let blended = blend(buf.get_pixel(pos), color, coverage);
+                buf.set_pixel(pos, blended);
+            });
+        }
+    }
+}
+
+/// `over` drawn on top of `under` with the given opacity.
+pub fn blend(under: RGB, over: RGB, opacity: Scalar) -> RGB {
+    let mix = |a: u8, b: u8| {
+        ((a as Scalar) * (1.0 - opacity) + (b as Scalar) * opacity).round() as u8
+    };
+    RGB(mix(under.0, over.0), mix(under.1, over.1), mix(under.2, over.2))
+}
+
+/// A tick label: fixed point for moderate magnitudes, scientific otherwise.
+pub fn format_value(value: Scalar) -> String {
+    let magnitude = value.abs();
+    if magnitude == 0.0 {
+        "0".to_string()
+    } else if (magnitude >= 1.0e4) || (magnitude < 1.0e-2) {
+        format!("{:.2e}", value)
+    } else {
+        format!("{:.3}", value)
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+const MARGIN: u32 = 8;
+const BAR_WIDTH: u32 = 12;
+const TICKS: usize = 5;
+const FONT_SIZE: Scalar = 13.0;
+
+/// Draw a vertical colour bar with tick labels and the label of the scale
+/// along the right edge of the drawable, for a field mapped with the given
+/// limits. Drawables too small to hold it are left alone.
+pub fn draw_colorbar<D: Drawable>(
+    buf:    &mut D,
+    text:   &TextRenderer,
+    scale:  &ColorScale,
+    limits: (Scalar, Scalar),
+) {
+    let (w, h) = buf.dimensions();
+    if (w < 120) || (h < 80) { return; }
+
+    let labels: Vec<String> = (0 .. TICKS).map(|k| {
+        let t = (k as Scalar) / ((TICKS - 1) as Scalar);
+        format_value(scale.value_at(t, limits))
+    }).collect();
+    let label_width = labels.iter().map(|l| text.width(l, FONT_SIZE)).max().unwrap_or(0);
+    let title_width = text.width(&scale.label, FONT_SIZE);
+    let line = FONT_SIZE.ceil() as u32 + 2;
+
+    let bar_height = h / 2;
+    let bar_x = w - MARGIN - BAR_WIDTH;
+    let bar_y = (h - bar_height) / 2;
+    let left = bar_x.saturating_sub(label_width + 4)
+        .min(w.saturating_sub(MARGIN + title_width))
+        .saturating_sub(4);
+    let top = bar_y.saturating_sub(line + line / 2 + 4);
+    let bottom = std::cmp::min(bar_y + bar_height + line / 2 + 4, h);
+
+    // Darken the background so that the labels stay legible.
+    for y in top .. bottom {
+        for x in left .. w {
+            let pos = PixelPos(x, y);
+            let darkened = blend(buf.get_pixel(pos), RGB(0, 0, 0), 0.5);
+            buf.set_pixel(pos, darkened);
+        }
+    }
+
+    let colormap = scale.lut.colormap;
+    for row in 0 .. bar_height {
+        let t = 1.0 - (row as Scalar) / ((bar_height - 1) as Scalar);
+        let c = colormap.color(t);
+        let color = RGB((c[0] * 255.0).round() as u8,
+                        (c[1] * 255.0).round() as u8,
+                        (c[2] * 255.0).round() as u8);
+        for x in bar_x .. (bar_x + BAR_WIDTH) {
+            buf.set_pixel(PixelPos(x, bar_y + row), color);
+        }
+    }
+
+    let white = RGB(255, 255, 255);
+    for (k, label) in labels.iter().enumerate() {
+        let t = (k as Scalar) / ((TICKS - 1) as Scalar);
+        let y = (bar_y as Scalar) + (1.0 - t) * ((bar_height - 1) as Scalar);
+        let x = bar_x as i32 - 4 - text.width(label, FONT_SIZE) as i32;
+        text.draw(buf, label, (x, y as i32 - (line / 2) as i32), FONT_SIZE, white);
+    }
+    if !scale.label.is_empty() {
+        let x = (w - MARGIN - title_width) as i32;
+        text.draw(buf, &scale.label, (x, top as i32 + 2), FONT_SIZE, white);
+    }
+}
+
+// -----------------------------------------------------------------------------
@@ -18,6 +18,20 @@ extern crate arrayfire;
 extern crate num_complex;
 extern crate num_traits;
 extern crate input;
+extern crate rusttype;
+#[macro_use]
+extern crate prettytable;
+#[macro_use]
+extern crate approx;
+#[macro_use]
+extern crate proptest;
+extern crate quickcheck;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde;
+#[macro_use]
+extern crate serde_json;
+extern crate toml;
 // extern crate webm;
 // extern crate vpx;
 // extern crate vpx_sys;
@@ -28,11 +42,34 @@ extern crate conrod_core as conrod;
 
 extern crate conrod_piston;
 
+// The tests that need an ArrayFire device are ignored, so that `cargo test`
+// passes without one; `cargo test -- --ignored` runs them.
 pub mod matrix;
 pub mod lbm;
+pub mod refinement;
+pub mod ibm;
+pub mod particles;
+pub mod tracers;
+pub mod free_surface;
+pub mod schedule;
+pub mod probe;
+pub mod spectral;
+pub mod convergence;
+pub mod case;
+pub mod sweep;
+pub mod benchmark;
 pub mod display;
+pub mod worker;
+pub mod headless;
+pub mod viewport;
+pub mod colormap;
+pub mod legend;
 pub mod render;
+pub mod overlay;
+pub mod layout;
 pub mod theme;
+pub mod panel;
+pub mod editor;
 // pub mod preconditioned;
 // pub mod record;
 pub mod qchem;
@@ -9,6 +9,7 @@ extern crate piston;
 extern crate arrayfire;
 extern crate gif;
 extern crate image;
+extern crate clap;
 // extern crate ffmpeg;
 
 use chemsim::display::{Drawable, RGB, PixelPos};
@@ -16,6 +17,12 @@ use chemsim::lbm::{Scalar, Matrix};
 use arrayfire as af;
 use arrayfire::HasAfEnum;
 
+/// Where the P and I keys save and load the geometry.
+const GEOMETRY_PATH: &str = "geometry.png";
+
+/// How many steps apart the drag is plotted.
+const DRAG_INTERVAL: usize = 10;
+
 pub fn draw_matrix<D: Drawable>(
     buffer: &mut D,
     matrix: &chemsim::matrix::Matrix,
@@ -33,106 +40,297 @@ pub fn draw_matrix<D: Drawable>(
     }
 }
 
-#[derive(Copy, Clone)]
-pub enum DisplayMode {
-    Density,
-    Speed,
-    Velocity,
-    MomentumDensity,
+pub struct LBMSim {
+    size:         (usize, usize),
+    state:        chemsim::lbm::State<chemsim::lbm::D2Q9>,
+    monitor:      chemsim::convergence::ConvergenceMonitor<chemsim::lbm::D2Q9>,
+    layout:       chemsim::layout::Layout,
+    text:         chemsim::legend::TextRenderer,
+    overlays:     Vec<chemsim::overlay::Overlay>,
+    tracers:      chemsim::tracers::TracerSet,
+    /// Whether the mouse paints tracers rather than solid nodes.
+    paint_dye:    bool,
+    /// In pixels of the layout, and whether the left button is held.
+    cursor:       ([f64; 2], bool),
+    in_view:      bool,
+    viscosity:      Scalar,
+    inlet_velocity: Scalar,
+    operator:       chemsim::lbm::OperatorKind,
+    /// The channel walls; the drag shown in the panel is that on every
+    /// other solid node.
+    walls:          chemsim::lbm::Geometry,
+    editor:         chemsim::editor::Editor,
+    paused:         bool,
+    single_step:    bool,
+    steps:          usize,
+    mlups:          Scalar,
 }
 
-impl DisplayMode {
-    pub fn next(&self) -> DisplayMode {
-        match *self {
-            DisplayMode::Density         => DisplayMode::Speed,
-            DisplayMode::Speed           => DisplayMode::Velocity,
-            DisplayMode::Velocity        => DisplayMode::MomentumDensity,
-            DisplayMode::MomentumDensity => DisplayMode::Density,
+impl LBMSim {
+    /// Show the field at the given index of `Content::fields` in the
+    /// selected tile, unless it plots a series.
+    fn set_field(&mut self, index: usize) {
+        let mut fields = chemsim::layout::Content::fields();
+        let tile = self.layout.selected_tile_mut();
+        if tile.content.is_field() {
+            tile.set_content(fields.swap_remove(index));
         }
     }
-}
 
-pub struct LBMSim {
-    speed_factor: usize,
-    display_mode: DisplayMode,
-    size:         (usize, usize),
-    state:        chemsim::lbm::State<chemsim::lbm::D2Q9>,
-    cursor:       ([f64; 2], bool),
+    /// The index in `Content::fields` of the field of the selected tile.
+    fn field_index(&self) -> Option<usize> {
+        let name = self.layout.selected_tile().content.name();
+        chemsim::layout::Content::fields().iter().position(|f| f.name() == name)
+    }
+
+    /// The drag on every solid node but the walls.
+    fn drag(&self) -> Scalar {
+        let obstacles = af::and(&self.state.geometry,
+                                &af::eq(&self.walls, &false, false),
+                                false);
+        self.state.momentum_exchange(&obstacles, (0.0, 0.0)).0.to_pair().0
+    }
+
+    /// Start checking for convergence afresh, e.g. after a setting changed.
+    fn restart_monitor(&mut self) {
+        self.monitor = new_monitor();
+    }
 }
 
 impl chemsim::display::Simulation for LBMSim {
     fn size(&self) -> (usize, usize) { self.size }
 
-    fn handle(&mut self, input: &piston::input::Event) {
+    fn handle(&mut self, input: &piston::input::Event, view: &chemsim::viewport::Viewport) {
         use piston::input::*;
         use piston::input::mouse::*;
         use piston::input::keyboard::*;
+        use chemsim::colormap::Range;
+        use chemsim::editor::Mode;
 
         if let Some(pos) = input.mouse_cursor_args() {
-            self.cursor = (pos, self.cursor.1);
-            if self.cursor.1 {
-                let x = f64::floor(pos[1]) as usize;
-                let y = f64::floor(pos[0]) as usize;
-                if (x < self.size.0) && (y < self.size.1) {
-                    let dims = self.state.geometry.dims();
-                    let [a, b, c, d] = dims.get();
-                    let mut vec: Vec<bool> = Vec::new();
-                    vec.resize((a * b * c * d) as usize, false);
-                    self.state.geometry.host(&mut vec);
-                    for a in 0 .. self.size.0 {
-                        for b in 0 .. self.size.1 {
-                            let diffX = i64::abs(a as i64 - x as i64);
-                            let diffY = i64::abs(b as i64 - y as i64);
-                            vec[(b * self.size.0) + a] = (diffX < 5) && (diffY < 5);
-                        }
-                    }
-                    self.state.geometry = af::Array::new(&vec[..], dims);
-                }
+            // Off the view, e.g. over the control panel, the cursor keeps
+            // its last position.
+            let mapped = view.to_lattice(pos);
+            self.in_view = mapped.is_some();
+            self.cursor = (mapped.unwrap_or(self.cursor.0), self.cursor.1);
+            // Strokes stay in the tile they started in.
+            let point = self.layout.relative(self.layout.selected, self.cursor.0);
+            let point = [point[0] as Scalar, point[1] as Scalar];
+            if self.cursor.1 && self.paint_dye {
+                self.tracers.paint(point, 5.0, 8, self.state.time,
+                                   chemsim::display::RGB(255, 80, 40));
+            } else if self.editor.is_drawing() {
+                self.editor.drag(point, &mut self.state);
             }
         } else if let Some(Button::Mouse(MouseButton::Left)) = input.press_args() {
             self.cursor = (self.cursor.0, true);
+            // Presses beside the tiles, e.g. on the control panel, are not
+            // edits; those on a tile select it.
+            let located = if self.in_view { self.layout.locate(self.cursor.0) } else { None };
+            if let Some((tile, [x, y])) = located {
+                self.layout.selected = tile;
+                if self.layout.tiles[tile].content.is_field() && !self.paint_dye {
+                    self.editor.press([x as Scalar, y as Scalar], &mut self.state);
+                }
+            }
         } else if let Some(Button::Mouse(MouseButton::Left)) = input.release_args() {
             self.cursor = (self.cursor.0, false);
+            if self.editor.is_drawing() {
+                self.editor.release(&mut self.state);
+                self.restart_monitor();
+            }
         } else if let Some(Button::Keyboard(k)) = input.release_args() {
-            let old_speed_factor = self.speed_factor;
-
             match k {
-                Key::S => {
-                    self.speed_factor -= 1;
+                Key::Space => {
+                    if let Some(i) = self.field_index() {
+                        let next = (i + 1) % chemsim::layout::Content::fields().len();
+                        self.set_field(next);
+                    }
                 },
-                Key::W  => {
-                    self.speed_factor += 1;
+                Key::Tab => {
+                    self.layout.select_next();
                 },
-                Key::Space => {
-                    self.display_mode = self.display_mode.next();
+                Key::K => {
+                    self.layout = if self.layout.tiles.len() == 1 {
+                        tiled_layout(self.size)
+                    } else {
+                        single_layout(self.size)
+                    };
+                },
+                Key::C => {
+                    let scale = &mut self.layout.selected_tile_mut().scale;
+                    let next = scale.lut.colormap.next();
+                    scale.set_colormap(next);
+                    println!("Colour map is now {}", next.name());
+                },
+                Key::L => {
+                    let scale = &mut self.layout.selected_tile_mut().scale;
+                    scale.logarithmic = !scale.logarithmic;
+                    println!("Logarithmic colour scale: {}", scale.logarithmic);
+                },
+                Key::R => {
+                    let scale = &mut self.layout.selected_tile_mut().scale;
+                    scale.range = match scale.range {
+                        Range::Percentile { .. } => Range::Symmetric(None),
+                        _ => Range::default(),
+                    };
+                    println!("Colour range is now {:?}", scale.range);
+                },
+                Key::T => {
+                    self.paint_dye = !self.paint_dye;
+                    println!("Painting {}", if self.paint_dye { "dye" } else { "walls" });
+                },
+                Key::X => {
+                    self.tracers.clear();
+                },
+                Key::E => {
+                    self.editor.mode = match self.editor.mode {
+                        Mode::Add   => Mode::Erase,
+                        Mode::Erase => Mode::Add,
+                    };
+                    println!("Editing mode is now {:?}", self.editor.mode);
+                },
+                Key::B => {
+                    self.editor.tool = self.editor.tool.next();
+                    println!("Editing tool is now {}", self.editor.tool.name());
+                },
+                Key::LeftBracket => {
+                    self.editor.radius = (self.editor.radius - 1.0).max(1.0);
+                    println!("Brush radius is now {}", self.editor.radius);
+                },
+                Key::RightBracket => {
+                    self.editor.radius += 1.0;
+                    println!("Brush radius is now {}", self.editor.radius);
+                },
+                Key::Z => {
+                    if self.editor.undo(&mut self.state) { self.restart_monitor(); }
+                },
+                Key::Y => {
+                    if self.editor.redo(&mut self.state) { self.restart_monitor(); }
+                },
+                Key::P => {
+                    match self.editor.save(&self.state, GEOMETRY_PATH) {
+                        Ok(()) => println!("Saved the geometry to {}", GEOMETRY_PATH),
+                        Err(e) => println!("Failed to save the geometry: {}", e),
+                    }
+                },
+                Key::I => {
+                    match self.editor.load(&mut self.state, GEOMETRY_PATH) {
+                        Ok(()) => {
+                            println!("Loaded the geometry from {}", GEOMETRY_PATH);
+                            self.restart_monitor();
+                        },
+                        Err(e) => println!("Failed to load the geometry: {}", e),
+                    }
+                },
+                Key::O => {
+                    self.overlays = next_overlays(&self.overlays, self.size);
+                    let names: Vec<&str> = self.overlays.iter().map(|o| o.name()).collect();
+                    println!("Overlays are now {:?}", names);
+                },
+                Key::F => {
+                    // Hold the limits of the last frame from now on.
+                    let tile = self.layout.selected_tile_mut();
+                    let (min, max) = tile.limits.get();
+                    tile.scale.range = Range::Fixed { min: min, max: max };
+                    println!("Colour range is now {:?}", tile.scale.range);
                 },
                 _ => {},
             };
+        }
+    }
 
-            if self.speed_factor < 1 {
-                self.speed_factor = 1;
-            }
+    fn step(&mut self) {
+        if !self.monitor.should_step() { return; }
+        self.single_step = false;
+        let start = std::time::Instant::now();
+        self.state.step();
+        self.monitor.check(&self.state);
+        if let Err(e) = self.tracers.step(&self.state) {
+            println!("Failed to write tracer trajectories: {}", e);
+        }
+        self.steps += 1;
+        if self.steps % DRAG_INTERVAL == 0 {
+            let (time, drag) = (self.state.time, self.drag());
+            self.layout.record("Drag", time, drag);
+        }
+        // Smoothed, as single steps vary.
+        let nodes = (self.size.0 * self.size.1) as f64;
+        let mlups = (nodes / start.elapsed().as_float_secs() / 1.0e6) as Scalar;
+        self.mlups = 0.9 * self.mlups + 0.1 * mlups;
+    }
 
-            if self.speed_factor > 100 {
-                self.speed_factor = 100;
-            }
+    /// Also once converged, until a setting changes.
+    fn is_paused(&self) -> bool {
+        (self.paused && !self.single_step) || !self.monitor.should_step()
+    }
 
-            if old_speed_factor != self.speed_factor {
-                println!("Speed factor is now {}", self.speed_factor);
-            }
-        }
+    fn is_finished(&self) -> bool {
+        self.monitor.should_stop()
+    }
 
-        // FIXME: drawing boundaries etc.
+    fn image_size(&self) -> (usize, usize) { self.layout.size() }
+
+    fn plots(&self) -> Vec<chemsim::panel::Plot> { self.layout.plots() }
+
+    fn controls(&self) -> Option<chemsim::panel::Controls> {
+        let cs = self.state.isothermal_speed_of_sound();
+        let fields = chemsim::layout::Content::fields();
+        Some(chemsim::panel::Controls {
+            viscosity:      self.viscosity,
+            inlet_velocity: self.inlet_velocity,
+            operator:       self.operator,
+            display_modes:  fields.iter().map(|f| f.name()).collect(),
+            display_mode:   self.field_index().unwrap_or(0),
+            paused:         self.paused,
+            time:           self.state.time,
+            mlups:          self.mlups,
+            max_mach:       (self.state.speed().maximum_real() as Scalar) / cs,
+            drag:           Some(self.drag()),
+        })
     }
 
-    fn step(&mut self, elapsed: &std::time::Duration) {
-        for _ in 0 .. self.speed_factor {
-            let t = std::time::Instant::now();
-            self.state.step();
-            println!("Step {} took {} ms",
-                     self.state.time,
-                     t.elapsed().as_millis());
+    fn command(&mut self, command: chemsim::panel::Command) {
+        use chemsim::panel::Command;
+        use chemsim::schedule::Schedule;
+
+        match command {
+            Command::SetViscosity(viscosity) => {
+                self.viscosity = viscosity;
+                self.state.collision = self.operator.build(viscosity,
+                                                           &self.state.discretization);
+            },
+            Command::SetInletVelocity(velocity) => {
+                self.inlet_velocity = velocity;
+                for inlet in &mut self.state.inlets {
+                    inlet.velocity.0 = Schedule::Constant(velocity);
+                }
+            },
+            Command::SetOperator(operator) => {
+                self.operator = operator;
+                self.state.collision = operator.build(self.viscosity,
+                                                      &self.state.discretization);
+            },
+            Command::SetDisplayMode(i) => {
+                self.set_field(i);
+                return;
+            },
+            Command::Pause(paused) => {
+                self.paused = paused;
+                return;
+            },
+            Command::Step => {
+                self.single_step = true;
+                return;
+            },
+            Command::Reset => {
+                self.state = build_state(self.size, self.viscosity, self.operator,
+                                         self.inlet_velocity);
+                self.tracers.clear();
+                self.editor.clear_history();
+            },
         }
+        self.restart_monitor();
     }
 
     fn render<D: chemsim::display::Drawable>(&self, buf: &mut D) {
@@ -141,7 +339,8 @@ impl chemsim::display::Simulation for LBMSim {
         // }
         // println!("Max speed: {}", self.state.speed().maximum_real());
         // for (i, (_, pop)) in self.state.populations().iter().enumerate() {
-        //     let fft = pop.dft(1.0).abs();
+        //     let (re, im) = pop.dft(1.0);
+        //     let fft = (re.hadamard(&re) + im.hadamard(&im)).sqrt();
         //     let nonzeros = af::count_all(fft.get_array()).0 as usize;
         //     let total    = fft.get_width() * fft.get_height();
         //     assert!(total > nonzeros);
@@ -154,30 +353,89 @@ impl chemsim::display::Simulation for LBMSim {
 
         use chemsim::render::*;
 
-        match self.display_mode {
-            DisplayMode::Density => {
-                render_scalar_field(&self.state.density(), buf);
-                println!("Render mode: density");
-            },
-            DisplayMode::Speed => {
-                render_scalar_field(&self.state.speed(), buf);
-                println!("Render mode: speed");
-            },
-            DisplayMode::Velocity => {
-                render_vector_field(&self.state.velocity(), buf);
-                println!("Render mode: velocity");
-            },
-            DisplayMode::MomentumDensity => {
-                render_vector_field(&self.state.momentum_density(), buf);
-                println!("Render mode: momentum density");
-            },
-        };
+        self.layout.render(&self.state, &self.text, buf, |tile| {
+            chemsim::overlay::draw_overlays(&self.overlays, &self.state.velocity(),
+                                            &self.state.geometry, tile);
+            render_geometry(&self.state.geometry, tile);
+            self.editor.draw_preview(tile);
+            self.tracers.draw(chemsim::tracers::Style::Trails, tile);
+        });
+    }
+}
+
+/// One tile of the density.
+fn single_layout(size: (usize, usize)) -> chemsim::layout::Layout {
+    use chemsim::layout::*;
+    Layout::single(size, Content::Scalar(chemsim::probe::Field::Density))
+}
 
-        render_geometry(&self.state.geometry, buf);
+/// The density, vorticity and velocity, and a plot of the drag.
+fn tiled_layout(size: (usize, usize)) -> chemsim::layout::Layout {
+    use chemsim::layout::*;
+    use chemsim::probe::Field;
+    Layout::new(size, 2)
+        .with_tile(Content::Scalar(Field::Density))
+        .with_tile(Content::Scalar(Field::Vorticity))
+        .with_tile(Content::Velocity)
+        .with_tile(Content::Series(Series::new("Drag", 2000)))
+}
+
+/// Cycle through no overlay, glyphs, streamlines and LIC.
+fn next_overlays(
+    current: &[chemsim::overlay::Overlay],
+    size:    (usize, usize),
+) -> Vec<chemsim::overlay::Overlay> {
+    use chemsim::overlay::Overlay;
+
+    let next = match current.first() {
+        None => Some("glyphs"),
+        Some(&Overlay::Glyphs(_)) => Some("streamlines"),
+        Some(&Overlay::Streamlines(_)) => Some("lic"),
+        Some(&Overlay::Lic(_)) => None,
+    };
+    next.and_then(|name| Overlay::from_name(name, size)).into_iter().collect()
+}
+
+fn new_monitor() -> chemsim::convergence::ConvergenceMonitor<chemsim::lbm::D2Q9> {
+    use chemsim::convergence::*;
+    ConvergenceMonitor::new(100, 1.0e-6, Action::OutputOnly)
+}
+
+/// The nodes along the edges of the box.
+fn walls(size: (usize, usize)) -> chemsim::lbm::Geometry {
+    let (w, h) = size;
+    let mut vec = Vec::with_capacity(w * h);
+    for y in 0 .. h {
+        for x in 0 .. w {
+            vec.push((x == 0) || (y == 0) || (x == w - 1) || (y == h - 1));
+        }
     }
+    let dim4 = af::Dim4::new(&[w as u64, h as u64, 1, 1]);
+    af::transpose(&af::Array::new(&vec[..], dim4), false)
 }
 
-fn initial_state(size: (usize, usize)) -> LBMSim {
+/// The cylinder in the middle of the box.
+fn obstacle(size: (usize, usize)) -> chemsim::lbm::Geometry {
+    let (w, h) = size;
+    let mut vec = Vec::with_capacity(w * h);
+    for y in 0 .. h {
+        for x in 0 .. w {
+            let mut r = 0.0;
+            r += (x as f64 - (w as f64 / 2.0)).powi(2);
+            r += (y as f64 - (h as f64 / 2.0)).powi(2);
+            vec.push(r.sqrt() < 25.0);
+        }
+    }
+    let dim4 = af::Dim4::new(&[w as u64, h as u64, 1, 1]);
+    af::transpose(&af::Array::new(&vec[..], dim4), false)
+}
+
+fn build_state(
+    size:           (usize, usize),
+    viscosity:      Scalar,
+    operator:       chemsim::lbm::OperatorKind,
+    inlet_velocity: Scalar,
+) -> chemsim::lbm::State<chemsim::lbm::D2Q9> {
     use chemsim::*;
 
     let (w, h) = size;
@@ -187,16 +445,15 @@ fn initial_state(size: (usize, usize)) -> LBMSim {
     // let collision = lbm::BGK { tau: 15.0 };
 
     // let viscosity = 10.0;
-    // let collision = lbm::TRT::new(0.25, viscosity, &disc);
+    // let collision = lbm::TRT::new(0.25, viscosity, 1.0 / 3.0, &disc);
 
     // let viscosity = 10.0;
-    // let collision = lbm::Regularized::new(lbm::TRT::new(0.25, viscosity, &disc));
+    // let collision = lbm::Regularized::new(lbm::TRT::new(0.25, viscosity, 1.0 / 3.0, &disc));
 
     // let viscosity = 10.0;
     // let collision = lbm::KBC::new(viscosity);
 
-    let viscosity = 10.0;
-    let collision = lbm::Regularized::new(lbm::KBC::new(viscosity));
+    let collision = operator.build(viscosity, &disc);
 
     let initial_velocity = {
         let mut vec_x = Vec::new();
@@ -205,7 +462,7 @@ fn initial_state(size: (usize, usize)) -> LBMSim {
         vec_y.resize(w * h, 0.0);
         for x in 0 .. w {
             for y in 0 .. h {
-                let scale = 0.02;
+                let scale = inlet_velocity;
                 // vec_x[(y * w) + x] = -(y as Scalar) * scale / (h as Scalar);
                 // vec_y[(y * w) + x] =  (x as Scalar) * scale / (w as Scalar);
                 vec_x[(y * w) + x] = scale; //  * f32::sin(x as f32 / 25.0);
@@ -280,13 +537,6 @@ fn initial_state(size: (usize, usize)) -> LBMSim {
                     //     || (x ==     0) || (y ==     0)
                     //     || (x == w - 1) || (y == h - 1));
                     // set(x, y, (y == 0) || (y == h - 1));
-                    let mut r = 0.0;
-                    r += (x as f64 - (w as f64 / 2.0)).powi(2);
-                    r += (y as f64 - (h as f64 / 2.0)).powi(2);
-                    r = r.sqrt();
-                    if r < 25.0 {
-                        set(x, y, true);
-                    }
                     if x == 0     { set(x, y, true); }
                     if y == 0     { set(x, y, true); }
                     if x == w - 1 { set(x, y, true); }
@@ -308,26 +558,124 @@ fn initial_state(size: (usize, usize)) -> LBMSim {
         let vec = vec;
 
         let dim4 = af::Dim4::new(&[w as u64, h as u64, 1, 1]);
-        af::transpose(&af::Array::new(&vec[..], dim4), false)
+        let walls = af::transpose(&af::Array::new(&vec[..], dim4), false);
+        af::or(&walls, &obstacle(size), false)
     };
 
-    let state = lbm::State::initial(
+    let mut state = lbm::State::initial(
         Box::new(lattice),
         geometry,
-        Box::new(collision),
+        collision,
         disc,
     );
 
+    // Hold the column next to the left wall at the inlet velocity.
+    let inlet = {
+        let mut vec = Vec::with_capacity(w * h);
+        for y in 0 .. h {
+            for x in 0 .. w { vec.push((x == 1) && (y != 0) && (y != h - 1)); }
+        }
+        let dim4 = af::Dim4::new(&[w as u64, h as u64, 1, 1]);
+        af::transpose(&af::Array::new(&vec[..], dim4), false)
+    };
+    state.inlets.push(schedule::Inlet::new(inlet, (
+        schedule::Schedule::Constant(inlet_velocity),
+        schedule::Schedule::Constant(0.0),
+    )));
+
+    state
+}
+
+fn initial_state(size: (usize, usize)) -> LBMSim {
+    use chemsim::*;
+
+    let (_, h) = size;
+    let viscosity = 10.0;
+    let operator = lbm::OperatorKind::RegularizedKBC;
+    let inlet_velocity = 0.02;
+
     LBMSim {
         size:         size,
-        state:        state,
-        speed_factor: 2,
-        display_mode: DisplayMode::Density,
+        state:        build_state(size, viscosity, operator, inlet_velocity),
+        monitor:      new_monitor(),
+        layout:       single_layout(size),
+        text:         legend::TextRenderer::new(),
+        overlays:     Vec::new(),
+        tracers:      tracers::TracerSet::new(tracers::Integrator::RK2,
+                                              tracers::Kind::Massless)
+            .with_source(tracers::Source::new(
+                probe::Shape::Line { start: [2.0, 40.0], end: [2.0, (h - 41) as Scalar],
+                                     samples: 16 },
+                20, display::RGB(255, 255, 255))),
+        paint_dye:    false,
         cursor:       ([0.0, 0.0], false),
+        in_view:      false,
+        viscosity:      viscosity,
+        inlet_velocity: inlet_velocity,
+        operator:       operator,
+        walls:          walls(size),
+        editor:         chemsim::editor::Editor::new(),
+        paused:         false,
+        single_step:    false,
+        steps:          0,
+        mlups:          0.0,
     }
 }
 
+fn is_number(value: String) -> Result<(), String> {
+    value.parse::<usize>().map(|_| ()).map_err(|_| format!("{:?} is not a number", value))
+}
+
+/// The command line: a single case, e.g. as a child process of a sweep, a
+/// whole sweep, images of the fields without a window, or by default the
+/// interactive window.
+fn arguments<'a, 'b>() -> clap::App<'a, 'b> {
+    use clap::{App, Arg};
+    App::new("chemsim")
+        .about("Lattice Boltzmann simulations on ArrayFire")
+        .arg(Arg::with_name("device").long("device").value_name("INDEX")
+             .validator(is_number)
+             .help("The ArrayFire device to run on"))
+        .arg(Arg::with_name("case").long("case").value_name("FILE")
+             .conflicts_with_all(&["sweep", "headless"])
+             .help("Run a single case file"))
+        .arg(Arg::with_name("result").long("result").value_name("FILE")
+             .requires("case")
+             .help("Where to save the result of the case, instead of printing it"))
+        .arg(Arg::with_name("sweep").long("sweep").value_name("FILE")
+             .conflicts_with("headless")
+             .help("Run every case of a sweep file"))
+        .arg(Arg::with_name("output").long("output").value_name("DIRECTORY")
+             .requires("sweep")
+             .help("Where to save the results of the sweep [default: sweep]"))
+        .arg(Arg::with_name("jobs").long("jobs").value_name("N")
+             .requires("sweep").conflicts_with("devices").validator(is_number)
+             .help("Run the sweep in this many child processes"))
+        .arg(Arg::with_name("devices").long("devices")
+             .requires("sweep")
+             .help("Run the sweep on every device in parallel"))
+        .arg(Arg::with_name("headless").long("headless").value_name("DIRECTORY")
+             .help("Write images of the fields instead of opening a window"))
+        .arg(Arg::with_name("steps").long("steps").value_name("N")
+             .requires("headless").validator(is_number)
+             .help("How many steps to run headless [default: 1000]"))
+        .arg(Arg::with_name("every").long("every").value_name("N")
+             .requires("headless").validator(is_number)
+             .help("How many steps apart the images are [default: 100]"))
+        .arg(Arg::with_name("fields").long("fields").value_name("NAMES")
+             .requires("headless").use_delimiter(true)
+             .help("The fields to draw, e.g. density,vorticity"))
+        .arg(Arg::with_name("overlays").long("overlays").value_name("NAMES")
+             .requires("headless").use_delimiter(true)
+             .help("The overlays to draw over the fields, e.g. glyphs,streamlines"))
+        .arg(Arg::with_name("format").long("format").value_name("EXTENSION")
+             .requires("headless")
+             .help("The image format, e.g. png or jpg"))
+}
+
 fn main() -> std::io::Result<()> {
+    let matches = arguments().get_matches();
+
     af::init();
     af::set_backend(af::Backend::CUDA);
     // ffmpeg::init()?;
@@ -339,6 +687,47 @@ fn main() -> std::io::Result<()> {
 
     println!("[NOTE] ArrayFire device info: {:?}", af::device_info());
 
+    // The validators have checked that the numbers parse.
+    let number = |name: &str, default: usize| {
+        matches.value_of(name).map_or(default, |n| n.parse().unwrap())
+    };
+    if let Some(device) = matches.value_of("device") {
+        af::set_device(device.parse().unwrap());
+    }
+    if let Some(path) = matches.value_of("case") {
+        let case = chemsim::case::CaseFile::load(path)?;
+        let result = chemsim::case::run(&case);
+        match matches.value_of("result") {
+            Some(output) => result.save(output)?,
+            None => chemsim::sweep::summary(&[result]).printstd(),
+        }
+        return Ok(());
+    }
+    if let Some(path) = matches.value_of("sweep") {
+        use chemsim::sweep::*;
+        let sweep = SweepFile::load(path)?;
+        let cases = sweep.expand();
+        let directory = std::path::PathBuf::from(
+            matches.value_of("output").unwrap_or("sweep"));
+        let execution = if matches.is_present("jobs") {
+            Execution::Processes {
+                program:   std::env::current_exe()?,
+                jobs:      number("jobs", 1),
+                devices:   af::device_count() as usize,
+                directory: directory.clone(),
+            }
+        } else if matches.is_present("devices") {
+            Execution::Devices((0 .. af::device_count()).collect())
+        } else {
+            Execution::Serial
+        };
+        let results = run(&cases, &execution)?;
+        std::fs::create_dir_all(&directory)?;
+        save(&results, directory.join("summary.json"))?;
+        summary(&results).printstd();
+        return Ok(());
+    }
+
     let recorder = false;
     let (w, h) = (400, 400);
 
@@ -346,23 +735,51 @@ fn main() -> std::io::Result<()> {
 
     use chemsim::display::Simulation;
 
-    let initial = initial_state((w, h));
+    // Images of the fields instead of a window, e.g. on a machine without a
+    // display.
+    if let Some(directory) = matches.value_of("headless") {
+        let mut sim = initial_state((w, h));
+        if let Some(fields) = matches.values_of("fields") {
+            let names: Vec<String> = fields.map(|s| s.trim().to_string()).collect();
+            let columns = std::cmp::min(names.len(), 2);
+            sim.layout = chemsim::layout::Layout::of_fields((w, h), &names, columns)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        }
+        if let Some(names) = matches.values_of("overlays") {
+            for name in names {
+                match chemsim::overlay::Overlay::from_name(name.trim(), (w, h)) {
+                    Some(overlay) => sim.overlays.push(overlay),
+                    None => return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("unknown overlay {:?}", name))),
+                }
+            }
+        }
+        sim.layout.highlight = false;
+        let mut naming = chemsim::headless::Naming::new(directory);
+        if let Some(format) = matches.value_of("format") {
+            naming.extension = format.to_string();
+        }
+        let frames = chemsim::headless::run(&mut sim, number("steps", 1000),
+                                            number("every", 100), &naming)?;
+        println!("Wrote {} images to {}", frames, naming.directory.display());
+        return Ok(());
+    }
+
 
     // {
     //     let start_time = std::time::Instant::now();
     //
-    //     let mut state = initial;
-    //     let mut last_step = std::time::Instant::now();
+    //     let mut state = initial_state((w, h));
     //     for _ in 0 .. 1000 {
-    //         state.step(&last_step.elapsed());
-    //         last_step = std::time::Instant::now();
+    //         state.step();
     //     }
     //
     //     println!("Average frames per second: {}",
     //              1000.0 / start_time.elapsed().as_float_secs());
     // }
 
-    chemsim::display::example(initial);
+    chemsim::display::example(move || initial_state((w, h)));
 
     // if recorder {
     //     let (w, h) = initial.size();
@@ -39,7 +39,7 @@ impl Matrix {
 
     pub fn new_filled(value: f32, dims: (usize, usize)) -> Self {
         let (w, h) = dims;
-        let dim4 = af::Dim4::new(&[w as u64, h as u64, 1, 1]);
+        let dim4 = af::Dim4::new(&[h as u64, w as u64, 1, 1]);
         Matrix::unsafe_new(af::constant(value, dim4))
     }
 
@@ -212,6 +212,62 @@ impl Matrix {
     pub fn minof(&self, rhs: &Self) -> Self {
         Matrix::unsafe_new(af::minof(&self.array, &rhs.array, true))
     }
+
+    /// The block of the given shape whose top-left corner is at the given
+    /// position.
+    pub fn subregion(&self, origin: (usize, usize), dims: (usize, usize)) -> Self {
+        Matrix::unsafe_new(af::index(&self.array, &region_seqs(origin, dims)))
+    }
+
+    /// Overwrite the block whose top-left corner is at the given position with
+    /// the given matrix.
+    pub fn set_subregion(&mut self, origin: (usize, usize), block: &Self) {
+        let seqs = region_seqs(origin, block.get_shape());
+        af::assign_seq(&mut self.array, &seqs, &block.array);
+    }
+
+    /// Resample a matrix to the given shape.
+    pub fn resize(&self, dims: (usize, usize), method: af::InterpType) -> Self {
+        let (w, h) = dims;
+        Matrix::unsafe_new(af::resize(&self.array, h as i64, w as i64, method))
+    }
+
+    /// The two-dimensional discrete Fourier transform, as its real and
+    /// imaginary parts.
+    pub fn dft(&self, norm_factor: f64) -> (Self, Self) {
+        let (w, h) = self.get_shape();
+        let transform = af::fft2(&self.array, norm_factor, h as i64, w as i64);
+        (Matrix::unsafe_new(af::real(&transform)),
+         Matrix::unsafe_new(af::imag(&transform)))
+    }
+
+    /// The power spectral density over wavenumbers, with the zero wavenumber
+    /// moved to the center.
+    pub fn psd(&self) -> Self {
+        let (w, h) = self.get_shape();
+        let (re, im) = self.dft(1.0);
+        let power = (re.hadamard(&re) + im.hadamard(&im)).scale(1.0 / ((w * h) as f32));
+        let offsets = [(h / 2) as i32, (w / 2) as i32, 0, 0];
+        Matrix::unsafe_new(af::shift(&power.array, &offsets))
+    }
+
+    /// Keep every `factor`-th row and column, starting with the first.
+    pub fn decimate(&self, factor: usize) -> Self {
+        let (w, h) = self.get_shape();
+        let step = factor as f64;
+        let seqs = [af::Seq::new(0.0, (h - 1) as f64, step),
+                    af::Seq::new(0.0, (w - 1) as f64, step)];
+        Matrix::unsafe_new(af::index(&self.array, &seqs))
+    }
+}
+
+/// The sequences indexing the block of the given shape whose top-left corner
+/// is at the given position, in the `(row, column)` order ArrayFire expects.
+pub fn region_seqs(origin: (usize, usize), dims: (usize, usize)) -> [af::Seq<f64>; 2] {
+    let ((x, y), (w, h)) = (origin, dims);
+    assert!((w > 0) && (h > 0));
+    [af::Seq::new(y as f64, (y + h - 1) as f64, 1.0),
+     af::Seq::new(x as f64, (x + w - 1) as f64, 1.0)]
 }
 
 // -----------------------------------------------------------------------------
@@ -289,3 +345,143 @@ impl<'a, 'b> Sub<&'a Matrix> for &'b Matrix {
 }
 
 // -----------------------------------------------------------------------------
+
+// -----------------------------------------------------------------------------
+
+/// Checks of `Matrix` arithmetic against the same operations on the host.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::{quickcheck, TestResult};
+
+    const SHAPE: (usize, usize) = (4, 3);
+    const TOLERANCE: f32 = 1.0e-5;
+
+    /// Two matrices' worth of reasonable values, or `None` if quickcheck gave
+    /// too few of them.
+    fn operands(data: &[f32]) -> Option<(Vec<f32>, Vec<f32>)> {
+        let n = SHAPE.0 * SHAPE.1;
+        if data.len() < 2 * n { return None; }
+        if data.iter().any(|x| !x.is_finite() || (x.abs() > 1.0e6)) { return None; }
+        Some((data[0 .. n].to_vec(), data[n .. (2 * n)].to_vec()))
+    }
+
+    fn agrees(matrix: &Matrix, reference: &[f32]) -> bool {
+        matrix.get_underlying().iter().zip(reference).all(|(a, b)| {
+            relative_eq!(*a, *b, epsilon = TOLERANCE, max_relative = TOLERANCE)
+        })
+    }
+
+    fn check<F, G>(data: Vec<f32>, device: F, host: G) -> TestResult
+    where F: Fn(&Matrix, &Matrix) -> Matrix, G: Fn(f32, f32) -> f32 {
+        match operands(&data) {
+            None => TestResult::discard(),
+            Some((a, b)) => {
+                let result = device(&Matrix::new(&a, SHAPE).unwrap(),
+                                    &Matrix::new(&b, SHAPE).unwrap());
+                let reference: Vec<f32>
+                    = a.iter().zip(&b).map(|(x, y)| host(*x, *y)).collect();
+                TestResult::from_bool(agrees(&result, &reference))
+            },
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn test_round_trip() {
+        fn prop(data: Vec<f32>) -> TestResult {
+            check(data, |a, _| a.clone(), |x, _| x)
+        }
+        quickcheck(prop as fn(Vec<f32>) -> TestResult);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_add() {
+        fn prop(data: Vec<f32>) -> TestResult {
+            check(data, |a, b| a + b, |x, y| x + y)
+        }
+        quickcheck(prop as fn(Vec<f32>) -> TestResult);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_sub() {
+        fn prop(data: Vec<f32>) -> TestResult {
+            check(data, |a, b| a - b, |x, y| x - y)
+        }
+        quickcheck(prop as fn(Vec<f32>) -> TestResult);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_hadamard() {
+        fn prop(data: Vec<f32>) -> TestResult {
+            check(data, |a, b| a.hadamard(b), |x, y| x * y)
+        }
+        quickcheck(prop as fn(Vec<f32>) -> TestResult);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_divide() {
+        fn prop(data: Vec<f32>) -> TestResult {
+            let n = SHAPE.0 * SHAPE.1;
+            if data.iter().skip(n).take(n).any(|y| y.abs() < 1.0e-3) {
+                return TestResult::discard();
+            }
+            check(data, |a, b| a.divide(b), |x, y| x / y)
+        }
+        quickcheck(prop as fn(Vec<f32>) -> TestResult);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_scale_and_shift() {
+        fn prop(data: Vec<f32>, k: f32) -> TestResult {
+            if !k.is_finite() || (k.abs() > 1.0e3) { return TestResult::discard(); }
+            check(data, |a, _| a.scale(k).shift(k), |x, _| x * k + k)
+        }
+        quickcheck(prop as fn(Vec<f32>, f32) -> TestResult);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_transpose() {
+        fn prop(data: Vec<f32>) -> TestResult {
+            let (w, h) = SHAPE;
+            match operands(&data) {
+                None => TestResult::discard(),
+                Some((a, _)) => {
+                    let transposed = Matrix::new(&a, SHAPE).unwrap().transpose();
+                    let mut reference = Vec::with_capacity(w * h);
+                    for x in 0 .. w {
+                        for y in 0 .. h { reference.push(a[(y * w) + x]); }
+                    }
+                    TestResult::from_bool(
+                        (transposed.get_shape() == (h, w))
+                            && agrees(&transposed, &reference))
+                },
+            }
+        }
+        quickcheck(prop as fn(Vec<f32>) -> TestResult);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_sum() {
+        fn prop(data: Vec<f32>) -> TestResult {
+            match operands(&data) {
+                None => TestResult::discard(),
+                Some((a, _)) => {
+                    let sum = Matrix::new(&a, SHAPE).unwrap().sum();
+                    let reference: f64 = a.iter().map(|x| *x as f64).sum();
+                    TestResult::from_bool(
+                        relative_eq!(sum, reference,
+                                     epsilon = 1.0e-3, max_relative = 1.0e-5))
+                },
+            }
+        }
+        quickcheck(prop as fn(Vec<f32>) -> TestResult);
+    }
+}
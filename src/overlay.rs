@@ -0,0 +1,343 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use arrayfire as af;
+use super::lbm::{Geometry, Matrix, Scalar};
+use super::probe::Shape;
+use super::legend::blend;
+use super::display::{Drawable, RGB, PixelPos};
+
+// -----------------------------------------------------------------------------
+
+/// A copy of a vector field and the solid nodes on the host, for overlays
+/// that follow the field from point to point.
+pub struct HostVectorField {
+    pub size:  (usize, usize),
+    pub vx:    Vec<Scalar>,
+    pub vy:    Vec<Scalar>,
+    /// Row-major like the velocity.
+    pub solid: Vec<bool>,
+}
+
+impl HostVectorField {
+    pub fn new(field: &(Matrix, Matrix), geometry: &Geometry) -> Self {
+        let size = field.0.get_shape();
+        let mut solid = vec![false; size.0 * size.1];
+        af::transpose(geometry, false).host(&mut solid);
+        HostVectorField {
+            size:  size,
+            vx:    field.0.get_underlying(),
+            vy:    field.1.get_underlying(),
+            solid: solid,
+        }
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        y * self.size.0 + x
+    }
+
+    /// The vector at a point, interpolated bilinearly between the nodes, or
+    /// nothing outside of the lattice or next to a solid node.
+    pub fn sample(&self, point: [Scalar; 2]) -> Option<(Scalar, Scalar)> {
+        let (w, h) = self.size;
+        let [x, y] = point;
+        if (x < 0.0) || (y < 0.0) || (x > (w - 1) as Scalar) || (y > (h - 1) as Scalar) {
+            return None;
+        }
+        let (i, j) = (std::cmp::min(x as usize, w - 2), std::cmp::min(y as usize, h - 2));
+        let (s, t) = (x - i as Scalar, y - j as Scalar);
+        let corners = [(i, j, (1.0 - s) * (1.0 - t)), (i + 1, j, s * (1.0 - t)),
+                       (i, j + 1, (1.0 - s) * t),       (i + 1, j + 1, s * t)];
+        let (mut u, mut v) = (0.0, 0.0);
+        for &(a, b, weight) in &corners {
+            let k = self.index(a, b);
+            if self.solid[k] { return None; }
+            u += weight * self.vx[k];
+            v += weight * self.vy[k];
+        }
+        Some((u, v))
+    }
+
+    /// The largest magnitude in the field.
+    pub fn max_speed(&self) -> Scalar {
+        self.vx.iter().zip(&self.vy)
+            .map(|(u, v)| (u * u + v * v).sqrt())
+            .fold(0.0, Scalar::max)
+    }
+
+    /// The unit vector along the field at a point, where it is defined and
+    /// not vanishingly small.
+    fn direction(&self, point: [Scalar; 2]) -> Option<(Scalar, Scalar)> {
+        let (u, v) = self.sample(point)?;
+        let speed = (u * u + v * v).sqrt();
+        if speed < 1.0e-12 { None } else { Some((u / speed, v / speed)) }
+    }
+}
+
+/// Blend a straight line into the drawable, clipped to its edges.
+pub fn draw_line<D: Drawable>(
+    buf:     &mut D,
+    from:    [Scalar; 2],
+    to:      [Scalar; 2],
+    color:   RGB,
+    opacity: Scalar,
+) {
+    let (w, h) = buf.dimensions();
+    let (dx, dy) = (to[0] - from[0], to[1] - from[1]);
+    let steps = dx.abs().max(dy.abs()).ceil().max(1.0) as usize;
+    for k in 0 .. (steps + 1) {
+        let s = (k as Scalar) / (steps as Scalar);
+        let (x, y) = ((from[0] + s * dx).round(), (from[1] + s * dy).round());
+        if (x < 0.0) || (y < 0.0) || (x >= w as Scalar) || (y >= h as Scalar) { continue; }
+        let pos = PixelPos(x as u32, y as u32);
+        let blended = blend(buf.get_pixel(pos), color, opacity);
+        buf.set_pixel(pos, blended);
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// Arrows on a regular grid of points, `spacing` pixels apart.
+pub struct Glyphs {
+    pub spacing: usize,
+    /// The length in pixels of an arrow of unit magnitude, or nothing to make
+    /// the longest arrow as long as the spacing.
+    pub scale:   Option<Scalar>,
+    pub color:   RGB,
+}
+
+impl Glyphs {
+    pub fn new(spacing: usize) -> Self {
+        assert!(spacing > 0, "glyph spacing must be positive");
+        Glyphs { spacing: spacing, scale: None, color: RGB(255, 255, 255) }
+    }
+
+    pub fn draw<D: Drawable>(&self, field: &HostVectorField, buf: &mut D) {
+        let (w, h) = field.size;
+        let scale = self.scale.unwrap_or_else(|| {
+            (self.spacing as Scalar) / field.max_speed().max(std::f32::MIN_POSITIVE)
+        });
+        let half = self.spacing / 2;
+        for y in (half .. h).step_by(self.spacing) {
+            for x in (half .. w).step_by(self.spacing) {
+                let p = [x as Scalar, y as Scalar];
+                let (u, v) = match field.sample(p) {
+                    Some(uv) => uv,
+                    None => continue,
+                };
+                let (ax, ay) = (u * scale, v * scale);
+                let length = (ax * ax + ay * ay).sqrt();
+                if length < 1.0 { continue; }
+
+                // Centre the arrow on the point, with a head a third as long.
+                let tail = [p[0] - 0.5 * ax, p[1] - 0.5 * ay];
+                let tip = [p[0] + 0.5 * ax, p[1] + 0.5 * ay];
+                draw_line(buf, tail, tip, self.color, 1.0);
+                let head = length / 3.0;
+                let (ux, uy) = (ax / length, ay / length);
+                for &side in &[-1.0, 1.0] {
+                    // The barbs are the arrow direction turned back by 30 degrees.
+                    let (c, s) = (0.866, 0.5 * side);
+                    let barb = [tip[0] - head * (c * ux - s * uy),
+                                tip[1] - head * (s * ux + c * uy)];
+                    draw_line(buf, tip, barb, self.color, 1.0);
+                }
+            }
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// Lines tangent to the field through a set of seed points, traced both ways
+/// with fourth order Runge–Kutta steps of `step` pixels until they leave the
+/// lattice, reach a solid node or a stagnation point, or reach `length`.
+pub struct Streamlines {
+    pub seeds:  Shape,
+    pub step:   Scalar,
+    pub length: Scalar,
+    pub color:  RGB,
+}
+
+impl Streamlines {
+    pub fn new(seeds: Shape) -> Self {
+        Streamlines { seeds: seeds, step: 0.5, length: 400.0, color: RGB(255, 255, 255) }
+    }
+
+    /// The points of the streamline through a seed.
+    pub fn trace(&self, field: &HostVectorField, seed: [Scalar; 2]) -> Vec<[Scalar; 2]> {
+        let steps = (self.length / self.step).ceil() as usize;
+        let mut halves: Vec<Vec<[Scalar; 2]>> = Vec::with_capacity(2);
+        for &sign in &[1.0, -1.0] {
+            let h = sign * self.step;
+            let f = |p: [Scalar; 2]| field.direction(p);
+            let mut points = Vec::new();
+            let mut p = seed;
+            for _ in 0 .. steps {
+                let k1 = match f(p) { Some(k) => k, None => break };
+                let k2 = match f([p[0] + 0.5 * h * k1.0, p[1] + 0.5 * h * k1.1]) {
+                    Some(k) => k, None => break,
+                };
+                let k3 = match f([p[0] + 0.5 * h * k2.0, p[1] + 0.5 * h * k2.1]) {
+                    Some(k) => k, None => break,
+                };
+                let k4 = match f([p[0] + h * k3.0, p[1] + h * k3.1]) {
+                    Some(k) => k, None => break,
+                };
+                p = [p[0] + h * (k1.0 + 2.0 * k2.0 + 2.0 * k3.0 + k4.0) / 6.0,
+                     p[1] + h * (k1.1 + 2.0 * k2.1 + 2.0 * k3.1 + k4.1) / 6.0];
+                points.push(p);
+            }
+            halves.push(points);
+        }
+        let mut result: Vec<[Scalar; 2]> = halves[1].iter().rev().cloned().collect();
+        result.push(seed);
+        result.extend(&halves[0]);
+        result
+    }
+
+    pub fn draw<D: Drawable>(&self, field: &HostVectorField, buf: &mut D) {
+        for seed in self.seeds.points() {
+            let line = self.trace(field, seed);
+            for segment in line.windows(2) {
+                draw_line(buf, segment[0], segment[1], self.color, 0.8);
+            }
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// Line-integral convolution, after "Imaging vector fields using line
+/// integral convolution" by Cabral and Leedom: white noise averaged along
+/// the streamline through each pixel, computed on the device and blended
+/// over the drawable so that the background shows through.
+pub struct Lic {
+    /// The number of unit steps taken along the streamline each way.
+    pub length:   usize,
+    /// How strongly the texture modulates the brightness of the background.
+    pub opacity:  Scalar,
+    /// The standard deviation of the texture about mid-grey, before clamping.
+    pub contrast: Scalar,
+    noise:        af::Array<f32>,
+}
+
+impl Lic {
+    pub fn new(size: (usize, usize), length: usize) -> Self {
+        let (w, h) = size;
+        let engine = af::RandomEngine::new(af::DEFAULT_RANDOM_ENGINE, Some(0));
+        let dims = af::Dim4::new(&[h as u64, w as u64, 1, 1]);
+        Lic {
+            length:   length,
+            opacity:  0.6,
+            contrast: 0.2,
+            noise:    af::random_uniform::<f32>(dims, &engine),
+        }
+    }
+
+    /// The texture, with values in `[0, 1]`.
+    pub fn texture(&self, field: &(Matrix, Matrix)) -> Matrix {
+        let (vx, vy) = field;
+        let speed = (vx.hadamard(vx) + vy.hadamard(vy)).sqrt().shift(1.0e-12);
+        let ux = af::div(vx.get_array(), speed.get_array(), false);
+        let uy = af::div(vy.get_array(), speed.get_array(), false);
+
+        let dims = self.noise.dims();
+        let (max_y, max_x) = ((dims[0] - 1) as f32, (dims[1] - 1) as f32);
+        let mut total = self.noise.clone();
+        for &sign in &[1.0f32, -1.0] {
+            let mut xs = af::range::<f32>(dims, 1);
+            let mut ys = af::range::<f32>(dims, 0);
+            for _ in 0 .. self.length {
+                let dx = af::approx2(&ux, &ys, &xs, af::InterpType::LINEAR, 0.0);
+                let dy = af::approx2(&uy, &ys, &xs, af::InterpType::LINEAR, 0.0);
+                xs = af::clamp(&(&xs + &(&dx * sign)), &0.0f32, &max_x, true);
+                ys = af::clamp(&(&ys + &(&dy * sign)), &0.0f32, &max_y, true);
+                total = &total + &af::approx2(&self.noise, &ys, &xs,
+                                              af::InterpType::LINEAR, 0.0);
+            }
+        }
+        let average = Matrix::unsafe_new(&total / ((2 * self.length + 1) as f32));
+
+        // Averaging shrinks the variance of the noise, so restore it.
+        let mean = af::mean_all(average.get_array()).0 as Scalar;
+        let std = (af::stdev_all(average.get_array()).0 as Scalar).max(std::f32::MIN_POSITIVE);
+        average.shift(-mean).scale(self.contrast / std).shift(0.5).clamp(0.0, 1.0)
+    }
+
+    pub fn draw<D: Drawable>(&self, field: &(Matrix, Matrix), buf: &mut D) {
+        let texture = self.texture(field).get_underlying();
+        let mut pixels = buf.get_pixels();
+        for (pixel, &t) in pixels.chunks_mut(4).zip(&texture) {
+            // Twice the texture is 1 on average, so the mean brightness stays.
+            let factor = (1.0 - self.opacity) + self.opacity * 2.0 * t;
+            for channel in &mut pixel[0 .. 3] {
+                *channel = ((*channel as Scalar) * factor).round().min(255.0) as u8;
+            }
+        }
+        buf.set_pixels(&pixels);
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// Anything drawn over a rendered field.
+pub enum Overlay {
+    Glyphs(Glyphs),
+    Streamlines(Streamlines),
+    Lic(Lic),
+}
+
+impl Overlay {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Overlay::Glyphs(_)      => "glyphs",
+            Overlay::Streamlines(_) => "streamlines",
+            Overlay::Lic(_)         => "LIC",
+        }
+    }
+
+    /// The overlay of the given name with default settings for a lattice of
+    /// the given size: streamlines are seeded along the left edge.
+    pub fn from_name(name: &str, size: (usize, usize)) -> Option<Self> {
+        let (w, h) = size;
+        match name.to_lowercase().as_str() {
+            "glyphs" => Some(Overlay::Glyphs(Glyphs::new(16))),
+            "streamlines" => {
+                let seeds = Shape::Line {
+                    start:   [2.0, 2.0],
+                    end:     [2.0, (h - 3) as Scalar],
+                    samples: 24,
+                };
+                Some(Overlay::Streamlines(Streamlines::new(seeds)))
+            },
+            "lic" => Some(Overlay::Lic(Lic::new((w, h), 10))),
+            _ => None,
+        }
+    }
+}
+
+/// Draw the overlays over the drawable in order, e.g. after the background
+/// field and before `render_geometry`, which then masks them.
+pub fn draw_overlays<D: Drawable>(
+    overlays: &[Overlay],
+    field:    &(Matrix, Matrix),
+    geometry: &Geometry,
+    buf:      &mut D,
+) {
+    if overlays.is_empty() { return; }
+    let needs_host = overlays.iter().any(|o| match *o {
+        Overlay::Lic(_) => false,
+        _ => true,
+    });
+    let host = if needs_host { Some(HostVectorField::new(field, geometry)) } else { None };
+    for overlay in overlays {
+        match *overlay {
+            Overlay::Glyphs(ref g)      => g.draw(host.as_ref().unwrap(), buf),
+            Overlay::Streamlines(ref s) => s.draw(host.as_ref().unwrap(), buf),
+            Overlay::Lic(ref l)         => l.draw(field, buf),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
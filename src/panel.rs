@@ -0,0 +1,362 @@
+// -----------------------------------------------------------------------------
+
+extern crate ttf_noto_sans;
+
+use std;
+use conrod;
+use conrod_piston;
+use graphics;
+use opengl_graphics::{self, GlGraphics, Texture, TextureSettings};
+use piston::input::Event;
+use super::lbm::{OperatorKind, Scalar};
+
+// -----------------------------------------------------------------------------
+
+/// The width in pixels of the panel, to the right of the simulation.
+pub const PANEL_WIDTH: u32 = 240;
+
+const MARGIN: conrod::Scalar = 10.0;
+const ROW: conrod::Scalar = 24.0;
+const GAP: conrod::Scalar = 6.0;
+
+/// What the panel shows: the settings a simulation lets the user change,
+/// and readouts of its progress.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Controls {
+    pub viscosity:      Scalar,
+    pub inlet_velocity: Scalar,
+    pub operator:       OperatorKind,
+    pub display_modes:  Vec<String>,
+    pub display_mode:   usize,
+    pub paused:         bool,
+    pub time:           Scalar,
+    /// Millions of lattice node updates per second over the last frame.
+    pub mlups:          Scalar,
+    pub max_mach:       Scalar,
+    pub drag:           Option<Scalar>,
+}
+
+/// What the user asked for through the panel.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Command {
+    SetViscosity(Scalar),
+    SetInletVelocity(Scalar),
+    SetOperator(OperatorKind),
+    SetDisplayMode(usize),
+    Pause(bool),
+    /// Take a single step while paused.
+    Step,
+    Reset,
+}
+
+/// A time series to plot over part of the simulation, e.g. a tile of a
+/// `layout::Layout`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Plot {
+    pub title:  String,
+    /// The left, top, width and height in pixels.
+    pub rect:   [f64; 4],
+    /// `(time, value)`, sorted by time.
+    pub points: Vec<(Scalar, Scalar)>,
+}
+
+impl Plot {
+    /// The value at the given time, interpolated linearly between points.
+    pub fn value_at(&self, time: f64) -> f64 {
+        let after = self.points.iter().position(|&(t, _)| (t as f64) >= time);
+        match after {
+            None => self.points.last().map(|&(_, v)| v as f64).unwrap_or(0.0),
+            Some(0) => self.points[0].1 as f64,
+            Some(k) => {
+                let ((t0, v0), (t1, v1)) = (self.points[k - 1], self.points[k]);
+                let s = (time - t0 as f64) / ((t1 - t0) as f64);
+                v0 as f64 + s * (v1 - v0) as f64
+            },
+        }
+    }
+}
+
+widget_ids! {
+    pub struct Ids {
+        view,
+        plot_backgrounds[],
+        plot_paths[],
+        plot_titles[],
+        canvas,
+        viscosity_label,
+        viscosity,
+        velocity_label,
+        velocity,
+        operator_label,
+        operator,
+        display_label,
+        display,
+        pause,
+        step,
+        reset,
+        readouts,
+    }
+}
+
+/// Lay out the panel and collect the commands given through it.
+pub fn gui(ui: &mut conrod::UiCell, ids: &Ids, controls: &Controls) -> Vec<Command> {
+    use conrod::{widget, Labelable, Positionable, Sizeable, Widget};
+
+    let mut commands = Vec::new();
+    let width = (PANEL_WIDTH as conrod::Scalar) - 2.0 * MARGIN;
+    let label = |text: &str, id: conrod::widget::Id, ui: &mut conrod::UiCell| {
+        widget::Text::new(text).font_size(12).down(2.0 * GAP).set(id, ui);
+    };
+
+    widget::Canvas::new()
+        .w_h(PANEL_WIDTH as conrod::Scalar, ui.win_h)
+        .top_right_of(ui.window)
+        .pad(MARGIN)
+        .scroll_kids_vertically()
+        .set(ids.canvas, ui);
+
+    // The viscosity spans decades, so its slider is logarithmic.
+    widget::Text::new("Viscosity")
+        .font_size(12)
+        .top_left_of(ids.canvas)
+        .set(ids.viscosity_label, ui);
+    let viscosity = widget::Slider::new(controls.viscosity.log10(), -4.0, 2.0)
+        .w_h(width, ROW)
+        .down(GAP)
+        .label(&format!("{:.3e}", controls.viscosity))
+        .label_font_size(12)
+        .set(ids.viscosity, ui);
+    if let Some(v) = viscosity {
+        commands.push(Command::SetViscosity((10.0 as Scalar).powf(v)));
+    }
+
+    label("Inlet velocity", ids.velocity_label, ui);
+    let velocity = widget::Slider::new(controls.inlet_velocity, 0.0, 0.2)
+        .w_h(width, ROW)
+        .down(GAP)
+        .label(&format!("{:.4}", controls.inlet_velocity))
+        .label_font_size(12)
+        .set(ids.velocity, ui);
+    if let Some(u) = velocity {
+        commands.push(Command::SetInletVelocity(u));
+    }
+
+    label("Collision operator", ids.operator_label, ui);
+    let operators = OperatorKind::all();
+    let names: Vec<&str> = operators.iter().map(|o| o.name()).collect();
+    let selected = operators.iter().position(|o| *o == controls.operator);
+    let operator = widget::DropDownList::new(&names, selected)
+        .w_h(width, ROW)
+        .down(GAP)
+        .label_font_size(12)
+        .set(ids.operator, ui);
+    if let Some(i) = operator {
+        commands.push(Command::SetOperator(operators[i]));
+    }
+
+    label("Display", ids.display_label, ui);
+    let display = widget::DropDownList::new(&controls.display_modes,
+                                            Some(controls.display_mode))
+        .w_h(width, ROW)
+        .down(GAP)
+        .label_font_size(12)
+        .set(ids.display, ui);
+    if let Some(i) = display {
+        commands.push(Command::SetDisplayMode(i));
+    }
+
+    let button = (width - 2.0 * GAP) / 3.0;
+    let pause = if controls.paused { "Resume" } else { "Pause" };
+    for _ in widget::Button::new()
+        .label(pause)
+        .label_font_size(12)
+        .w_h(button, ROW)
+        .down(3.0 * GAP)
+        .set(ids.pause, ui) {
+            commands.push(Command::Pause(!controls.paused));
+        }
+    for _ in widget::Button::new()
+        .label("Step")
+        .label_font_size(12)
+        .w_h(button, ROW)
+        .right(GAP)
+        .set(ids.step, ui) {
+            commands.push(Command::Step);
+        }
+    for _ in widget::Button::new()
+        .label("Reset")
+        .label_font_size(12)
+        .w_h(button, ROW)
+        .right(GAP)
+        .set(ids.reset, ui) {
+            commands.push(Command::Reset);
+        }
+
+    let drag = match controls.drag {
+        Some(d) => format!("{:.4e}", d),
+        None => "-".to_string(),
+    };
+    let readouts = format!("Time: {:.4}\nMLUPS: {:.1}\nMax Mach: {:.4}\nDrag: {}",
+                           controls.time, controls.mlups, controls.max_mach, drag);
+    widget::Text::new(&readouts)
+        .font_size(12)
+        .w(width)
+        .down_from(ids.pause, 3.0 * GAP)
+        .set(ids.readouts, ui);
+
+    commands
+}
+
+/// Plot each series as a line over its rectangle, in window pixels, within
+/// the part of the window left of the panel.
+pub fn plots(ui: &mut conrod::UiCell, ids: &Ids, plots: &[Plot]) {
+    use conrod::{color, widget, Colorable, Positionable, Sizeable, Widget};
+
+    widget::Canvas::new()
+        .w_h(ui.win_w - PANEL_WIDTH as conrod::Scalar, ui.win_h)
+        .top_left_of(ui.window)
+        .color(color::TRANSPARENT)
+        .border(0.0)
+        .crop_kids()
+        .set(ids.view, ui);
+
+    for (k, plot) in plots.iter().enumerate() {
+        let [left, top, w, h] = plot.rect;
+        // Conrod measures from the middle of the window, upwards.
+        let x = left + w / 2.0 - ui.win_w / 2.0;
+        let y = ui.win_h / 2.0 - (top + h / 2.0);
+        widget::Rectangle::fill([w, h])
+            .x_y(x, y)
+            .color(color::DARK_CHARCOAL)
+            .parent(ids.view)
+            .set(ids.plot_backgrounds[k], ui);
+
+        let title = match plot.points.last() {
+            Some(&(_, v)) => format!("{}: {:.4e}", plot.title, v),
+            None => plot.title.clone(),
+        };
+        widget::Text::new(&title)
+            .font_size(12)
+            .top_left_with_margin_on(ids.plot_backgrounds[k], MARGIN)
+            .color(color::WHITE)
+            .set(ids.plot_titles[k], ui);
+
+        if plot.points.len() < 2 { continue; }
+        let (t0, t1) = (plot.points[0].0 as f64, plot.points[plot.points.len() - 1].0 as f64);
+        let values = plot.points.iter().map(|&(_, v)| v as f64);
+        let (mut min, mut max) = values.fold((std::f64::MAX, std::f64::MIN),
+                                             |(a, b), v| (a.min(v), b.max(v)));
+        if max <= min { min -= 0.5; max += 0.5; }
+        let margin = 0.05 * (max - min);
+        widget::PlotPath::new(t0, t1, min - margin, max + margin, |t| plot.value_at(t))
+            .w_h(w - 2.0 * MARGIN, h - 2.0 * MARGIN - ROW)
+            .mid_bottom_with_margin_on(ids.plot_backgrounds[k], MARGIN)
+            .color(color::LIGHT_BLUE)
+            .thickness(1.5)
+            .set(ids.plot_paths[k], ui);
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// The conrod state behind a control panel drawn along the right edge of a
+/// window.
+pub struct Panel {
+    ui:                 conrod::Ui,
+    ids:                Ids,
+    glyph_cache:        conrod::text::GlyphCache<'static>,
+    text_texture_cache: Texture,
+    text_vertex_data:   Vec<u8>,
+    image_map:          conrod::image::Map<Texture>,
+}
+
+impl Panel {
+    /// A panel for a window of the given size, panel included.
+    pub fn new(window: (u32, u32)) -> Self {
+        let (w, h) = window;
+        let mut ui = conrod::UiBuilder::new([w as f64, h as f64])
+            .theme(super::theme::theme())
+            .build();
+
+        use conrod::text::FontCollection;
+        ui.fonts.insert(FontCollection::from_bytes(ttf_noto_sans::REGULAR)
+                        .expect("failed to FontCollection::from_bytes")
+                        .into_font()
+                        .expect("failed to into_font"));
+
+        // A texture to cache text on the GPU.
+        let glyph_cache = conrod::text::GlyphCache::builder()
+            .dimensions(w, h)
+            .scale_tolerance(0.1)
+            .position_tolerance(0.1)
+            .build();
+        let init = vec![128; (w * h) as usize];
+        let text_texture_cache
+            = Texture::from_memory_alpha(&init, w, h, &TextureSettings::new()).unwrap();
+
+        let ids = Ids::new(ui.widget_id_generator());
+        Panel {
+            ui:                 ui,
+            ids:                ids,
+            glyph_cache:        glyph_cache,
+            text_texture_cache: text_texture_cache,
+            text_vertex_data:   Vec::new(),
+            image_map:          conrod::image::Map::new(),
+        }
+    }
+
+    pub fn handle(&mut self, event: &Event, window: (f64, f64)) {
+        if let Some(e) = conrod_piston::event::convert(event.clone(), window.0, window.1) {
+            self.ui.handle_event(e);
+        }
+    }
+
+    /// Lay out the panel for the current controls and the plots, and return
+    /// the commands the user gave since the last update.
+    pub fn update(&mut self, controls: &Controls, series: &[Plot]) -> Vec<Command> {
+        {
+            let mut generator = self.ui.widget_id_generator();
+            self.ids.plot_backgrounds.resize(series.len(), &mut generator);
+            self.ids.plot_paths.resize(series.len(), &mut generator);
+            self.ids.plot_titles.resize(series.len(), &mut generator);
+        }
+        let mut ui = self.ui.set_widgets();
+        plots(&mut ui, &self.ids, series);
+        gui(&mut ui, &self.ids, controls)
+    }
+
+    pub fn draw(&mut self, context: graphics::Context, gl: &mut GlGraphics) {
+        use conrod::text::rt::Rect;
+
+        let primitives = self.ui.draw();
+        let text_vertex_data = &mut self.text_vertex_data;
+        let cache_queued_glyphs
+            = |graphics: &mut GlGraphics, cache: &mut Texture, rect: Rect<u32>, data: &[u8]| {
+                text_vertex_data.clear();
+                text_vertex_data.extend(data.iter().flat_map(|&b| vec![255, 255, 255, b]));
+                opengl_graphics::UpdateTexture::update(
+                    cache, &mut (),
+                    opengl_graphics::Format::Rgba8,
+                    &text_vertex_data[..],
+                    [rect.min.x, rect.min.y],
+                    [rect.width(), rect.height()],
+                ).expect("failed to update texture")
+            };
+
+        // The images are the textures themselves.
+        fn texture_from_image<T>(img: &T) -> &T { img }
+
+        conrod_piston::draw::primitives(
+            primitives,
+            context,
+            gl,
+            &mut self.text_texture_cache,
+            &mut self.glyph_cache,
+            &self.image_map,
+            cache_queued_glyphs,
+            texture_from_image,
+        );
+    }
+}
+
+// -----------------------------------------------------------------------------
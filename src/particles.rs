@@ -0,0 +1,249 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use arrayfire as af;
+use super::lbm::{Lattice, Geometry, Matrix, Scalar, State};
+
+// -----------------------------------------------------------------------------
+
+/// A rigid circular particle.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Particle {
+    /// The position of the center, in lattice node coordinates.
+    pub position:         [Scalar; 2],
+    /// The velocity of the center, in physical units.
+    pub velocity:         [Scalar; 2],
+    pub angle:            Scalar,
+    pub angular_velocity: Scalar,
+    /// The radius, in lattice nodes.
+    pub radius:           Scalar,
+    /// The mass per unit area of the particle.
+    pub density:          Scalar,
+}
+
+impl Particle {
+    pub fn new(position: [Scalar; 2], radius: Scalar, density: Scalar) -> Self {
+        Particle {
+            position:         position,
+            velocity:         [0.0, 0.0],
+            angle:            0.0,
+            angular_velocity: 0.0,
+            radius:           radius,
+            density:          density,
+        }
+    }
+
+    pub fn area(&self, delta_x: Scalar) -> Scalar {
+        let r = self.radius * delta_x;
+        std::f32::consts::PI * r * r
+    }
+
+    pub fn mass(&self, delta_x: Scalar) -> Scalar {
+        self.density * self.area(delta_x)
+    }
+
+    pub fn moment_of_inertia(&self, delta_x: Scalar) -> Scalar {
+        let r = self.radius * delta_x;
+        0.5 * self.mass(delta_x) * r * r
+    }
+
+    /// The nodes covered by the particle.
+    pub fn footprint(&self, xs: &Matrix, ys: &Matrix) -> Geometry {
+        self.neighbourhood(xs, ys, 0.0)
+    }
+
+    /// The nodes within the given distance (in lattice nodes) of the particle.
+    pub fn neighbourhood(&self, xs: &Matrix, ys: &Matrix, margin: Scalar) -> Geometry {
+        let dx = xs.shift(-self.position[0]);
+        let dy = ys.shift(-self.position[1]);
+        let r2 = dx.hadamard(&dx) + dy.hadamard(&dy);
+        let r = self.radius + margin;
+        af::le(r2.get_array(), &(r * r), false)
+    }
+
+    /// The rigid-body velocity of the particle, evaluated everywhere, in
+    /// physical units.
+    pub fn surface_velocity(&self, xs: &Matrix, ys: &Matrix, delta_x: Scalar)
+                            -> (Matrix, Matrix) {
+        let omega = self.angular_velocity * delta_x;
+        let rx = xs.shift(-self.position[0]);
+        let ry = ys.shift(-self.position[1]);
+        (ry.scale(-omega).shift(self.velocity[0]),
+         rx.scale(omega).shift(self.velocity[1]))
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// The short-range repulsion of "A fictitious domain approach to the direct
+/// numerical simulation of incompressible viscous flow past moving rigid
+/// bodies" by Glowinski et al., which keeps particles from overlapping each
+/// other or the edges of the domain.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SoftCollision {
+    /// The gap (in lattice nodes) below which the repulsion acts.
+    pub range:   Scalar,
+    /// The inverse of the stiffness of the repulsion.
+    pub epsilon: Scalar,
+}
+
+impl SoftCollision {
+    fn force(&self, gap: Scalar, direction: [Scalar; 2], scale: Scalar) -> [Scalar; 2] {
+        if gap >= self.range { return [0.0, 0.0]; }
+        let magnitude = scale * (self.range - gap).powi(2) / self.epsilon;
+        [magnitude * direction[0], magnitude * direction[1]]
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// Rigid particles suspended in a lattice, with two-way coupling.
+///
+/// Each step the particles feel the hydrodynamic force and torque measured by
+/// momentum exchange, gravity (corrected for buoyancy) and the soft collision
+/// forces, and are then moved explicitly. Their footprints are written into
+/// the geometry of the lattice as moving walls, and the nodes they uncover are
+/// refilled with the equilibrium at the particle surface velocity.
+///
+/// The explicit coupling is unstable for particles much lighter than the
+/// fluid.
+pub struct Suspension {
+    pub particles:     Vec<Particle>,
+    /// The gravitational acceleration, in physical units.
+    pub gravity:       [Scalar; 2],
+    pub collision:     SoftCollision,
+    /// The density of the fluid, for the buoyancy correction.
+    pub fluid_density: Scalar,
+    walls:             Geometry,
+    xs:                Matrix,
+    ys:                Matrix,
+}
+
+impl Suspension {
+    /// Create a suspension in the given lattice, whose current geometry is
+    /// taken to be the static walls.
+    pub fn new<L: Lattice>(
+        state:     &mut State<L>,
+        particles: Vec<Particle>,
+        gravity:   [Scalar; 2],
+        collision: SoftCollision,
+    ) -> Self {
+        let dims = state.geometry.dims();
+        let mut result = Suspension {
+            particles:     particles,
+            gravity:       gravity,
+            collision:     collision,
+            fluid_density: 1.0,
+            walls:         state.geometry.clone(),
+            xs:            Matrix::unsafe_new(af::range::<f32>(dims, 1)),
+            ys:            Matrix::unsafe_new(af::range::<f32>(dims, 0)),
+        };
+        result.update_geometry(state);
+        result
+    }
+
+    /// Advance the lattice and the particles by one time step.
+    pub fn step<L: Lattice>(&mut self, state: &mut State<L>) {
+        let dt = state.delta_t();
+        let dx = state.delta_x();
+
+        let loads: Vec<([Scalar; 2], Scalar)> = self.particles.iter().map(|p| {
+            let mask = p.footprint(&self.xs, &self.ys);
+            let (force, torque) = state.momentum_exchange(&mask, (p.position[0],
+                                                                  p.position[1]));
+            let (fx, fy) = force.to_pair();
+            ([fx, fy], torque)
+        }).collect();
+
+        let contacts = self.contact_forces(state.size(), dx);
+
+        for ((p, (force, torque)), contact) in self.particles.iter_mut().zip(loads).zip(contacts) {
+            let mass = p.mass(dx);
+            let buoyancy = (p.density - self.fluid_density) * p.area(dx);
+            for d in 0 .. 2 {
+                let total = force[d] + contact[d] + buoyancy * self.gravity[d];
+                p.velocity[d] += total * dt / mass;
+                p.position[d] += p.velocity[d] * dt / dx;
+            }
+            p.angular_velocity += torque * dt / p.moment_of_inertia(dx);
+            p.angle += p.angular_velocity * dt;
+        }
+
+        self.update_geometry(state);
+        state.step();
+    }
+
+    /// Write the particle footprints and surface velocities into the lattice,
+    /// refilling the nodes that have been uncovered since the last update.
+    /// Both take velocities in lattice units, as `State::velocity`.
+    fn update_geometry<L: Lattice>(&self, state: &mut State<L>) {
+        let dx = state.delta_x();
+        let to_lattice = state.delta_t() / dx;
+        let size = state.size();
+
+        // A particle moves less than a node per step, so the nodes it
+        // uncovers are within a node of its new footprint.
+        let mut geometry = self.walls.clone();
+        let mut ux = Matrix::new_filled(0.0, size);
+        let mut uy = Matrix::new_filled(0.0, size);
+        let mut refill_ux = Matrix::new_filled(0.0, size);
+        let mut refill_uy = Matrix::new_filled(0.0, size);
+        for p in &self.particles {
+            let mask = p.footprint(&self.xs, &self.ys);
+            let near = p.neighbourhood(&self.xs, &self.ys, 1.5);
+            let (px, py) = p.surface_velocity(&self.xs, &self.ys, dx);
+            let (px, py) = (px.scale(to_lattice), py.scale(to_lattice));
+            let keep = af::eq(&mask, &false, false);
+            af::replace(ux.get_array_mut(), &keep, px.get_array());
+            af::replace(uy.get_array_mut(), &keep, py.get_array());
+            let keep = af::eq(&near, &false, false);
+            af::replace(refill_ux.get_array_mut(), &keep, px.get_array());
+            af::replace(refill_uy.get_array_mut(), &keep, py.get_array());
+            geometry = af::or(&geometry, &mask, false);
+        }
+
+        let uncovered = af::and(&state.geometry,
+                                &af::eq(&geometry, &false, false),
+                                false);
+        let density = Matrix::new_filled(self.fluid_density, size);
+        state.refill(&uncovered, &density, &(refill_ux, refill_uy));
+
+        state.geometry = geometry;
+        state.wall_velocity = Some((ux, uy));
+    }
+
+    fn contact_forces(&self, size: (usize, usize), delta_x: Scalar) -> Vec<[Scalar; 2]> {
+        let (w, h) = (size.0 as Scalar, size.1 as Scalar);
+        let scale = delta_x;
+        let mut forces = vec![[0.0, 0.0]; self.particles.len()];
+        for (i, a) in self.particles.iter().enumerate() {
+            for (j, b) in self.particles.iter().enumerate().skip(i + 1) {
+                let (dx, dy) = (a.position[0] - b.position[0],
+                                a.position[1] - b.position[1]);
+                let distance = (dx * dx + dy * dy).sqrt().max(std::f32::EPSILON);
+                let gap = distance - a.radius - b.radius;
+                let f = self.collision.force(
+                    gap, [dx / distance, dy / distance], scale);
+                forces[i][0] += f[0];
+                forces[i][1] += f[1];
+                forces[j][0] -= f[0];
+                forces[j][1] -= f[1];
+            }
+
+            let walls = [
+                (a.position[0] - a.radius,           [ 1.0,  0.0]),
+                (w - 1.0 - a.position[0] - a.radius, [-1.0,  0.0]),
+                (a.position[1] - a.radius,           [ 0.0,  1.0]),
+                (h - 1.0 - a.position[1] - a.radius, [ 0.0, -1.0]),
+            ];
+            for &(gap, direction) in &walls {
+                let f = self.collision.force(gap, direction, scale);
+                forces[i][0] += f[0];
+                forces[i][1] += f[1];
+            }
+        }
+        forces
+    }
+}
+
+// -----------------------------------------------------------------------------
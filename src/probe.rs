@@ -0,0 +1,324 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use std::io::Write;
+use arrayfire as af;
+use serde_json;
+use super::lbm::{self, Lattice, Matrix, Scalar, State};
+
+// -----------------------------------------------------------------------------
+
+/// A quantity that can be sampled over the lattice.
+pub enum Field {
+    Density,
+    /// The isothermal pressure `cs^2 rho`.
+    Pressure,
+    VelocityX,
+    VelocityY,
+    Speed,
+    Vorticity,
+    /// A named field computed from the density and velocity.
+    Derived(String, Box<Fn(&Matrix, &(Matrix, Matrix)) -> Matrix>),
+}
+
+impl Field {
+    pub fn name(&self) -> &str {
+        match *self {
+            Field::Density           => "density",
+            Field::Pressure          => "pressure",
+            Field::VelocityX         => "velocity_x",
+            Field::VelocityY         => "velocity_y",
+            Field::Speed             => "speed",
+            Field::Vorticity         => "vorticity",
+            Field::Derived(ref n, _) => n.as_str(),
+        }
+    }
+
+    pub fn evaluate<L: Lattice>(&self, state: &State<L>) -> Matrix {
+        match *self {
+            Field::Density   => state.density(),
+            Field::Pressure  => {
+                let cs = state.isothermal_speed_of_sound();
+                state.density().scale(cs * cs)
+            },
+            Field::VelocityX => state.velocity().0,
+            Field::VelocityY => state.velocity().1,
+            Field::Speed     => state.speed(),
+            Field::Vorticity => vorticity(&state.velocity(), state.delta_x()),
+            Field::Derived(_, ref f) => f(&state.density(), &state.velocity()),
+        }
+    }
+}
+
+/// The vorticity `d(uy)/dx - d(ux)/dy`, by central differences.
+pub fn vorticity(velocity: &(Matrix, Matrix), delta_x: Scalar) -> Matrix {
+    let (ux, uy) = velocity;
+    let at = |m: &Matrix, x: Scalar, y: Scalar| {
+        // The value at the node offset by (x, y) from each node.
+        Matrix::unsafe_new(lbm::translate(m.get_array(), lbm::Vector::new(-x, -y)))
+    };
+    let duy_dx = at(uy, 1.0, 0.0) - at(uy, -1.0, 0.0);
+    let dux_dy = at(ux, 0.0, 1.0) - at(ux, 0.0, -1.0);
+    (duy_dx - dux_dy).scale(1.0 / (2.0 * delta_x))
+}
+
+// -----------------------------------------------------------------------------
+
+/// Where a probe samples, in lattice node coordinates.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Shape {
+    Point([Scalar; 2]),
+    /// Evenly spaced points from `start` to `end`, inclusive.
+    Line { start: [Scalar; 2], end: [Scalar; 2], samples: usize },
+    /// A grid of evenly spaced points covering the rectangle.
+    Rectangle { origin: [Scalar; 2], extent: [Scalar; 2], samples: (usize, usize) },
+}
+
+impl Shape {
+    pub fn points(&self) -> Vec<[Scalar; 2]> {
+        let lerp = |a: Scalar, b: Scalar, k: usize, n: usize| {
+            if n < 2 { a } else { a + (b - a) * (k as Scalar) / ((n - 1) as Scalar) }
+        };
+        match *self {
+            Shape::Point(p) => vec![p],
+            Shape::Line { start, end, samples } => {
+                (0 .. samples).map(|k| {
+                    [lerp(start[0], end[0], k, samples), lerp(start[1], end[1], k, samples)]
+                }).collect()
+            },
+            Shape::Rectangle { origin, extent, samples } => {
+                let (nx, ny) = samples;
+                let mut result = Vec::with_capacity(nx * ny);
+                for j in 0 .. ny {
+                    for i in 0 .. nx {
+                        result.push([lerp(origin[0], origin[0] + extent[0], i, nx),
+                                     lerp(origin[1], origin[1] + extent[1], j, ny)]);
+                    }
+                }
+                result
+            },
+        }
+    }
+}
+
+/// Bilinear interpolation of a field at the given points, which must lie
+/// within the lattice.
+pub fn sample(field: &Matrix, points: &[[Scalar; 2]]) -> Vec<Scalar> {
+    let n = points.len() as u64;
+    let xs: Vec<Scalar> = points.iter().map(|p| p[0]).collect();
+    let ys: Vec<Scalar> = points.iter().map(|p| p[1]).collect();
+    let dims = af::Dim4::new(&[n, 1, 1, 1]);
+    let values = af::approx2(field.get_array(),
+                             &af::Array::new(&ys, dims),
+                             &af::Array::new(&xs, dims),
+                             af::InterpType::BILINEAR,
+                             0.0);
+    let mut result = vec![0.0; points.len()];
+    values.host(&mut result);
+    result
+}
+
+// -----------------------------------------------------------------------------
+
+/// A named set of points at which some fields are sampled.
+pub struct Probe {
+    pub name:   String,
+    pub shape:  Shape,
+    pub fields: Vec<Field>,
+}
+
+impl Probe {
+    pub fn new(name: &str, shape: Shape, fields: Vec<Field>) -> Self {
+        Probe { name: name.to_string(), shape: shape, fields: fields }
+    }
+
+    /// The name of each column, in the order the samples come in: every
+    /// point of the first field, then every point of the next one, etc.
+    pub fn columns(&self) -> Vec<String> {
+        let count = self.shape.points().len();
+        let mut result = Vec::with_capacity(count * self.fields.len());
+        for field in &self.fields {
+            if count == 1 {
+                result.push(field.name().to_string());
+            } else {
+                for k in 0 .. count { result.push(format!("{}[{}]", field.name(), k)); }
+            }
+        }
+        result
+    }
+
+    pub fn sample<L: Lattice>(&self, state: &State<L>) -> Vec<Scalar> {
+        let points = self.shape.points();
+        let mut result = Vec::new();
+        for field in &self.fields {
+            result.extend(sample(&field.evaluate(state), &points));
+        }
+        result
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Format {
+    /// One `<probe>.csv` file per probe, with a row per sample.
+    Csv,
+    /// One `<probe>/` directory per probe, holding a little-endian `f32` file
+    /// per field (a row of every point of the probe per sample) and a
+    /// `header.json` describing them.
+    Columnar,
+}
+
+enum Writer {
+    Csv(std::io::BufWriter<std::fs::File>),
+    Columnar(Vec<std::io::BufWriter<std::fs::File>>),
+}
+
+/// Samples a set of probes every `interval` steps, keeping the time series
+/// in memory and writing them out as they come.
+pub struct ProbeSet {
+    pub probes:   Vec<Probe>,
+    pub interval: usize,
+    pub times:    Vec<Scalar>,
+    /// The samples of each probe, one row per sample time.
+    pub samples:  Vec<Vec<Vec<Scalar>>>,
+    directory:    std::path::PathBuf,
+    format:       Format,
+    writers:      Vec<Writer>,
+    calls:        usize,
+}
+
+impl ProbeSet {
+    pub fn new<P: AsRef<std::path::Path>>(
+        directory: P,
+        format:    Format,
+        probes:    Vec<Probe>,
+        interval:  usize,
+    ) -> std::io::Result<Self> {
+        assert!(interval > 0, "probe interval must be positive");
+        let directory = directory.as_ref().to_path_buf();
+        std::fs::create_dir_all(&directory)?;
+
+        let mut writers = Vec::with_capacity(probes.len());
+        for probe in &probes {
+            writers.push(match format {
+                Format::Csv => {
+                    let path = directory.join(format!("{}.csv", probe.name));
+                    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                    writeln!(file, "time,{}", probe.columns().join(","))?;
+                    Writer::Csv(file)
+                },
+                Format::Columnar => {
+                    let path = directory.join(&probe.name);
+                    std::fs::create_dir_all(&path)?;
+                    let mut files = Vec::with_capacity(probe.fields.len() + 1);
+                    files.push(std::io::BufWriter::new(
+                        std::fs::File::create(path.join("time.f32"))?));
+                    for field in &probe.fields {
+                        files.push(std::io::BufWriter::new(std::fs::File::create(
+                            path.join(format!("{}.f32", field.name())))?));
+                    }
+                    Writer::Columnar(files)
+                },
+            });
+        }
+
+        let count = probes.len();
+        let mut result = ProbeSet {
+            probes:    probes,
+            interval:  interval,
+            times:     Vec::new(),
+            samples:   vec![Vec::new(); count],
+            directory: directory,
+            format:    format,
+            writers:   writers,
+            calls:     0,
+        };
+        result.write_headers()?;
+        Ok(result)
+    }
+
+    /// Call once per step; samples every probe every `interval` calls.
+    pub fn record<L: Lattice>(&mut self, state: &State<L>) -> std::io::Result<()> {
+        let due = self.calls % self.interval == 0;
+        self.calls += 1;
+        if !due { return Ok(()); }
+
+        self.times.push(state.time);
+        for ((probe, writer), samples) in self.probes.iter()
+            .zip(&mut self.writers)
+            .zip(&mut self.samples) {
+                let row = probe.sample(state);
+                match *writer {
+                    Writer::Csv(ref mut file) => {
+                        let values: Vec<String>
+                            = row.iter().map(|v| v.to_string()).collect();
+                        writeln!(file, "{},{}", state.time, values.join(","))?;
+                    },
+                    Writer::Columnar(ref mut files) => {
+                        files[0].write_all(&state.time.to_bits().to_le_bytes())?;
+                        let width = row.len() / probe.fields.len();
+                        for (file, chunk) in files[1 ..].iter_mut().zip(row.chunks(width)) {
+                            for v in chunk { file.write_all(&v.to_bits().to_le_bytes())?; }
+                        }
+                    },
+                }
+                samples.push(row);
+            }
+        Ok(())
+    }
+
+    /// The time series of one column of one probe.
+    pub fn series(&self, probe: &str, column: &str) -> Option<Vec<Scalar>> {
+        let p = self.probes.iter().position(|p| p.name == probe)?;
+        let c = self.probes[p].columns().iter().position(|c| c == column)?;
+        Some(self.samples[p].iter().map(|row| row[c]).collect())
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        for writer in &mut self.writers {
+            match *writer {
+                Writer::Csv(ref mut file) => file.flush()?,
+                Writer::Columnar(ref mut files) => {
+                    for file in files { file.flush()?; }
+                },
+            }
+        }
+        self.write_headers()
+    }
+
+    fn write_headers(&self) -> std::io::Result<()> {
+        if self.format != Format::Columnar { return Ok(()); }
+        for probe in &self.probes {
+            let points = probe.shape.points();
+            let fields: Vec<serde_json::Value> = probe.fields.iter().map(|f| json!({
+                "name":  f.name(),
+                "file":  format!("{}.f32", f.name()),
+                "width": points.len(),
+            })).collect();
+            let header = json!({
+                "probe":    probe.name,
+                "dtype":    "f32le",
+                "rows":     self.times.len(),
+                "interval": self.interval,
+                "points":   points.iter().map(|p| vec![p[0], p[1]]).collect::<Vec<_>>(),
+                "time":     "time.f32",
+                "fields":   fields,
+            });
+            let path = self.directory.join(&probe.name).join("header.json");
+            let file = std::fs::File::create(path)?;
+            serde_json::to_writer_pretty(file, &header)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ProbeSet {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            println!("Failed to flush probes: {}", e);
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
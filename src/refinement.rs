@@ -0,0 +1,202 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use arrayfire as af;
+use super::lbm::{Lattice, CollisionOperator, Discretization, Geometry};
+use super::lbm::{State, Populations, Scalar};
+
+// -----------------------------------------------------------------------------
+
+/// The ratio between the lattice spacing of a block and that of its parent.
+pub const REFINEMENT_RATIO: usize = 2;
+
+// -----------------------------------------------------------------------------
+
+/// The dimensionless relaxation time that the given collision operator
+/// corresponds to on the given discretization.
+pub fn relaxation_time<L: Lattice>(
+    collision:      &CollisionOperator<L>,
+    discretization: &Discretization,
+) -> Scalar {
+    let cs = discretization.speed_of_sound(L::velocity_set().cs2);
+    let nu = collision.kinematic_shear_viscosity(discretization);
+    (nu / (cs * cs * discretization.delta_t)) + 0.5
+}
+
+// -----------------------------------------------------------------------------
+
+/// A lattice block with twice the resolution of its parent, nested inside it.
+///
+/// The coupling follows "Theory and applications of an alternative lattice
+/// Boltzmann grid refinement algorithm" by Dupuis and Chopard: populations
+/// crossing the interface keep their equilibrium part and have their
+/// non-equilibrium part rescaled by the ratio of relaxation times. The block
+/// takes `REFINEMENT_RATIO` steps per parent step, and the parent values on
+/// its boundary are interpolated linearly in time between the two.
+///
+/// Populations are exchanged before collision, when the rescaling is
+/// `tau_f / (2 tau_c)` and holds for any relaxation times.
+pub struct RefinedBlock<L> {
+    /// The top-left corner of the block, in parent lattice nodes.
+    pub origin: (usize, usize),
+    /// The size of the block, in parent lattice nodes.
+    pub extent: (usize, usize),
+    /// The fine lattice, which may itself contain refined blocks.
+    pub state:  State<L>,
+    interior:   Geometry,
+    /// The parent populations over the block at the end of the last parent
+    /// step, before collision, as prolongated onto the fine lattice.
+    previous:   Populations,
+}
+
+impl<L: Lattice> RefinedBlock<L> {
+    pub fn new(
+        parent:    &State<L>,
+        origin:    (usize, usize),
+        extent:    (usize, usize),
+        collision: Box<CollisionOperator<L>>,
+    ) -> Self {
+        let (x, y) = origin;
+        let (w, h) = extent;
+        let (pw, ph) = parent.size();
+        assert!((w > 2) && (h > 2), "refined block is too small");
+        assert!((x + w <= pw) && (y + h <= ph), "refined block out of bounds");
+
+        let fine_size = (REFINEMENT_RATIO * w, REFINEMENT_RATIO * h);
+        let ratio = REFINEMENT_RATIO as Scalar;
+        let disc = Discretization {
+            delta_x: parent.delta_x() / ratio,
+            delta_t: parent.delta_t() / ratio,
+        };
+
+        let geometry = {
+            let seqs = super::matrix::region_seqs(origin, extent);
+            let coarse = af::index(&parent.geometry, &seqs).cast::<f32>();
+            let (fw, fh) = fine_size;
+            let fine = af::resize(&coarse, fh as i64, fw as i64,
+                                  af::InterpType::NEAREST);
+            af::gt(&fine, &0.5f32, false)
+        };
+
+        let interior = {
+            let (fw, fh) = fine_size;
+            let mut vec = Vec::new();
+            vec.resize(fw * fh, false);
+            for i in 1 .. (fw - 1) {
+                for j in 1 .. (fh - 1) { vec[j * fw + i] = true; }
+            }
+            let dim4 = af::Dim4::new(&[fw as u64, fh as u64, 1, 1]);
+            af::transpose(&af::Array::new(&vec[..], dim4), false)
+        };
+
+        let tau_c = relaxation_time(&*parent.collision, &parent.discretization);
+        let tau_f = relaxation_time(&*collision, &disc);
+        // The parent holds post-collision populations here, so the first
+        // step starts from a slightly different non-equilibrium part; it is
+        // exact when the parent starts at equilibrium.
+        let pops = Self::convert(parent, origin, extent,
+                                 rescaling_factor(tau_c, tau_f));
+
+        let mut state = State::initial(
+            Box::new(parent.lattice.with_populations(pops.clone())),
+            geometry,
+            collision,
+            disc,
+        );
+        state.time = parent.time;
+
+        RefinedBlock {
+            origin:   origin,
+            extent:   extent,
+            state:    state,
+            interior: interior,
+            previous: pops,
+        }
+    }
+
+    /// The populations of the parent over this block, rescaled and
+    /// interpolated onto the fine lattice.
+    pub fn prolongate(&self, parent: &State<L>) -> Populations {
+        Self::convert(parent, self.origin, self.extent,
+                      self.rescaling_factor(parent))
+    }
+
+    /// Advance the block by one parent time step, taking its boundary values
+    /// from the parent populations before collision at the start and the
+    /// end of that step, and restrict the result to the parent. The parent
+    /// must have propagated but not yet collided.
+    pub fn advance(&mut self, parent: &mut State<L>) {
+        let after = self.prolongate(parent);
+        let before = std::mem::replace(&mut self.previous, after.clone());
+        for k in 0 .. REFINEMENT_RATIO {
+            let theta = ((k + 1) as Scalar) / (REFINEMENT_RATIO as Scalar);
+            self.state.propagate();
+            {
+                let pops = self.state.lattice.populations_mut();
+                for (pair, (b, a)) in pops.iter_mut().zip(before.iter().zip(&after)) {
+                    let boundary = b.1.scale(1.0 - theta) + a.1.scale(theta);
+                    af::replace(pair.1.get_array_mut(),
+                                &self.interior,
+                                boundary.get_array());
+                }
+            }
+            self.state.step_blocks();
+            if k + 1 == REFINEMENT_RATIO { self.restrict(parent); }
+            self.state.relax();
+        }
+    }
+
+    /// Overwrite the parent populations under the block, except for the
+    /// nodes on its edge, with the populations of the fine lattice. Both
+    /// must be those before collision.
+    pub fn restrict(&self, parent: &mut State<L>) {
+        let alpha = self.rescaling_factor(parent);
+        let f_eq  = self.state.equilibrium();
+        let f_neq = self.state.non_equilibrium();
+        let (x, y) = self.origin;
+        let (w, h) = self.extent;
+        let pops = parent.lattice.populations_mut();
+        for ((pair, (_, eq)), (_, neq)) in pops.iter_mut().zip(&f_eq).zip(&f_neq) {
+            let fine = eq + neq.scale(1.0 / alpha);
+            let coarse = fine
+                .decimate(REFINEMENT_RATIO)
+                .subregion((1, 1), (w - 2, h - 2));
+            pair.1.set_subregion((x + 1, y + 1), &coarse);
+        }
+    }
+
+    fn rescaling_factor(&self, parent: &State<L>) -> Scalar {
+        let tau_c = relaxation_time(&*parent.collision, &parent.discretization);
+        let tau_f = relaxation_time(&*self.state.collision,
+                                    &self.state.discretization);
+        rescaling_factor(tau_c, tau_f)
+    }
+
+    fn convert(
+        parent: &State<L>,
+        origin: (usize, usize),
+        extent: (usize, usize),
+        alpha:  Scalar,
+    ) -> Populations {
+        let (w, h) = extent;
+        let fine_size = (REFINEMENT_RATIO * w, REFINEMENT_RATIO * h);
+        let f_eq  = parent.equilibrium();
+        let f_neq = parent.non_equilibrium();
+        let mut result = Vec::with_capacity(f_eq.len());
+        for ((dir, eq), (_, neq)) in f_eq.iter().zip(&f_neq) {
+            let coarse = (eq + neq.scale(alpha)).subregion(origin, extent);
+            let fine = coarse.resize(fine_size, af::InterpType::BILINEAR);
+            result.push((dir.clone(), fine));
+        }
+        result
+    }
+}
+
+/// The factor by which pre-collision non-equilibrium populations are scaled
+/// when passing from a lattice with relaxation time `tau_c` to one with twice
+/// its resolution and relaxation time `tau_f`.
+pub fn rescaling_factor(tau_c: Scalar, tau_f: Scalar) -> Scalar {
+    tau_f / ((REFINEMENT_RATIO as Scalar) * tau_c)
+}
+
+// -----------------------------------------------------------------------------
@@ -0,0 +1,208 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use std::io::BufRead;
+use super::lbm::{Geometry, Matrix, Scalar};
+
+// -----------------------------------------------------------------------------
+
+/// A scalar that varies with the physical time of a simulation.
+pub enum Schedule {
+    Constant(Scalar),
+    /// Rises linearly from zero to `value` over `duration`, then stays there.
+    Ramp { value: Scalar, duration: Scalar },
+    /// `mean + amplitude * sin(2 pi t / period + phase)`.
+    Sinusoid { mean: Scalar, amplitude: Scalar, period: Scalar, phase: Scalar },
+    /// Linear interpolation between `(time, value)` points sorted by time,
+    /// holding the first and last values outside of them.
+    Table(Vec<(Scalar, Scalar)>),
+    Closure(Box<Fn(Scalar) -> Scalar>),
+    Product(Box<Schedule>, Box<Schedule>),
+}
+
+impl Schedule {
+    /// A sinusoidal pulsation, e.g. for Womersley flow.
+    pub fn pulsatile(mean: Scalar, amplitude: Scalar, period: Scalar) -> Self {
+        Schedule::Sinusoid {
+            mean:      mean,
+            amplitude: amplitude,
+            period:    period,
+            phase:     0.0,
+        }
+    }
+
+    /// This schedule multiplied by another, e.g. a ramp to start a pulsatile
+    /// flow smoothly.
+    pub fn times(self, other: Schedule) -> Self {
+        Schedule::Product(Box::new(self), Box::new(other))
+    }
+
+    pub fn evaluate(&self, time: Scalar) -> Scalar {
+        match *self {
+            Schedule::Constant(value) => value,
+            Schedule::Ramp { value, duration } => {
+                if time >= duration { value } else { value * time.max(0.0) / duration }
+            },
+            Schedule::Sinusoid { mean, amplitude, period, phase } => {
+                let angle = 2.0 * std::f32::consts::PI * time / period + phase;
+                mean + amplitude * angle.sin()
+            },
+            Schedule::Table(ref points) => interpolate(points, time),
+            Schedule::Closure(ref f) => f(time),
+            Schedule::Product(ref a, ref b) => a.evaluate(time) * b.evaluate(time),
+        }
+    }
+
+    /// A table read from a CSV file with a time and a value on each line.
+    /// Blank lines and lines starting with `#` are skipped, as is a header,
+    /// which is a first line whose first field is not a number.
+    pub fn from_csv<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        let invalid = |message: String| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+        };
+
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let mut points: Vec<(Scalar, Scalar)> = Vec::new();
+        let mut first = true;
+        for (number, line) in file.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+
+            let fields: Vec<&str> = line.split(',').map(|s| s.trim()).collect();
+            let (t, v) = match fields.as_slice() {
+                [t, v] => (*t, *v),
+                _ => return Err(invalid(format!("line {}: expected two columns",
+                                                number + 1))),
+            };
+            let is_header = first && t.parse::<Scalar>().is_err();
+            first = false;
+            if is_header { continue; }
+
+            let parse = |field: &str| field.parse::<Scalar>().map_err(|e| {
+                invalid(format!("line {}: {:?}: {}", number + 1, field, e))
+            });
+            points.push((parse(t)?, parse(v)?));
+        }
+
+        if points.is_empty() {
+            return Err(invalid("schedule table is empty".to_string()));
+        }
+        if points.windows(2).any(|p| p[1].0 <= p[0].0) {
+            return Err(invalid("schedule table times must increase".to_string()));
+        }
+        Ok(Schedule::Table(points))
+    }
+}
+
+fn interpolate(points: &[(Scalar, Scalar)], time: Scalar) -> Scalar {
+    let (first, last) = (points[0], points[points.len() - 1]);
+    if time <= first.0 { return first.1; }
+    if time >= last.0 { return last.1; }
+    let k = points.iter().position(|p| p.0 > time).unwrap();
+    let ((t0, v0), (t1, v1)) = (points[k - 1], points[k]);
+    v0 + (v1 - v0) * (time - t0) / (t1 - t0)
+}
+
+// -----------------------------------------------------------------------------
+
+/// Nodes held at the equilibrium with a scheduled velocity, applied by
+/// `State::step` after collision.
+pub struct Inlet {
+    pub nodes:    Geometry,
+    pub velocity: (Schedule, Schedule),
+    /// A factor on the velocity at each node, e.g. a parabolic profile.
+    pub profile:  Option<Matrix>,
+    pub density:  Scalar,
+}
+
+impl Inlet {
+    pub fn new(nodes: Geometry, velocity: (Schedule, Schedule)) -> Self {
+        Inlet { nodes: nodes, velocity: velocity, profile: None, density: 1.0 }
+    }
+
+    pub fn with_profile(mut self, profile: Matrix) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    /// The velocity of the inlet at the given time, over the whole lattice.
+    pub fn velocity_at(&self, time: Scalar, size: (usize, usize)) -> (Matrix, Matrix) {
+        scheduled_field(&self.velocity, &self.profile, time, size)
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// A body force density with scheduled components, applied by `State::step`
+/// along with `State::body_force`.
+pub struct ScheduledForce {
+    pub force:   (Schedule, Schedule),
+    /// A factor on the force at each node, e.g. to confine it to a region.
+    pub profile: Option<Matrix>,
+}
+
+impl ScheduledForce {
+    pub fn new(force: (Schedule, Schedule)) -> Self {
+        ScheduledForce { force: force, profile: None }
+    }
+
+    pub fn with_profile(mut self, profile: Matrix) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    pub fn force_at(&self, time: Scalar, size: (usize, usize)) -> (Matrix, Matrix) {
+        scheduled_field(&self.force, &self.profile, time, size)
+    }
+}
+
+fn scheduled_field(
+    schedules: &(Schedule, Schedule),
+    profile:   &Option<Matrix>,
+    time:      Scalar,
+    size:      (usize, usize),
+) -> (Matrix, Matrix) {
+    let (x, y) = (schedules.0.evaluate(time), schedules.1.evaluate(time));
+    match *profile {
+        Some(ref p) => (p.scale(x), p.scale(y)),
+        None => (Matrix::new_filled(x, size), Matrix::new_filled(y, size)),
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// Checks of reading schedule tables.
+#[cfg(test)]
+mod tests {
+    use std;
+    use super::*;
+
+    fn table(name: &str, contents: &str) -> std::io::Result<Schedule> {
+        let path = std::env::temp_dir().join(format!("schedule_{}.csv", name));
+        std::fs::write(&path, contents)?;
+        let result = Schedule::from_csv(&path);
+        std::fs::remove_file(&path)?;
+        result
+    }
+
+    #[test]
+    fn test_csv_header() {
+        match table("header", "time, value\n0, 1\n2, 3\n").unwrap() {
+            Schedule::Table(points) => assert_eq!(points, vec![(0.0, 1.0), (2.0, 3.0)]),
+            _ => panic!("expected a table"),
+        }
+        match table("no_header", "# comment\n0, 1\n2, 3\n").unwrap() {
+            Schedule::Table(points) => assert_eq!(points, vec![(0.0, 1.0), (2.0, 3.0)]),
+            _ => panic!("expected a table"),
+        }
+    }
+
+    #[test]
+    fn test_csv_invalid_rows() {
+        let error = table("bad_first", "0, one\n2, 3\n").err().unwrap();
+        assert!(error.to_string().starts_with("line 1:"), "{}", error);
+        let error = table("bad_later", "time, value\n0, 1\n2x, 3\n").err().unwrap();
+        assert!(error.to_string().starts_with("line 3:"), "{}", error);
+    }
+}
@@ -0,0 +1,191 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use arrayfire as af;
+use super::lbm::{Matrix, Scalar};
+use super::probe::ProbeSet;
+
+// -----------------------------------------------------------------------------
+
+/// The discrete Fourier transform of a real signal, as its real and imaginary
+/// parts.
+pub fn fft(signal: &[Scalar]) -> (Vec<Scalar>, Vec<Scalar>) {
+    let n = signal.len();
+    let input = af::Array::new(signal, af::Dim4::new(&[n as u64, 1, 1, 1]));
+    let transform = af::fft(&input, 1.0, n as i64);
+    let mut re = vec![0.0; n];
+    let mut im = vec![0.0; n];
+    af::real(&transform).host(&mut re);
+    af::imag(&transform).host(&mut im);
+    (re, im)
+}
+
+// -----------------------------------------------------------------------------
+
+/// A one-sided power spectral density.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Spectrum {
+    pub frequencies: Vec<Scalar>,
+    pub power:       Vec<Scalar>,
+}
+
+/// The Hann window of the given length.
+fn hann(n: usize) -> Vec<Scalar> {
+    (0 .. n).map(|k| {
+        let phase = 2.0 * std::f32::consts::PI * (k as Scalar) / ((n - 1) as Scalar);
+        0.5 * (1.0 - phase.cos())
+    }).collect()
+}
+
+/// The periodogram of a signal sampled every `sample_interval`, with its
+/// mean removed and a Hann window applied.
+pub fn psd(signal: &[Scalar], sample_interval: Scalar) -> Spectrum {
+    let n = signal.len();
+    assert!(n >= 4, "signal is too short for a spectrum");
+    let mean = signal.iter().sum::<Scalar>() / (n as Scalar);
+    let window = hann(n);
+    let norm: Scalar = window.iter().map(|w| w * w).sum();
+    let windowed: Vec<Scalar>
+        = signal.iter().zip(&window).map(|(x, w)| (x - mean) * w).collect();
+    let (re, im) = fft(&windowed);
+
+    let bins = n / 2 + 1;
+    let frequencies = (0 .. bins)
+        .map(|k| (k as Scalar) / ((n as Scalar) * sample_interval))
+        .collect();
+    let power = (0 .. bins).map(|k| {
+        // Every bin but DC and Nyquist also holds the negative frequency.
+        let doubled = if (k == 0) || (2 * k == n) { 1.0 } else { 2.0 };
+        doubled * (re[k] * re[k] + im[k] * im[k]) * sample_interval / norm
+    }).collect();
+    Spectrum { frequencies: frequencies, power: power }
+}
+
+/// Welch's estimate of the spectrum: the average of the periodograms of
+/// half-overlapping segments of the given length, which trades resolution
+/// for less noise.
+pub fn welch(signal: &[Scalar], sample_interval: Scalar, segment: usize) -> Spectrum {
+    assert!(segment <= signal.len(), "segment is longer than the signal");
+    let hop = std::cmp::max(segment / 2, 1);
+    let mut count = 0;
+    let mut result: Option<Spectrum> = None;
+    let mut start = 0;
+    while start + segment <= signal.len() {
+        let s = psd(&signal[start .. (start + segment)], sample_interval);
+        result = Some(match result {
+            None => s,
+            Some(mut r) => {
+                for (p, q) in r.power.iter_mut().zip(&s.power) { *p += q; }
+                r
+            },
+        });
+        count += 1;
+        start += hop;
+    }
+    let mut result = result.unwrap();
+    for p in &mut result.power { *p /= count as Scalar; }
+    result
+}
+
+/// The frequency of the highest peak of the spectrum, excluding the zero
+/// frequency, refined by fitting a parabola through the peak and its
+/// neighbours.
+pub fn dominant_frequency(spectrum: &Spectrum) -> Option<Scalar> {
+    let p = &spectrum.power;
+    if p.len() < 3 { return None; }
+    let k = (1 .. p.len())
+        .max_by(|&a, &b| p[a].partial_cmp(&p[b]).unwrap_or(std::cmp::Ordering::Equal))?;
+    if p[k] <= 0.0 { return None; }
+    let df = spectrum.frequencies[1] - spectrum.frequencies[0];
+    if k + 1 >= p.len() {
+        return Some(spectrum.frequencies[k]);
+    }
+    let (a, b, c) = (p[k - 1], p[k], p[k + 1]);
+    let denominator = a - 2.0 * b + c;
+    let offset = if denominator.abs() > 0.0 { 0.5 * (a - c) / denominator } else { 0.0 };
+    Some(spectrum.frequencies[k] + offset * df)
+}
+
+/// The Strouhal number `f L / U` of a shedding frequency.
+pub fn strouhal(frequency: Scalar, length: Scalar, velocity: Scalar) -> Scalar {
+    frequency * length / velocity
+}
+
+/// The Strouhal number of one column of a probe, e.g. the cross-stream
+/// velocity in the wake of a body of the given length in a stream of the
+/// given velocity. The first `transient` samples are discarded.
+pub fn probe_strouhal(
+    probes:    &ProbeSet,
+    probe:     &str,
+    column:    &str,
+    transient: usize,
+    length:    Scalar,
+    velocity:  Scalar,
+) -> Option<Scalar> {
+    let series = probes.series(probe, column)?;
+    if series.len() < transient + 4 { return None; }
+    let interval = probes.times[1] - probes.times[0];
+    let spectrum = psd(&series[transient ..], interval);
+    dominant_frequency(&spectrum).map(|f| strouhal(f, length, velocity))
+}
+
+// -----------------------------------------------------------------------------
+
+/// The proper orthogonal decomposition of a set of field snapshots.
+pub struct Pod {
+    pub mean:         Matrix,
+    /// The spatial modes, orthonormal and sorted by decreasing energy.
+    pub modes:        Vec<Matrix>,
+    /// The fraction of the fluctuation energy captured by each mode.
+    pub energies:     Vec<Scalar>,
+    /// The coefficient of each mode in each snapshot.
+    pub coefficients: Vec<Vec<Scalar>>,
+}
+
+/// The first `count` POD modes of the snapshots, by the method of snapshots
+/// of Sirovich, "Turbulence and the dynamics of coherent structures", which
+/// only needs the eigenvectors of the small correlation matrix between
+/// snapshots.
+pub fn pod(snapshots: &[Matrix], count: usize) -> Pod {
+    let m = snapshots.len();
+    assert!(m >= 2, "POD needs at least two snapshots");
+    let shape = snapshots[0].get_shape();
+    let n = shape.0 * shape.1;
+    let count = std::cmp::min(count, m);
+
+    let mut mean = Matrix::new_filled(0.0, shape);
+    for s in snapshots { mean += s.clone(); }
+    let mean = mean.scale(1.0 / (m as Scalar));
+
+    let mut data = Vec::with_capacity(n * m);
+    for s in snapshots { data.extend((s - &mean).get_underlying()); }
+    let x = af::Array::new(&data, af::Dim4::new(&[n as u64, m as u64, 1, 1]));
+
+    let correlation = af::matmul(&x, &x, af::MatProp::TRANS, af::MatProp::NONE);
+    let (u, s, _) = af::svd(&correlation);
+    let projected = af::matmul(&x, &u, af::MatProp::NONE, af::MatProp::NONE);
+
+    let mut eigenvalues = vec![0.0; m];
+    let mut eigenvectors = vec![0.0; m * m];
+    let mut columns = vec![0.0; n * m];
+    s.host(&mut eigenvalues);
+    u.host(&mut eigenvectors);
+    projected.host(&mut columns);
+
+    let total: Scalar = eigenvalues.iter().sum();
+    let mut modes = Vec::with_capacity(count);
+    let mut energies = Vec::with_capacity(count);
+    let mut coefficients = Vec::with_capacity(count);
+    for j in 0 .. count {
+        let sigma = eigenvalues[j].max(std::f32::MIN_POSITIVE).sqrt();
+        let column: Vec<Scalar>
+            = columns[(j * n) .. ((j + 1) * n)].iter().map(|v| v / sigma).collect();
+        modes.push(Matrix::new(&column, shape).unwrap());
+        energies.push(eigenvalues[j] / total);
+        coefficients.push((0 .. m).map(|t| sigma * eigenvectors[j * m + t]).collect());
+    }
+
+    Pod { mean: mean, modes: modes, energies: energies, coefficients: coefficients }
+}
+
+// -----------------------------------------------------------------------------
@@ -0,0 +1,329 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use arrayfire as af;
+use prettytable::Table;
+use serde_json;
+use toml;
+use super::lbm::{OperatorKind, Scalar};
+use super::case::{self, CaseFile, CaseResult};
+
+// -----------------------------------------------------------------------------
+
+/// The values each swept field of a case file takes. Fields left empty keep
+/// the value of the base case.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct Grid {
+    #[serde(default)]
+    pub resolution:     Vec<usize>,
+    #[serde(default)]
+    pub inlet_velocity: Vec<Scalar>,
+    #[serde(default)]
+    pub operator:       Vec<OperatorKind>,
+    #[serde(default)]
+    pub viscosity:      Vec<Scalar>,
+    /// Sets the viscosity from the inlet velocity, so it cannot be swept
+    /// along with the viscosity.
+    #[serde(default)]
+    pub reynolds:       Vec<Scalar>,
+}
+
+/// A base case and the grid of parameters to run it at, as read from a TOML
+/// file with a `[base]` and a `[grid]` table.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct SweepFile {
+    pub base: CaseFile,
+    #[serde(default)]
+    pub grid: Grid,
+}
+
+impl SweepFile {
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        let invalid = |message: String| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+        };
+        let text = std::fs::read_to_string(path)?;
+        let sweep: SweepFile = toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+        if !sweep.grid.viscosity.is_empty() && !sweep.grid.reynolds.is_empty() {
+            return Err(invalid("a sweep cannot set both the viscosity and the \
+                                Reynolds number".to_string()));
+        }
+        Ok(sweep)
+    }
+
+    /// Every combination of the swept values, each named after the base case
+    /// and the values that differ from it.
+    pub fn expand(&self) -> Vec<CaseFile> {
+        fn axis<T: Clone>(values: &[T]) -> Vec<Option<T>> {
+            if values.is_empty() {
+                vec![None]
+            } else {
+                values.iter().cloned().map(Some).collect()
+            }
+        }
+
+        let grid = &self.grid;
+        let base = &self.base;
+        let mut cases = Vec::new();
+        for resolution in axis(&grid.resolution) {
+            for velocity in axis(&grid.inlet_velocity) {
+                for operator in axis(&grid.operator) {
+                    for viscosity in axis(&grid.viscosity) {
+                        for reynolds in axis(&grid.reynolds) {
+                            let mut case = base.clone();
+                            let mut name = vec![base.name.clone()];
+                            if let Some(n) = resolution {
+                                case.resolution = n;
+                                name.push(format!("n{}", n));
+                            }
+                            if let Some(u) = velocity {
+                                case.inlet_velocity = u;
+                                name.push(format!("u{}", u));
+                            }
+                            if let Some(o) = operator {
+                                case.operator = o;
+                                name.push(format!("{:?}", o));
+                            }
+                            if let Some(nu) = viscosity {
+                                case.viscosity = nu;
+                                name.push(format!("nu{}", nu));
+                            }
+                            if let Some(re) = reynolds {
+                                case.set_reynolds(re);
+                                name.push(format!("re{}", re));
+                            }
+                            case.name = name.join("-");
+                            cases.push(case);
+                        }
+                    }
+                }
+            }
+        }
+        cases
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// How the cases of a sweep are spread over the hardware.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Execution {
+    /// One case after the other on the active device.
+    Serial,
+    /// A thread per ArrayFire device, each taking cases in turn.
+    Devices(Vec<i32>),
+    /// Up to `jobs` child processes at a time, each running
+    /// `program --case <file> --result <file> --device <k>` and cycling
+    /// through `devices` devices. The case and result files are kept in
+    /// `directory`.
+    Processes {
+        program:   std::path::PathBuf,
+        jobs:      usize,
+        devices:   usize,
+        directory: std::path::PathBuf,
+    },
+}
+
+/// Run every case and return the results in the same order.
+pub fn run(cases: &[CaseFile], execution: &Execution) -> std::io::Result<Vec<CaseResult>> {
+    match *execution {
+        Execution::Serial => Ok(cases.iter().map(|c| {
+            println!("[NOTE] Running case {}", c.name);
+            case::run(c)
+        }).collect()),
+        Execution::Devices(ref devices) => run_on_devices(cases, devices),
+        Execution::Processes { ref program, jobs, devices, ref directory } => {
+            run_in_processes(cases, program, jobs, devices, directory)
+        },
+    }
+}
+
+fn run_on_devices(cases: &[CaseFile], devices: &[i32]) -> std::io::Result<Vec<CaseResult>> {
+    use std::sync::{mpsc, Arc, Mutex};
+
+    assert!(!devices.is_empty(), "a sweep needs at least one device");
+    let backend = af::get_active_backend();
+    let queue = Arc::new(Mutex::new(cases.iter().cloned().enumerate().rev().collect::<Vec<_>>()));
+    let (sender, receiver) = mpsc::channel();
+
+    let workers: Vec<_> = devices.iter().map(|&device| {
+        let queue = queue.clone();
+        let sender = sender.clone();
+        std::thread::spawn(move || {
+            // The backend and device are per thread in ArrayFire.
+            af::set_backend(backend);
+            af::set_device(device);
+            loop {
+                // The queue is reversed, so this takes the cases in order.
+                let next = queue.lock().unwrap().pop();
+                let (index, case) = match next {
+                    Some(next) => next,
+                    None => break,
+                };
+                println!("[NOTE] Running case {} on device {}", case.name, device);
+                // A failed case is reported with the others rather than
+                // taking its worker and the rest of its cases down.
+                let result = std::panic::catch_unwind(|| case::run(&case)).map_err(|panic| {
+                    let message = panic.downcast_ref::<&str>().map(|s| s.to_string())
+                        .or_else(|| panic.downcast_ref::<String>().cloned())
+                        .unwrap_or_default();
+                    std::io::Error::new(std::io::ErrorKind::Other,
+                                        format!("case {} failed on device {}: {}",
+                                                case.name, device, message))
+                });
+                sender.send((index, result)).unwrap();
+            }
+        })
+    }).collect();
+    drop(sender);
+
+    let mut results: Vec<Option<std::io::Result<CaseResult>>>
+        = (0 .. cases.len()).map(|_| None).collect();
+    for (index, result) in receiver { results[index] = Some(result); }
+    for worker in workers { worker.join().unwrap(); }
+    results.into_iter().map(|r| r.unwrap()).collect()
+}
+
+fn run_in_processes(
+    cases:     &[CaseFile],
+    program:   &std::path::Path,
+    jobs:      usize,
+    devices:   usize,
+    directory: &std::path::Path,
+) -> std::io::Result<Vec<CaseResult>> {
+    assert!((jobs > 0) && (devices > 0), "a sweep needs a job and a device");
+    std::fs::create_dir_all(directory)?;
+
+    let wait = |child: &mut std::process::Child, name: &str| -> std::io::Result<()> {
+        let status = child.wait()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(std::io::Error::new(std::io::ErrorKind::Other,
+                                    format!("case {} failed: {}", name, status)))
+        }
+    };
+
+    let mut running: std::collections::VecDeque<(std::process::Child, &str)>
+        = std::collections::VecDeque::new();
+    let mut paths = Vec::with_capacity(cases.len());
+    let mut failure = None;
+    for (k, case) in cases.iter().enumerate() {
+        if running.len() == jobs {
+            let (mut child, name) = running.pop_front().unwrap();
+            if let Err(e) = wait(&mut child, name) {
+                failure = Some(e);
+                break;
+            }
+        }
+        let input = directory.join(format!("{}.toml", case.name));
+        let output = directory.join(format!("{}.json", case.name));
+        println!("[NOTE] Running case {} in a child process", case.name);
+        let child = case.save(&input).and_then(|()| {
+            std::process::Command::new(program)
+                .arg("--case").arg(&input)
+                .arg("--result").arg(&output)
+                .arg("--device").arg((k % devices).to_string())
+                .spawn()
+        });
+        match child {
+            Ok(child) => running.push_back((child, &case.name)),
+            Err(e) => {
+                failure = Some(e);
+                break;
+            },
+        }
+        paths.push(output);
+    }
+    // Every child is waited for, even after a failure, so that none is left
+    // running once the sweep has returned.
+    for (mut child, name) in running {
+        let result = wait(&mut child, name);
+        if failure.is_none() { failure = result.err(); }
+    }
+    if let Some(e) = failure { return Err(e); }
+
+    paths.iter().map(|path| -> std::io::Result<CaseResult> {
+        let file = std::fs::File::open(path)?;
+        Ok(serde_json::from_reader(file)?)
+    }).collect()
+}
+
+// -----------------------------------------------------------------------------
+
+/// Write the results of a sweep as a JSON array.
+pub fn save<P: AsRef<std::path::Path>>(results: &[CaseResult], path: P) -> std::io::Result<()> {
+    let file = std::fs::File::create(path)?;
+    serde_json::to_writer_pretty(file, results)?;
+    Ok(())
+}
+
+/// Tabulate the scalar outputs of every case.
+pub fn summary(results: &[CaseResult]) -> Table {
+    let mut table = Table::new();
+    table.add_row(row!["Case", "Operator", "Resolution", "Re", "Steps", "Status",
+                       "Drag", "Lift (RMS)", "Strouhal", "MLUPS"]);
+    for r in results {
+        let strouhal = match r.strouhal {
+            Some(st) => format!("{:.4}", st),
+            None => String::new(),
+        };
+        table.add_row(row![r.case.name, r.case.operator.name(), r.case.resolution,
+                           format!("{:.1}", r.case.reynolds()), r.steps, r.status,
+                           format!("{:.4}", r.drag), format!("{:.4}", r.lift),
+                           strouhal, format!("{:.1}", r.mlups)]);
+    }
+    table
+}
+
+// -----------------------------------------------------------------------------
+
+/// Checks of the expansion of a sweep into cases.
+#[cfg(test)]
+mod tests {
+    use toml;
+    use super::*;
+
+    fn sweep(grid: &str) -> SweepFile {
+        let text = format!("
+            [base]
+            name           = \"cylinder\"
+            resolution     = 20
+            domain         = [22.0, 4.1]
+            center         = [2.0, 2.0]
+            viscosity      = 0.01
+            inlet_velocity = 1.0
+            operator       = \"BGK\"
+            duration       = 10.0
+
+            [grid]
+            {}
+        ", grid);
+        toml::from_str(&text).unwrap()
+    }
+
+    #[test]
+    fn test_expand_without_grid() {
+        let sweep = sweep("");
+        assert_eq!(sweep.expand(), vec![sweep.base.clone()]);
+    }
+
+    #[test]
+    fn test_expand_product() {
+        let sweep = sweep("resolution = [10, 20]\n\
+                           operator   = [\"BGK\", \"TRT\", \"KBC\"]\n\
+                           reynolds   = [100.0]");
+        let cases = sweep.expand();
+        let names: Vec<&str> = cases.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["cylinder-n10-BGK-re100", "cylinder-n10-TRT-re100",
+                               "cylinder-n10-KBC-re100", "cylinder-n20-BGK-re100",
+                               "cylinder-n20-TRT-re100", "cylinder-n20-KBC-re100"]);
+        assert_eq!(cases[4].resolution, 20);
+        assert_eq!(cases[4].operator, OperatorKind::TRT);
+        for case in &cases {
+            assert!((case.reynolds() - 100.0).abs() < 1.0e-3);
+            assert_eq!(case.inlet_velocity, sweep.base.inlet_velocity);
+            assert_eq!(case.domain, sweep.base.domain);
+        }
+    }
+}
@@ -0,0 +1,285 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use std::io::Write;
+use super::lbm::{Lattice, Matrix, Scalar, State};
+use super::probe::{self, Shape};
+use super::overlay::draw_line;
+use super::display::{Drawable, RGB, PixelPos};
+
+// -----------------------------------------------------------------------------
+
+/// A point carried by the flow, e.g. a particle of dye.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Tracer {
+    pub id:       usize,
+    /// In lattice node coordinates.
+    pub position: [Scalar; 2],
+    /// In lattice units, i.e. nodes per time step, as `State::velocity`.
+    pub velocity: [Scalar; 2],
+    pub released: Scalar,
+    pub color:    RGB,
+    /// The most recent positions, oldest first.
+    pub trail:    std::collections::VecDeque<[Scalar; 2]>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Integrator {
+    /// The explicit midpoint method.
+    RK2,
+    RK4,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Kind {
+    /// Tracers that follow the fluid exactly.
+    Massless,
+    /// Small heavy spheres that relax towards the fluid velocity by Stokes
+    /// drag over the given response time `rho_p d^2 / (18 mu)`. The
+    /// integrators are explicit, so it must not be much shorter than the time
+    /// step.
+    Inertial { response_time: Scalar },
+}
+
+/// Releases a tracer at each point of a shape every `interval` steps.
+pub struct Source {
+    pub points:   Shape,
+    pub interval: usize,
+    pub color:    RGB,
+}
+
+impl Source {
+    pub fn new(points: Shape, interval: usize, color: RGB) -> Self {
+        assert!(interval > 0, "source interval must be positive");
+        Source { points: points, interval: interval, color: color }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Style {
+    Dots,
+    /// The dots with a line fading along their trail.
+    Trails,
+}
+
+// -----------------------------------------------------------------------------
+
+/// A population of tracers advected by the velocity of a `State`, bilinearly
+/// interpolated on the device for all of them at once.
+pub struct TracerSet {
+    pub tracers:      Vec<Tracer>,
+    pub sources:      Vec<Source>,
+    pub integrator:   Integrator,
+    pub kind:         Kind,
+    /// How many past positions each tracer keeps for drawing.
+    pub trail_length: usize,
+    /// Sources stop releasing once there are this many tracers.
+    pub capacity:     usize,
+    next_id:          usize,
+    calls:            usize,
+    trajectories:     Option<(std::io::BufWriter<std::fs::File>, usize)>,
+}
+
+impl TracerSet {
+    pub fn new(integrator: Integrator, kind: Kind) -> Self {
+        TracerSet {
+            tracers:      Vec::new(),
+            sources:      Vec::new(),
+            integrator:   integrator,
+            kind:         kind,
+            trail_length: 32,
+            capacity:     100000,
+            next_id:      0,
+            calls:        0,
+            trajectories: None,
+        }
+    }
+
+    pub fn with_source(mut self, source: Source) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    /// Write the time, id, position and velocity of every tracer, in lattice
+    /// units, to a CSV file every `interval` steps.
+    pub fn record<P: AsRef<std::path::Path>>(
+        &mut self,
+        path:     P,
+        interval: usize,
+    ) -> std::io::Result<()> {
+        assert!(interval > 0, "trajectory interval must be positive");
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "time,id,x,y,vx,vy")?;
+        self.trajectories = Some((file, interval));
+        Ok(())
+    }
+
+    pub fn release(&mut self, position: [Scalar; 2], time: Scalar, color: RGB) {
+        if self.tracers.len() >= self.capacity { return; }
+        self.tracers.push(Tracer {
+            id:       self.next_id,
+            position: position,
+            velocity: [0.0, 0.0],
+            released: time,
+            color:    color,
+            trail:    std::collections::VecDeque::new(),
+        });
+        self.next_id += 1;
+    }
+
+    /// Release `count` tracers evenly spread over a disk, e.g. under the
+    /// mouse, on a Fermat spiral.
+    pub fn paint(&mut self, center: [Scalar; 2], radius: Scalar, count: usize,
+                 time: Scalar, color: RGB) {
+        let golden = std::f32::consts::PI * (3.0 - (5.0 as Scalar).sqrt());
+        for k in 0 .. count {
+            let r = radius * ((k as Scalar + 0.5) / (count as Scalar)).sqrt();
+            let angle = golden * (k as Scalar);
+            self.release([center[0] + r * angle.cos(), center[1] + r * angle.sin()],
+                         time, color);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.tracers.clear();
+    }
+
+    /// Release from the sources, then move every tracer over one time step
+    /// of the state, dropping those that leave the lattice or enter a solid.
+    pub fn step<L: Lattice>(&mut self, state: &State<L>) -> std::io::Result<()> {
+        let calls = self.calls;
+        self.calls += 1;
+        let releases: Vec<([Scalar; 2], RGB)> = self.sources.iter()
+            .filter(|s| calls % s.interval == 0)
+            .flat_map(|s| s.points.points().into_iter().map(move |p| (p, s.color)))
+            .collect();
+        for (p, color) in releases { self.release(p, state.time, color); }
+        if self.tracers.is_empty() { return Ok(()); }
+
+        let velocity = state.velocity();
+        let dt = state.discretization.delta_t;
+        let kind = self.kind;
+
+        // The derivative of (x, y, vx, vy) per time step. The velocities are
+        // in lattice units, so they are the displacement in nodes.
+        let derivative = |states: &[[Scalar; 4]]| -> Vec<[Scalar; 4]> {
+            let points: Vec<[Scalar; 2]> = states.iter().map(|s| [s[0], s[1]]).collect();
+            let ux = probe::sample(&velocity.0, &points);
+            let uy = probe::sample(&velocity.1, &points);
+            states.iter().zip(ux.iter().zip(&uy)).map(|(s, (&u, &v))| match kind {
+                Kind::Massless => [u, v, 0.0, 0.0],
+                Kind::Inertial { response_time } => {
+                    let relax = dt / response_time;
+                    [s[2], s[3], (u - s[2]) * relax, (v - s[3]) * relax]
+                },
+            }).collect()
+        };
+        let advance = |states: &[[Scalar; 4]], rates: &[[Scalar; 4]], h: Scalar| {
+            states.iter().zip(rates).map(|(s, r)| {
+                [s[0] + h * r[0], s[1] + h * r[1], s[2] + h * r[2], s[3] + h * r[3]]
+            }).collect::<Vec<[Scalar; 4]>>()
+        };
+
+        let start: Vec<[Scalar; 4]> = self.tracers.iter().map(|t| {
+            [t.position[0], t.position[1], t.velocity[0], t.velocity[1]]
+        }).collect();
+        let k1 = derivative(&start);
+        let end = match self.integrator {
+            Integrator::RK2 => {
+                let k2 = derivative(&advance(&start, &k1, 0.5));
+                advance(&start, &k2, 1.0)
+            },
+            Integrator::RK4 => {
+                let k2 = derivative(&advance(&start, &k1, 0.5));
+                let k3 = derivative(&advance(&start, &k2, 0.5));
+                let k4 = derivative(&advance(&start, &k3, 1.0));
+                let mut result = start.clone();
+                for (i, r) in result.iter_mut().enumerate() {
+                    for c in 0 .. 4 {
+                        r[c] += (k1[i][c] + 2.0 * k2[i][c] + 2.0 * k3[i][c] + k4[i][c]) / 6.0;
+                    }
+                }
+                result
+            },
+        };
+        let sampled: Vec<[Scalar; 2]> = match kind {
+            // Massless tracers take the velocity of the fluid where they land.
+            Kind::Massless => {
+                derivative(&end).iter().map(|r| [r[0], r[1]]).collect()
+            },
+            Kind::Inertial { .. } => end.iter().map(|s| [s[2], s[3]]).collect(),
+        };
+
+        let (w, h) = state.size();
+        let (wf, hf) = (w as Scalar, h as Scalar);
+        let points: Vec<[Scalar; 2]> = end.iter().map(|s| {
+            let wrap = |x: Scalar, n: Scalar, periodic: bool| {
+                if periodic { x.rem_euclid(n) } else { x }
+            };
+            [wrap(s[0], wf, state.periodic.0), wrap(s[1], hf, state.periodic.1)]
+        }).collect();
+        let solid = probe::sample(&Matrix::unsafe_new(state.geometry.cast::<f32>()), &points);
+
+        let trail_length = self.trail_length;
+        let moved = std::mem::replace(&mut self.tracers, Vec::new());
+        for (k, mut tracer) in moved.into_iter().enumerate() {
+            let p = points[k];
+            let inside = (p[0] >= 0.0) && (p[1] >= 0.0) && (p[0] <= wf - 1.0)
+                && (p[1] <= hf - 1.0);
+            if !inside || (solid[k] >= 0.5) { continue; }
+            tracer.trail.push_back(tracer.position);
+            while tracer.trail.len() > trail_length { tracer.trail.pop_front(); }
+            tracer.position = p;
+            tracer.velocity = sampled[k];
+            self.tracers.push(tracer);
+        }
+
+        if let Some((ref mut file, interval)) = self.trajectories {
+            if calls % interval == 0 {
+                for t in &self.tracers {
+                    writeln!(file, "{},{},{},{},{},{}", state.time, t.id,
+                             t.position[0], t.position[1], t.velocity[0], t.velocity[1])?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn draw<D: Drawable>(&self, style: Style, buf: &mut D) {
+        let (w, h) = buf.dimensions();
+        for t in &self.tracers {
+            if style == Style::Trails {
+                let n = t.trail.len();
+                let mut previous = t.position;
+                // Newest first, fading towards the tail. Segments that jump
+                // across a periodic edge are skipped.
+                for (k, &p) in t.trail.iter().rev().enumerate() {
+                    let jump = (p[0] - previous[0]).abs() + (p[1] - previous[1]).abs();
+                    if jump < 0.5 * (w.min(h) as Scalar) {
+                        let opacity = 0.8 * (1.0 - (k as Scalar) / (n as Scalar));
+                        draw_line(buf, previous, p, t.color, opacity);
+                    }
+                    previous = p;
+                }
+            }
+            let (x, y) = (t.position[0].round() as u32, t.position[1].round() as u32);
+            for &(dx, dy) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+                if (x + dx < w) && (y + dy < h) {
+                    buf.set_pixel(PixelPos(x + dx, y + dy), t.color);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for TracerSet {
+    fn drop(&mut self) {
+        if let Some((ref mut file, _)) = self.trajectories {
+            if let Err(e) = file.flush() {
+                println!("Failed to flush trajectories: {}", e);
+            }
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
@@ -0,0 +1,197 @@
+// -----------------------------------------------------------------------------
+
+use std;
+
+// -----------------------------------------------------------------------------
+
+/// How the lattice is filtered when a node covers several pixels.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Magnification {
+    /// Every node is a sharp square.
+    Nearest,
+    Linear,
+}
+
+impl Magnification {
+    pub fn next(&self) -> Self {
+        match *self {
+            Magnification::Nearest => Magnification::Linear,
+            Magnification::Linear  => Magnification::Nearest,
+        }
+    }
+}
+
+const MIN_ZOOM: f64 = 0.25;
+const MAX_ZOOM: f64 = 64.0;
+
+/// Where the lattice is shown in the part of the window set aside for it.
+///
+/// At unit zoom the whole lattice fits the area, keeping its aspect ratio;
+/// zooming magnifies it further around `center`. Window coordinates are in
+/// pixels from the top left corner of the area, lattice coordinates in nodes.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Viewport {
+    pub lattice:       (usize, usize),
+    pub area:          (f64, f64),
+    pub zoom:          f64,
+    /// The lattice point shown in the middle of the area.
+    pub center:        [f64; 2],
+    pub magnification: Magnification,
+}
+
+impl Viewport {
+    pub fn new(lattice: (usize, usize), area: (f64, f64)) -> Self {
+        Viewport {
+            lattice:       lattice,
+            area:          area,
+            zoom:          1.0,
+            center:        [lattice.0 as f64 / 2.0, lattice.1 as f64 / 2.0],
+            magnification: Magnification::Nearest,
+        }
+    }
+
+    /// A window size for the lattice that fits within `max`: shrunk to fit
+    /// if the lattice is larger, and enlarged by a whole factor, up to half
+    /// of `max`, if it is much smaller.
+    pub fn initial_area(lattice: (usize, usize), max: (u32, u32)) -> (u32, u32) {
+        let (w, h) = (lattice.0 as f64, lattice.1 as f64);
+        let fit = (max.0 as f64 / w).min(max.1 as f64 / h);
+        let scale = if fit < 1.0 { fit } else { (fit / 2.0).floor().max(1.0) };
+        ((w * scale).round() as u32, (h * scale).round() as u32)
+    }
+
+    /// Pixels per lattice node.
+    pub fn scale(&self) -> f64 {
+        let (w, h) = (self.lattice.0 as f64, self.lattice.1 as f64);
+        self.zoom * (self.area.0 / w).min(self.area.1 / h)
+    }
+
+    /// Where the origin of the lattice is in the window.
+    pub fn offset(&self) -> [f64; 2] {
+        let s = self.scale();
+        [self.area.0 / 2.0 - s * self.center[0], self.area.1 / 2.0 - s * self.center[1]]
+    }
+
+    /// The lattice point under a window point, or nothing outside of the
+    /// area, e.g. over a panel beside it. The point may lie beyond the edges
+    /// of the lattice.
+    pub fn to_lattice(&self, window: [f64; 2]) -> Option<[f64; 2]> {
+        let inside = (window[0] >= 0.0) && (window[1] >= 0.0)
+            && (window[0] < self.area.0) && (window[1] < self.area.1);
+        if !inside { return None; }
+        let (s, o) = (self.scale(), self.offset());
+        Some([(window[0] - o[0]) / s, (window[1] - o[1]) / s])
+    }
+
+    pub fn to_window(&self, lattice: [f64; 2]) -> [f64; 2] {
+        let (s, o) = (self.scale(), self.offset());
+        [o[0] + s * lattice[0], o[1] + s * lattice[1]]
+    }
+
+    /// The affine transform from lattice to window coordinates, as rows of a
+    /// 2×3 matrix, e.g. to compose with a graphics context.
+    pub fn transform(&self) -> [[f64; 3]; 2] {
+        let (s, o) = (self.scale(), self.offset());
+        [[s, 0.0, o[0]], [0.0, s, o[1]]]
+    }
+
+    /// Multiply the zoom by `factor`, keeping the lattice point under the
+    /// given window point where it is.
+    pub fn zoom_at(&mut self, factor: f64, window: [f64; 2]) {
+        let before = self.to_lattice(window).unwrap_or(self.center);
+        let zoom = (self.zoom * factor).max(MIN_ZOOM).min(MAX_ZOOM);
+        let ratio = self.zoom / zoom;
+        self.zoom = zoom;
+        for k in 0 .. 2 {
+            self.center[k] = before[k] + (self.center[k] - before[k]) * ratio;
+        }
+        self.clamp_center();
+    }
+
+    /// Move the view by a distance in window pixels, e.g. that of a drag.
+    pub fn pan(&mut self, delta: [f64; 2]) {
+        let s = self.scale();
+        self.center = [self.center[0] - delta[0] / s, self.center[1] - delta[1] / s];
+        self.clamp_center();
+    }
+
+    pub fn resize(&mut self, area: (f64, f64)) {
+        self.area = (area.0.max(1.0), area.1.max(1.0));
+    }
+
+    /// Show the whole lattice again.
+    pub fn reset(&mut self) {
+        let magnification = self.magnification;
+        *self = Viewport::new(self.lattice, self.area);
+        self.magnification = magnification;
+    }
+
+    /// Keep some of the lattice in view.
+    fn clamp_center(&mut self) {
+        let (w, h) = (self.lattice.0 as f64, self.lattice.1 as f64);
+        self.center = [self.center[0].max(0.0).min(w), self.center[1].max(0.0).min(h)];
+    }
+}
+
+impl std::fmt::Display for Viewport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:.2}x zoom around ({:.1}, {:.1}), {:?} magnification",
+               self.zoom, self.center[0], self.center[1], self.magnification)
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// Checks of the mapping between window and lattice coordinates.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: [f64; 2], b: [f64; 2]) -> bool {
+        (a[0] - b[0]).abs() < 1.0e-9 && (a[1] - b[1]).abs() < 1.0e-9
+    }
+
+    /// A view zoomed in off the middle of the lattice.
+    fn viewport() -> Viewport {
+        let mut viewport = Viewport::new((100, 50), (400.0, 300.0));
+        viewport.zoom = 3.0;
+        viewport.center = [30.0, 20.0];
+        viewport
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let viewport = viewport();
+        for &window in &[[0.0, 0.0], [123.5, 45.25], [399.0, 299.0]] {
+            let lattice = viewport.to_lattice(window).unwrap();
+            assert!(close(viewport.to_window(lattice), window));
+        }
+        for &lattice in &[[30.0, 20.0], [20.5, 13.75], [40.0, 25.0]] {
+            let window = viewport.to_window(lattice);
+            assert!(close(viewport.to_lattice(window).unwrap(), lattice));
+        }
+        assert_eq!(viewport.to_lattice([400.0, 10.0]), None);
+    }
+
+    #[test]
+    fn test_zoom_keeps_point_under_cursor() {
+        let mut viewport = viewport();
+        let cursor = [100.0, 250.0];
+        let before = viewport.to_lattice(cursor).unwrap();
+        viewport.zoom_at(1.5, cursor);
+        assert_eq!(viewport.zoom, 4.5);
+        assert!(close(viewport.to_lattice(cursor).unwrap(), before));
+        viewport.zoom_at(0.5, cursor);
+        assert!(close(viewport.to_lattice(cursor).unwrap(), before));
+    }
+
+    #[test]
+    fn test_pan_keeps_point_under_cursor() {
+        let mut viewport = viewport();
+        let (cursor, delta) = ([200.0, 100.0], [-40.0, 25.0]);
+        let before = viewport.to_lattice(cursor).unwrap();
+        viewport.pan(delta);
+        let after = [cursor[0] + delta[0], cursor[1] + delta[1]];
+        assert!(close(viewport.to_lattice(after).unwrap(), before));
+    }
+}
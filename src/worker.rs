@@ -0,0 +1,171 @@
+// -----------------------------------------------------------------------------
+
+use std;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use arrayfire as af;
+use image;
+use piston::input::{Event, Input};
+use super::panel::{Command, Controls, Plot};
+use super::viewport::Viewport;
+use super::display::{Drawable, Simulation, RGB};
+
+// -----------------------------------------------------------------------------
+
+/// What the display tells the worker.
+pub enum Message {
+    /// An input event, with the viewport it happened in. Only the input
+    /// itself is sent, as other events need not be `Send`.
+    Input(Input, Viewport),
+    Command(Command),
+    Quit,
+}
+
+/// A rendered frame of the simulation, with what the display shows beside
+/// it.
+pub struct Snapshot {
+    pub size:     (usize, usize),
+    /// Row-major RGBA.
+    pub pixels:   Vec<u8>,
+    pub controls: Option<Controls>,
+    pub plots:    Vec<Plot>,
+    pub finished: bool,
+    /// How many time steps were taken since the previous snapshot.
+    pub steps:    usize,
+}
+
+/// Runs a simulation on its own thread, stepping it as fast as it goes and
+/// rendering a snapshot at most every `interval`, so that the display never
+/// waits for the solver. The number of steps between snapshots adapts to the
+/// cost of a step.
+///
+/// The simulation is built on the worker thread, so it need not be `Send`;
+/// the ArrayFire backend and device of the spawning thread carry over.
+pub struct Worker {
+    messages:  mpsc::Sender<Message>,
+    snapshots: mpsc::Receiver<Snapshot>,
+    thread:    Option<std::thread::JoinHandle<()>>,
+}
+
+impl Worker {
+    pub fn spawn<S, F>(build: F, interval: Duration) -> Self
+        where S: Simulation, F: FnOnce() -> S + Send + 'static {
+        let (message_tx, message_rx) = mpsc::channel();
+        // Only one snapshot waits at a time: the worker does not render
+        // frames the display has no use for.
+        let (snapshot_tx, snapshot_rx) = mpsc::sync_channel(1);
+        let backend = af::get_active_backend();
+        let device = af::get_device();
+        let thread = std::thread::Builder::new()
+            .name("simulation".to_string())
+            .spawn(move || {
+                af::set_backend(backend);
+                af::set_device(device);
+                run(build(), message_rx, snapshot_tx, interval);
+            })
+            .expect("failed to spawn the simulation thread");
+        Worker { messages: message_tx, snapshots: snapshot_rx, thread: Some(thread) }
+    }
+
+    /// Returns whether the worker is still there to receive it.
+    pub fn send(&self, message: Message) -> bool {
+        self.messages.send(message).is_ok()
+    }
+
+    /// Block until the next snapshot, or nothing if the worker has stopped.
+    pub fn wait(&self) -> Option<Snapshot> {
+        self.snapshots.recv().ok()
+    }
+
+    /// The newest snapshot since the last call, if any.
+    pub fn latest(&self) -> Option<Snapshot> {
+        self.snapshots.try_iter().last()
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.send(Message::Quit);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                println!("The simulation thread panicked");
+            }
+        }
+    }
+}
+
+fn run<S: Simulation>(
+    mut simulation: S,
+    messages:       mpsc::Receiver<Message>,
+    snapshots:      mpsc::SyncSender<Snapshot>,
+    interval:       Duration,
+) {
+    let mut buffer: image::RgbaImage = Drawable::new((1, 1), RGB(0, 0, 0));
+    let mut pending: Option<Snapshot> = None;
+    // Whether anything changed since the last snapshot.
+    let mut dirty = true;
+    let mut steps = 0;
+    let mut last_frame: Option<Instant> = None;
+
+    loop {
+        let busy = !simulation.is_paused() && !simulation.is_finished();
+        let mut received = Vec::new();
+        if busy {
+            received.extend(messages.try_iter());
+        } else {
+            // Nothing to step, so wait for input or the next frame.
+            let elapsed = last_frame.map(|t| t.elapsed()).unwrap_or(interval);
+            let remaining = interval.checked_sub(elapsed).unwrap_or(Duration::from_millis(0));
+            match messages.recv_timeout(std::cmp::max(remaining, Duration::from_millis(1))) {
+                Ok(message) => received.push(message),
+                Err(mpsc::RecvTimeoutError::Timeout) => {},
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+            received.extend(messages.try_iter());
+        }
+        for message in received {
+            match message {
+                Message::Input(input, view) => simulation.handle(&Event::Input(input), &view),
+                Message::Command(command)   => simulation.command(command),
+                Message::Quit               => return,
+            }
+            dirty = true;
+        }
+
+        if busy {
+            simulation.step();
+            steps += 1;
+            dirty = true;
+        }
+
+        let due = last_frame.map(|t| t.elapsed() >= interval).unwrap_or(true);
+        if pending.is_none() && dirty && due {
+            let size = simulation.image_size();
+            if buffer.dimensions() != (size.0 as u32, size.1 as u32) {
+                buffer = Drawable::new((size.0 as u32, size.1 as u32), RGB(0, 0, 0));
+            }
+            simulation.render(&mut buffer);
+            pending = Some(Snapshot {
+                size:     size,
+                pixels:   buffer.get_pixels(),
+                controls: simulation.controls(),
+                plots:    simulation.plots(),
+                finished: simulation.is_finished(),
+                steps:    steps,
+            });
+            steps = 0;
+            dirty = false;
+            last_frame = Some(Instant::now());
+        }
+
+        if let Some(snapshot) = pending.take() {
+            match snapshots.try_send(snapshot) {
+                Ok(()) => {},
+                Err(mpsc::TrySendError::Full(snapshot)) => pending = Some(snapshot),
+                Err(mpsc::TrySendError::Disconnected(_)) => return,
+            }
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------